@@ -0,0 +1,279 @@
+use itertools::Itertools;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::Read;
+use std::path::Path;
+
+use crate::TurbineCsv;
+
+/// How alike two names must be, by Jaro-Winkler similarity, for a new name to
+/// be suggested as an alias of an existing one.
+const SUGGESTION_THRESHOLD: f64 = 0.9;
+
+/// A row of the aliases file. A row without a model gives another name for the
+/// manufacturer; a row with one gives another name for that model of the
+/// manufacturer, e.g.
+///
+/// ```text
+/// manufacturer,model,alias
+/// GE Wind,,GE Energy
+/// GE Wind,1.5sle,GE 1.5 SLE
+/// ```
+#[derive(Debug, Deserialize)]
+struct AliasRow {
+    manufacturer: String,
+    model: Option<String>,
+    alias: String,
+}
+
+/// The canonical manufacturer and model names, keyed by their aliases in
+/// lower case so that differences of case are ignored. Each canonical name
+/// is also an alias of itself, for the same reason.
+#[derive(Debug, Default)]
+pub struct Aliases {
+    manufacturers: HashMap<String, String>,
+    models: HashMap<(String, String), String>,
+    /// The (alias, manufacturer) pairs from the file, to store in the database.
+    manufacturer_aliases: Vec<(String, String)>,
+    /// The (manufacturer, alias, model) rows from the file, to store in the database.
+    model_aliases: Vec<(String, String, String)>,
+}
+
+impl Aliases {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    fn from_reader(reader: impl Read) -> Result<Self, Box<dyn Error>> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+
+        let mut aliases = Aliases::default();
+        for row in rdr.deserialize() {
+            let row: AliasRow = row?;
+            // A canonical name may also be the alias of another, which wins.
+            let manufacturer_key = row.manufacturer.to_lowercase();
+            aliases.manufacturers.entry(manufacturer_key.clone()).or_insert_with(|| row.manufacturer.clone());
+
+            match row.model.filter(|m| !m.is_empty()) {
+                Some(model) => {
+                    aliases.models.entry((manufacturer_key.clone(), model.to_lowercase())).or_insert_with(|| model.clone());
+                    aliases.models.insert((manufacturer_key, row.alias.to_lowercase()), model.clone());
+                    aliases.model_aliases.push((row.manufacturer, row.alias, model));
+                }
+                None => {
+                    aliases.manufacturers.insert(row.alias.to_lowercase(), row.manufacturer.clone());
+                    aliases.manufacturer_aliases.push((row.alias, row.manufacturer));
+                }
+            }
+        }
+
+        aliases.resolve_chains()?;
+        Ok(aliases)
+    }
+
+    /// Follows chains of aliases, such as GE Energy for GE for GE Wind, so
+    /// that each alias gives the name at the end of its chain.
+    fn resolve_chains(&mut self) -> Result<(), Box<dyn Error>> {
+        let manufacturers = self.manufacturers.keys()
+            .map(|key| Ok((key.clone(), resolve(&self.manufacturers, key, |name| name.to_lowercase())?)))
+            .collect::<Result<HashMap<_, _>, String>>()?;
+
+        // Model aliases given under an alias of the manufacturer belong to the
+        // canonical one, and again an alias wins over a canonical name.
+        let mut models = HashMap::new();
+        for ((manufacturer, alias), model) in self.models.drain() {
+            let key = (manufacturers[&manufacturer].to_lowercase(), alias);
+            if model.to_lowercase() != key.1 || !models.contains_key(&key) {
+                models.insert(key, model);
+            }
+        }
+        self.models = models.keys()
+            .map(|key| Ok((key.clone(), resolve(&models, key, |name| (key.0.clone(), name.to_lowercase()))?)))
+            .collect::<Result<_, String>>()?;
+
+        for (_, manufacturer) in &mut self.manufacturer_aliases {
+            *manufacturer = manufacturers[&manufacturer.to_lowercase()].clone();
+        }
+        for (manufacturer, _, model) in &mut self.model_aliases {
+            *manufacturer = manufacturers[&manufacturer.to_lowercase()].clone();
+            *model = self.models[&(manufacturer.to_lowercase(), model.to_lowercase())].clone();
+        }
+        self.manufacturers = manufacturers;
+
+        Ok(())
+    }
+
+    /// Replaces the turbine's manufacturer and model with their canonical
+    /// names. Returns true if either was replaced.
+    pub fn apply(&self, t: &mut TurbineCsv) -> bool {
+        let mut renamed = false;
+
+        if let Some(manufacturer) = self.manufacturers.get(&t.t_manu.to_lowercase()) {
+            renamed |= *manufacturer != t.t_manu;
+            t.t_manu = manufacturer.clone();
+        }
+        if let Some(model) = self.models.get(&(t.t_manu.to_lowercase(), t.t_model.to_lowercase())) {
+            renamed |= *model != t.t_model;
+            t.t_model = model.clone();
+        }
+
+        renamed
+    }
+
+    /// The canonical manufacturer names.
+    pub fn manufacturers(&self) -> impl Iterator<Item = &String> {
+        self.manufacturers.values().unique()
+    }
+
+    /// The (alias, manufacturer) pairs from the file.
+    pub fn manufacturer_aliases(&self) -> &[(String, String)] {
+        &self.manufacturer_aliases
+    }
+
+    /// The (manufacturer, alias, model) rows from the file.
+    pub fn model_aliases(&self) -> &[(String, String, String)] {
+        &self.model_aliases
+    }
+}
+
+/// The name at the end of the chain of aliases starting at `key`, where
+/// `key_of` gives the key of a name. Fails if the chain is a cycle.
+fn resolve<K: Clone + Eq + Hash>(names: &HashMap<K, String>, key: &K, key_of: impl Fn(&str) -> K) -> Result<String, String> {
+    let mut seen = vec![key.clone()];
+    let mut name = &names[key];
+    loop {
+        let next = key_of(name);
+        if seen.last() == Some(&next) {
+            return Ok(name.clone());
+        }
+        if seen.contains(&next) {
+            return Err(format!("The aliases file names {} as an alias of a name that is in turn an alias of it", name));
+        }
+        match names.get(&next) {
+            Some(alias_of) => name = alias_of,
+            None => return Ok(name.clone()),
+        }
+        seen.push(next);
+    }
+}
+
+/// The candidate most like `name`, if any is alike enough to suggest that
+/// `name` is another spelling of it.
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a String>) -> Option<&'a String> {
+    let name = name.to_lowercase();
+
+    candidates.into_iter()
+        .filter(|c| c.to_lowercase() != name)
+        .map(|c| (c, strsim::jaro_winkler(&name, &c.to_lowercase())))
+        .filter(|(_, similarity)| *similarity >= SUGGESTION_THRESHOLD)
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(c, _)| c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::iowa;
+
+    const ALIASES: &str = "\
+manufacturer,model,alias
+Vestas,,Vestas Wind Systems
+Vestas,V90,V-90
+GE Wind,1.5sle,GE 1.5 SLE
+";
+
+    fn aliases() -> Aliases {
+        Aliases::from_reader(ALIASES.as_bytes()).unwrap()
+    }
+
+    /// Applies the aliases to a turbine with the names given, returning the
+    /// names it ends up with.
+    fn apply(aliases: &Aliases, manufacturer: &str, model: &str) -> (String, String) {
+        let mut t = iowa(|t| { t.t_manu = manufacturer.to_string(); t.t_model = model.to_string(); });
+        aliases.apply(&mut t);
+        (t.t_manu, t.t_model)
+    }
+
+    fn names(manufacturer: &str, model: &str) -> (String, String) {
+        (manufacturer.to_string(), model.to_string())
+    }
+
+    #[test]
+    fn reads_manufacturer_and_model_aliases() {
+        let aliases = aliases();
+        assert_eq!(aliases.manufacturer_aliases(), &[("Vestas Wind Systems".to_string(), "Vestas".to_string())]);
+        assert_eq!(aliases.model_aliases(), &[
+            ("Vestas".to_string(), "V-90".to_string(), "V90".to_string()),
+            ("GE Wind".to_string(), "GE 1.5 SLE".to_string(), "1.5sle".to_string()),
+        ]);
+        assert_eq!(aliases.manufacturers().sorted().collect::<Vec<_>>(), vec!["GE Wind", "Vestas"]);
+    }
+
+    #[test]
+    fn applies_the_canonical_names_ignoring_case() {
+        let aliases = aliases();
+        assert_eq!(apply(&aliases, "VESTAS wind systems", "v-90"), names("Vestas", "V90"));
+        assert_eq!(apply(&aliases, "ge wind", "ge 1.5 sle"), names("GE Wind", "1.5sle"));
+        // A model alias belongs to its manufacturer.
+        assert_eq!(apply(&aliases, "Vestas", "GE 1.5 SLE"), names("Vestas", "GE 1.5 SLE"));
+    }
+
+    #[test]
+    fn canonical_names_are_not_renamed() {
+        let aliases = aliases();
+        let mut t = iowa(|_| {});
+        assert!(!aliases.apply(&mut t));
+        assert_eq!(apply(&aliases, "Siemens", "SWT-2.3-108"), names("Siemens", "SWT-2.3-108"));
+    }
+
+    #[test]
+    fn follows_chains_of_aliases() {
+        // GE is an alias of GE Wind with an alias of its own, and so is the
+        // model 1.5-77 of 1.5sle.
+        let file = "\
+manufacturer,model,alias
+GE,,GE Energy
+GE Wind,,GE
+GE,1.5-77,GE 1.5
+GE Wind,1.5sle,1.5-77
+";
+        let aliases = Aliases::from_reader(file.as_bytes()).unwrap();
+
+        assert_eq!(apply(&aliases, "GE Energy", "GE 1.5"), names("GE Wind", "1.5sle"));
+        assert_eq!(apply(&aliases, "GE", "1.5-77"), names("GE Wind", "1.5sle"));
+        assert_eq!(aliases.manufacturers().collect::<Vec<_>>(), vec!["GE Wind"]);
+        assert_eq!(aliases.manufacturer_aliases(), &[
+            ("GE Energy".to_string(), "GE Wind".to_string()),
+            ("GE".to_string(), "GE Wind".to_string()),
+        ]);
+        assert_eq!(aliases.model_aliases(), &[
+            ("GE Wind".to_string(), "GE 1.5".to_string(), "1.5sle".to_string()),
+            ("GE Wind".to_string(), "1.5-77".to_string(), "1.5sle".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn rejects_a_cycle_of_aliases() {
+        let file = "manufacturer,model,alias\nGE Wind,,GE\nGE,,GE Wind\n";
+        let err = Aliases::from_reader(file.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("in turn an alias of it"), "{}", err);
+    }
+
+    #[test]
+    fn suggests_the_most_similar_name() {
+        let names = vec!["Vestas".to_string(), "Vensys".to_string(), "Siemens".to_string()];
+        assert_eq!(suggest("Vestass", &names), Some(&names[0]));
+        assert_eq!(suggest("Nordex", &names), None);
+    }
+
+    #[test]
+    fn does_not_suggest_a_name_differing_only_in_case() {
+        let names = vec!["Vestas".to_string()];
+        assert_eq!(suggest("VESTAS", &names), None);
+    }
+}
@@ -0,0 +1,246 @@
+use log::warn;
+use repository::load::{HistoryEntry, TurbineRow};
+use repository::models::ChangeType;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::{parse_date, TurbineCsv};
+
+impl From<&TurbineCsv> for TurbineRow {
+    fn from(t: &TurbineCsv) -> Self {
+        TurbineRow {
+            case_id: t.case_id,
+            county_fips: t.t_fips,
+            project: t.p_name.clone(),
+            project_state: t.t_state.clone(),
+            project_year: t.p_year,
+            manufacturer: t.t_manu.clone(),
+            model: t.t_model.clone(),
+            raw_manufacturer: Some(t.raw_manu.clone()),
+            raw_model: Some(t.raw_model.clone()),
+            image_source: t.t_img_srce.clone(),
+            retrofit: t.retrofit != 0,
+            retrofit_year: t.retrofit_year,
+            attributes_confidence_level: t.t_conf_atr,
+            location_confidence_level: t.t_conf_loc,
+            image_date: t.t_img_date.as_deref().and_then(parse_date),
+            latitude: micro_degrees(t.ylat),
+            longitude: micro_degrees(t.xlong),
+            faa_ors: t.faa_ors.clone(),
+            faa_asn: t.faa_asn.clone(),
+            usgs_pr_id: t.usgs_pr_id,
+            eia_id: t.eia_id,
+            hub_height: t.t_hh.map(centimetres),
+            total_height_to_tip: t.t_ttlh.map(centimetres),
+        }
+    }
+}
+
+/// The attributes of a turbine recorded in its history, formatted for display.
+fn attributes(t: &TurbineRow) -> Vec<(&'static str, Option<String>)> {
+    vec![
+        ("CountyFips", Some(format!("{:05}", t.county_fips))),
+        ("Project", Some(format!("{}, {}", t.project, t.project_state))),
+        ("ProjectYear", t.project_year.map(|y| y.to_string())),
+        ("Model", Some(format!("{} {}", t.manufacturer, t.model))),
+        ("RawManufacturer", t.raw_manufacturer.clone()),
+        ("RawModel", t.raw_model.clone()),
+        ("ImageSource", Some(t.image_source.clone())),
+        ("Retrofit", Some(t.retrofit.to_string())),
+        ("RetrofitYear", t.retrofit_year.map(|y| y.to_string())),
+        ("AttributesConfidenceLevel", Some(t.attributes_confidence_level.to_string())),
+        ("LocationConfidenceLevel", Some(t.location_confidence_level.to_string())),
+        ("ImageDate", t.image_date.clone()),
+        ("Location", Some(format!("{:.6}, {:.6}",
+            t.latitude as f64 / 1_000_000.0, t.longitude as f64 / 1_000_000.0))),
+        ("FaaOrs", t.faa_ors.clone()),
+        ("FaaAsn", t.faa_asn.clone()),
+        ("UsgsPrId", t.usgs_pr_id.map(|id| id.to_string())),
+        ("EiaId", t.eia_id.map(|id| id.to_string())),
+        ("HubHeight", t.hub_height.map(|h| format!("{:.2}", h as f64 / 100.0))),
+        ("TotalHeightToTip", t.total_height_to_tip.map(|h| format!("{:.2}", h as f64 / 100.0))),
+    ]
+}
+
+/// An attribute that differs between two versions of a turbine, with its old
+/// and new values.
+pub type Difference = (&'static str, Option<String>, Option<String>);
+
+/// The attributes that differ between two versions of a turbine.
+pub fn differences(old: &TurbineRow, new: &TurbineRow) -> Vec<Difference> {
+    attributes(old).into_iter()
+        .zip(attributes(new))
+        .filter(|((_, old_value), (_, new_value))| old_value != new_value)
+        .map(|((attribute, old_value), (_, new_value))| (attribute, old_value, new_value))
+        .collect()
+}
+
+fn micro_degrees(d: f32) -> i64 {
+    (f64::from(d) * 1_000_000.0).round() as i64
+}
+
+fn centimetres(m: f32) -> i64 {
+    (f64::from(m) * 100.0).round() as i64
+}
+
+/// The difference between a batch of turbines from the CSV and the database,
+/// keyed on the USWTDB case_id, along with the history rows describing it.
+#[derive(Debug, Default)]
+pub struct TurbineChanges {
+    /// The new and changed turbines, to be written.
+    pub upserts: Vec<TurbineRow>,
+    pub new: usize,
+    pub changed: usize,
+    pub removed: Vec<i32>,
+    pub unchanged: usize,
+    pub history: Vec<HistoryEntry>,
+}
+
+impl TurbineChanges {
+    /// Compares a batch of turbines with their rows in the database. `seen`
+    /// collects the case_ids of every batch, so that duplicates are skipped
+    /// and the removals can be found once the whole CSV has been read.
+    pub fn compute(turbines: &[TurbineCsv], existing: &HashMap<i32, TurbineRow>, seen: &mut HashSet<i32>) -> Self {
+        let mut changes = TurbineChanges::default();
+
+        for t in turbines {
+            if !seen.insert(t.case_id) {
+                warn!("Ignoring duplicate case_id {} in the CSV", t.case_id);
+                continue;
+            }
+
+            let new = TurbineRow::from(t);
+            match existing.get(&t.case_id) {
+                None => {
+                    changes.upserts.push(new);
+                    changes.new += 1;
+                    changes.history.push(HistoryEntry::new(t.case_id, ChangeType::New));
+                }
+                Some(old) => {
+                    if *old == new {
+                        changes.unchanged += 1;
+                        continue;
+                    }
+
+                    let entries = differences(old, &new).into_iter()
+                        .map(|(attribute, old_value, new_value)| HistoryEntry {
+                            attribute: Some(attribute),
+                            old_value,
+                            new_value,
+                            ..HistoryEntry::new(t.case_id, ChangeType::Changed)
+                        });
+                    changes.history.extend(entries);
+                    changes.upserts.push(new);
+                    changes.changed += 1;
+                }
+            }
+        }
+
+        changes
+    }
+
+    /// The turbines that are in the database but were not seen in the CSV.
+    pub fn removals(existing: &HashSet<i32>, seen: &HashSet<i32>) -> Self {
+        let mut removed = existing.difference(seen).copied().collect::<Vec<_>>();
+        removed.sort_unstable();
+
+        let history = removed.iter()
+            .map(|case_id| HistoryEntry::new(*case_id, ChangeType::Decommissioned))
+            .collect();
+
+        TurbineChanges { removed, history, ..Default::default() }
+    }
+}
+
+/// The running totals of the changes made by a load.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChangeCounts {
+    pub new: usize,
+    pub changed: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+impl ChangeCounts {
+    pub fn add(&mut self, changes: &TurbineChanges) {
+        self.new += changes.new;
+        self.changed += changes.changed;
+        self.removed += changes.removed.len();
+        self.unchanged += changes.unchanged;
+    }
+}
+
+impl fmt::Display for ChangeCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} new, {} changed, {} decommissioned, {} unchanged",
+            self.new, self.changed, self.removed, self.unchanged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::iowa;
+
+    fn existing(turbines: &[TurbineCsv]) -> HashMap<i32, TurbineRow> {
+        turbines.iter().map(|t| (t.case_id, TurbineRow::from(t))).collect()
+    }
+
+    #[test]
+    fn new_turbine_is_inserted() {
+        let changes = TurbineChanges::compute(&[iowa(|_| {})], &HashMap::new(), &mut HashSet::new());
+
+        assert_eq!((changes.new, changes.changed, changes.unchanged), (1, 0, 0));
+        assert_eq!(changes.upserts.len(), 1);
+        assert_eq!(changes.history.len(), 1);
+        assert_eq!(changes.history[0].change_type, ChangeType::New);
+    }
+
+    #[test]
+    fn unchanged_turbine_is_not_written() {
+        let changes = TurbineChanges::compute(&[iowa(|_| {})], &existing(&[iowa(|_| {})]), &mut HashSet::new());
+
+        assert_eq!((changes.new, changes.changed, changes.unchanged), (0, 0, 1));
+        assert!(changes.upserts.is_empty());
+        assert!(changes.history.is_empty());
+    }
+
+    #[test]
+    fn changed_turbine_records_each_attribute() {
+        let new = iowa(|t| {
+            t.t_hh = Some(85.5);
+            t.eia_id = None;
+        });
+        let changes = TurbineChanges::compute(&[new], &existing(&[iowa(|_| {})]), &mut HashSet::new());
+
+        assert_eq!((changes.new, changes.changed, changes.unchanged), (0, 1, 0));
+        let history = changes.history.iter()
+            .map(|h| (h.change_type, h.attribute, h.old_value.as_deref(), h.new_value.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(history, vec![
+            (ChangeType::Changed, Some("EiaId"), Some("56291"), None),
+            (ChangeType::Changed, Some("HubHeight"), Some("80.00"), Some("85.50")),
+        ]);
+    }
+
+    #[test]
+    fn duplicate_case_id_is_skipped() {
+        let mut seen = HashSet::new();
+        let changes = TurbineChanges::compute(&[iowa(|_| {}), iowa(|t| t.t_hh = Some(90.0))], &HashMap::new(), &mut seen);
+
+        assert_eq!(changes.new, 1);
+        assert_eq!(changes.upserts[0].hub_height, Some(8000));
+        assert_eq!(seen, [3000001].iter().copied().collect());
+    }
+
+    #[test]
+    fn unseen_turbines_are_decommissioned() {
+        let existing = [3, 1, 2].iter().copied().collect();
+        let seen = [2].iter().copied().collect();
+        let changes = TurbineChanges::removals(&existing, &seen);
+
+        assert_eq!(changes.removed, vec![1, 3]);
+        assert!(changes.history.iter().all(|h| h.change_type == ChangeType::Decommissioned));
+        assert_eq!(changes.history.iter().map(|h| h.case_id).collect::<Vec<_>>(), vec![1, 3]);
+    }
+}
@@ -0,0 +1,36 @@
+use repository::plan::Plan;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+/// Tables with a row per turbine, which are summarised rather than listed;
+/// the JSON plan lists them in full.
+const SUMMARISED: &[&str] = &["Turbine", "TurbineHistory"];
+
+/// Prints the changes a dry run found, and writes them as JSON to `json` if given.
+pub fn report(plan: &Plan, json: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    println!("Dry run: nothing has been written. The load would make these changes:");
+    for (table, changes) in plan.iter().filter(|(_, changes)| !changes.is_empty()) {
+        println!("    {}: {}", table, changes);
+        if SUMMARISED.contains(table) {
+            continue;
+        }
+
+        let rows = changes.inserted.iter().map(|key| ('+', key))
+            .chain(changes.updated.iter().map(|key| ('~', key)))
+            .chain(changes.removed.iter().map(|key| ('-', key)));
+        for (change, key) in rows {
+            println!("        {} {}", change, key);
+        }
+    }
+    if plan.values().all(|changes| changes.is_empty()) {
+        println!("    None");
+    }
+
+    if let Some(path) = json {
+        serde_json::to_writer_pretty(File::create(path)?, plan)?;
+        println!("The plan has been written to {}", path.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use logging_timer::{finish, stimer};
+use repository::Repository;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::snapshot::{Snapshot, TurbineDetail};
+
+/// A turbine in the layout of the USWTDB CSV, the reverse of `TurbineCsv`.
+/// Missing values are left blank rather than written as -9999.
+#[derive(Debug, Serialize)]
+struct TurbineRecord<'a> {
+    case_id: i32,
+    faa_ors: Option<&'a str>,
+    faa_asn: Option<&'a str>,
+    usgs_pr_id: Option<i32>,
+    eia_id: Option<i32>,
+    t_state: &'a str,
+    t_county: &'a str,
+    t_fips: Option<i32>,
+    p_name: &'a str,
+    p_year: Option<i16>,
+    p_tnum: Option<i16>,
+    p_cap: Option<String>,
+    t_manu: &'a str,
+    t_model: &'a str,
+    t_cap: Option<i32>,
+    t_hh: Option<String>,
+    t_rd: Option<String>,
+    t_rsa: Option<String>,
+    t_ttlh: Option<String>,
+    retrofit: u8,
+    retrofit_year: Option<i16>,
+    t_conf_atr: u8,
+    t_conf_loc: u8,
+    t_img_date: Option<String>,
+    t_img_srce: &'a str,
+    xlong: String,
+    ylat: String,
+}
+
+impl<'a> TurbineRecord<'a> {
+    /// With `raw_names` the manufacturer and model are given as they were in
+    /// the loaded file, before aliases were applied.
+    fn new(d: &TurbineDetail<'a>, raw_names: bool) -> Self {
+        let t = d.turbine;
+        let (t_manu, t_model) = match (raw_names, &t.raw_manufacturer, &t.raw_model) {
+            (true, Some(manufacturer), Some(model)) => (manufacturer.as_str(), model.as_str()),
+            _ => (d.manufacturer.name.as_str(), d.model.name.as_str()),
+        };
+
+        TurbineRecord {
+            case_id: t.case_id,
+            faa_ors: t.faa_ors.as_deref(),
+            faa_asn: t.faa_asn.as_deref(),
+            usgs_pr_id: t.usgs_pr_id,
+            eia_id: t.eia_id,
+            t_state: &d.county.state_id,
+            t_county: &d.county.name,
+            t_fips: d.county.fips,
+            p_name: &d.project.name,
+            p_year: d.project.year,
+            p_tnum: d.project.num_turbines,
+            p_cap: number(d.project.capacity_mw),
+            t_manu,
+            t_model,
+            t_cap: d.model.capacity_kw,
+            t_hh: number(t.hub_height),
+            t_rd: number(d.model.rotor_diameter),
+            t_rsa: number(d.model.rotor_swept_area),
+            t_ttlh: number(t.total_height_to_tip),
+            retrofit: t.retrofit as u8,
+            retrofit_year: t.retrofit_year,
+            t_conf_atr: t.attributes_confidence_level.clone() as u8,
+            t_conf_loc: t.location_confidence_level.clone() as u8,
+            // The USWTDB writes dates as m/d/yyyy.
+            t_img_date: t.image_date.map(|date| date.format("%-m/%-d/%Y").to_string()),
+            t_img_srce: &d.image_source.name,
+            xlong: t.longitude.normalize().to_string(),
+            ylat: t.latitude.normalize().to_string(),
+        }
+    }
+}
+
+/// Formats a decimal without the trailing zeros added by the column's scale,
+/// so that 80.00 is written as 80, as in the USWTDB.
+fn number(value: Option<Decimal>) -> Option<String> {
+    value.map(|v| v.normalize().to_string())
+}
+
+/// Writes every turbine in the database to `output`, or to stdout if none is
+/// given, as a CSV in the USWTDB layout.
+pub async fn export(db: &mut dyn Repository, output: Option<&Path>, raw_names: bool) -> Result<(), Box<dyn Error>> {
+    let snapshot = Snapshot::read(db).await?;
+    let tmr = stimer!("EXPORT_TURBINES");
+
+    let out: Box<dyn Write> = match output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    let mut writer = csv::Writer::from_writer(out);
+
+    for detail in snapshot.details() {
+        writer.serialize(TurbineRecord::new(&detail?, raw_names))?;
+    }
+    writer.flush()?;
+
+    finish!(tmr, "Exported {} turbines", snapshot.turbines.len());
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use flate2::read::GzDecoder;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// How the turbines file is packaged, decided by its extension.
+enum Packaging {
+    Csv,
+    Zip,
+    Gzip,
+}
+
+fn packaging(path: &Path) -> Packaging {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("zip") => Packaging::Zip,
+        Some("gz") => Packaging::Gzip,
+        _ => Packaging::Csv,
+    }
+}
+
+/// Returns the name of the CSV file within `path`: the `uswtdb_v*.csv` entry of
+/// a zip, the name without `.gz` of a gzipped file, otherwise the file name itself.
+pub fn csv_name(path: &Path) -> Result<String, Box<dyn Error>> {
+    match packaging(path) {
+        Packaging::Zip => {
+            let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+            let index = find_csv_entry(&mut archive, path)?;
+            let entry = archive.by_index(index)?;
+            Ok(entry_file_name(entry.name()).to_string())
+        }
+        Packaging::Gzip => Ok(file_name(path.file_stem())),
+        Packaging::Csv => Ok(file_name(path.file_name())),
+    }
+}
+
+/// Calls `f` with a reader over the CSV data in `path`. Zipped and gzipped
+/// files are decompressed as they are read, nothing is extracted to disk.
+pub fn with_csv_reader<T, F>(path: &Path, f: F) -> Result<T, Box<dyn Error>>
+where
+    F: FnOnce(&mut dyn Read) -> Result<T, Box<dyn Error>>,
+{
+    let mut file = BufReader::new(File::open(path)?);
+
+    match packaging(path) {
+        Packaging::Csv => f(&mut file),
+        Packaging::Gzip => f(&mut GzDecoder::new(file)),
+        Packaging::Zip => {
+            let mut archive = ZipArchive::new(file)?;
+            let index = find_csv_entry(&mut archive, path)?;
+            let mut entry = archive.by_index(index)?;
+            f(&mut entry)
+        }
+    }
+}
+
+/// Finds the USWTDB CSV in the archive. If no entry is named `uswtdb_v*.csv`
+/// a lone CSV is accepted, whatever its name.
+fn find_csv_entry<R: Read + std::io::Seek>(archive: &mut ZipArchive<R>, path: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut csvs = Vec::new();
+    for index in 0..archive.len() {
+        let name = entry_file_name(archive.by_index(index)?.name()).to_ascii_lowercase();
+        if name.ends_with(".csv") {
+            if name.starts_with("uswtdb_v") {
+                return Ok(index);
+            }
+            csvs.push(index);
+        }
+    }
+
+    match csvs.as_slice() {
+        [index] => Ok(*index),
+        [] => Err(format!("{} does not contain a CSV file", path.display()).into()),
+        _ => Err(format!("{} contains several CSV files but none is named uswtdb_v*.csv", path.display()).into()),
+    }
+}
+
+/// Strips any directories from the name of a zip entry.
+fn entry_file_name(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+fn file_name(name: Option<&std::ffi::OsStr>) -> String {
+    name.map(|f| f.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    const CSV: &str = "case_id,t_state\n3000001,IA\n";
+
+    fn write_zip(path: &Path, entries: &[&str]) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for name in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(CSV.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn read(path: &Path) -> String {
+        with_csv_reader(path, |reader| {
+            let mut text = String::new();
+            reader.read_to_string(&mut text)?;
+            Ok(text)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_a_plain_csv() {
+        let dir = TempDir::new("input-csv");
+        let path = dir.write("uswtdb_v4_1_20210721.csv", CSV);
+
+        assert_eq!(csv_name(&path).unwrap(), "uswtdb_v4_1_20210721.csv");
+        assert_eq!(read(&path), CSV);
+    }
+
+    #[test]
+    fn reads_a_gzipped_csv() {
+        let dir = TempDir::new("input-gzip");
+        let path = dir.path("uswtdb_v4_1_20210721.csv.gz");
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        encoder.write_all(CSV.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        assert_eq!(csv_name(&path).unwrap(), "uswtdb_v4_1_20210721.csv");
+        assert_eq!(read(&path), CSV);
+    }
+
+    #[test]
+    fn reads_the_uswtdb_entry_of_a_zip() {
+        let dir = TempDir::new("input-zip");
+        let path = dir.path("uswtdbCSV.zip");
+        write_zip(&path, &["readme.csv", "data/uswtdb_v4_1_20210721.csv"]);
+
+        assert_eq!(csv_name(&path).unwrap(), "uswtdb_v4_1_20210721.csv");
+        assert_eq!(read(&path), CSV);
+    }
+
+    #[test]
+    fn accepts_a_lone_csv_in_a_zip() {
+        let dir = TempDir::new("input-lone");
+        let path = dir.path("turbines.zip");
+        write_zip(&path, &["turbines.csv"]);
+
+        assert_eq!(csv_name(&path).unwrap(), "turbines.csv");
+    }
+
+    #[test]
+    fn rejects_a_zip_without_one_csv() {
+        let dir = TempDir::new("input-ambiguous");
+        let several = dir.path("several.zip");
+        write_zip(&several, &["a.csv", "b.csv"]);
+        let none = dir.path("none.zip");
+        write_zip(&none, &["readme.txt"]);
+
+        assert!(csv_name(&several).unwrap_err().to_string().contains("several CSV files"));
+        assert!(csv_name(&none).unwrap_err().to_string().contains("does not contain a CSV file"));
+    }
+
+    #[test]
+    fn reads_a_zip_laid_out_as_published() {
+        let dir = TempDir::new("input-published");
+        let path = dir.path("uswtdbCSV.zip");
+        write_zip(&path, &["uswtdb_v4_1_20210721.csv"]);
+
+        assert_eq!(csv_name(&path).unwrap(), "uswtdb_v4_1_20210721.csv");
+        assert_eq!(read(&path), CSV);
+    }
+}
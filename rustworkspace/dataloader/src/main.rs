@@ -1,23 +1,198 @@
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use env_logger::Builder;
-use itertools::Itertools;
-use logging_timer::{executing, finish, stimer};
-use once_cell::sync::Lazy;
-use serde::Deserialize;
+use log::{error, info, warn};
+use logging_timer::{finish, stimer};
+use serde::{Deserialize, Deserializer};
 use std::error::Error;
+use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Instant;
 use structopt::StructOpt;
-use tiberius::{Client, ToSql};
-use tokio_util::compat::{Compat, TokioAsyncWriteCompatExt};
-use tokio::net::TcpStream;
+
+mod aliases;
+mod changes;
+mod dry_run;
+mod export;
+mod input;
+mod normalize;
+mod pipeline;
+mod release;
+mod report;
+mod retry;
+mod rows;
+mod snapshot;
+mod specs;
+mod stats;
+#[cfg(test)]
+mod testing;
+mod validation;
+mod verify;
+mod watch;
+
+use aliases::Aliases;
+use release::Release;
+use report::RunReport;
+use repository::plan::DryRun;
+use repository::dimension::{SyncCounts, Value};
+use repository::{Pool, Repository};
+use settings::{ConfigArgs, LoadSettings, Settings};
+use validation::{Progress, Quarantine};
 
 #[derive(StructOpt, Debug)]
 struct Opt {
+    #[structopt(flatten)]
+    config_args: ConfigArgs,
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Loads the US states file, the turbines file, or both.
+    Load(LoadOpt),
+    /// Loads each new USWTDB file that appears in a directory, then archives it.
+    Watch(WatchOpt),
+    /// Compares the turbines in the database with a USWTDB file and lists the differences.
+    Verify(VerifyOpt),
+    /// Writes the turbines in the database as a CSV in the USWTDB layout.
+    Export {
+        /// The file to write [default: stdout].
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+        /// Give manufacturers and models as named in the loaded file, before aliases were applied.
+        #[structopt(long)]
+        raw_names: bool,
+    },
+    /// Prints the number of turbines and their capacity by state, manufacturer and year.
+    Stats,
+    /// Creates the database schema, or upgrades it to the latest version.
+    Migrate {
+        /// Print the SQL of the pending migrations instead of applying them.
+        #[structopt(long)]
+        dry_run: bool,
+        /// Apply migrations that delete existing rows, such as the turbines loaded before they had case_ids.
+        #[structopt(long)]
+        allow_data_loss: bool,
+    },
+}
+
+#[derive(StructOpt, Debug, Clone)]
+struct LoadOpt {
     #[structopt(short, long, parse(from_os_str))]
     us_states_file: Option<PathBuf>,
+    /// The USWTDB file: a CSV, the zipped distribution or a .csv.gz.
     #[structopt(short, long, parse(from_os_str))]
-    turbines_file: Option<PathBuf>
+    turbines_file: Option<PathBuf>,
+    /// A CSV of other names for manufacturers and models (manufacturer,model,alias).
+    #[structopt(long, parse(from_os_str))]
+    aliases_file: Option<PathBuf>,
+    /// Where to write the rows that fail validation.
+    #[structopt(long, parse(from_os_str), default_value = "quarantine.csv")]
+    quarantine_file: PathBuf,
+    /// The load fails if more than this percentage of the rows in a file fail validation [default: 1].
+    #[structopt(long)]
+    max_rejected_percent: Option<f64>,
+    /// The number of turbines written to the database at a time [default: 2000].
+    #[structopt(long)]
+    batch_size: Option<usize>,
+    /// Print what the load would insert, update and delete in each table, without writing anything.
+    #[structopt(long)]
+    dry_run: bool,
+    /// Also write the plan of a dry run to this file as JSON. Implies --dry-run.
+    #[structopt(long, parse(from_os_str))]
+    plan_json: Option<PathBuf>,
+    /// Write a JSON report of the load to this file, overriding load.report_file.
+    #[structopt(long, parse(from_os_str))]
+    report_file: Option<PathBuf>,
+    /// Record the load in the LoadRun table, whether or not it succeeds.
+    #[structopt(long)]
+    record_run: bool,
+    /// Commit each batch with a checkpoint, so that the load can be resumed if it stops.
+    #[structopt(long)]
+    checkpoint: bool,
+    /// Continue the checkpointed load of the turbines file from its last checkpoint. Implies --checkpoint.
+    #[structopt(long)]
+    resume: bool,
+    /// The number of times to retry after a transient error such as a dropped connection [default: 3].
+    #[structopt(long)]
+    retries: Option<u32>,
+    /// The number of connections dimensions are written on at once; above 1 needs --commit-dimensions-early [default: 1].
+    #[structopt(long)]
+    dimension_parallelism: Option<usize>,
+    /// Allow dimension rows to be committed as they are written, so that a failed load keeps them.
+    #[structopt(long)]
+    commit_dimensions_early: bool,
+}
+
+impl LoadOpt {
+    fn is_dry_run(&self) -> bool {
+        self.dry_run || self.plan_json.is_some()
+    }
+
+    /// Applies the flags that override the configured settings.
+    fn override_settings(&self, settings: &mut LoadSettings) {
+        if let Some(max_rejected_percent) = self.max_rejected_percent {
+            settings.max_rejected_percent = max_rejected_percent;
+        }
+        if let Some(batch_size) = self.batch_size {
+            settings.batch_size = batch_size;
+        }
+        if let Some(report_file) = &self.report_file {
+            settings.report_file = Some(report_file.clone());
+        }
+        if self.record_run {
+            settings.record_runs = true;
+        }
+        if self.checkpoint || self.resume {
+            settings.checkpoint = true;
+        }
+        if self.resume {
+            settings.resume = true;
+        }
+        if let Some(retries) = self.retries {
+            settings.retries = retries;
+        }
+        if let Some(dimension_parallelism) = self.dimension_parallelism {
+            settings.dimension_parallelism = dimension_parallelism;
+        }
+        if self.commit_dimensions_early {
+            settings.commit_dimensions_early = true;
+        }
+    }
+}
+
+#[derive(StructOpt, Debug)]
+struct VerifyOpt {
+    /// The USWTDB file to compare with: a CSV, the zipped distribution or a .csv.gz.
+    #[structopt(short, long, parse(from_os_str))]
+    turbines_file: PathBuf,
+    /// The aliases file the database was loaded with.
+    #[structopt(long, parse(from_os_str))]
+    aliases_file: Option<PathBuf>,
+    /// Where to write the rows that fail validation.
+    #[structopt(long, parse(from_os_str), default_value = "quarantine.csv")]
+    quarantine_file: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+struct WatchOpt {
+    /// The directory new uswtdb*.zip and uswtdb*.csv files are downloaded to.
+    #[structopt(parse(from_os_str))]
+    dir: PathBuf,
+    /// Where to move each file once processed, into a loaded, failed or skipped subdirectory [default: <dir>/processed].
+    #[structopt(long, parse(from_os_str))]
+    archive_dir: Option<PathBuf>,
+    /// The seconds between scans of the directory.
+    #[structopt(long, default_value = "300")]
+    interval: u64,
+    /// Scan the directory once, then exit; fails if any file failed to load.
+    #[structopt(long)]
+    once: bool,
+    #[structopt(flatten)]
+    load: LoadOpt,
 }
 
 #[tokio::main]
@@ -25,13 +200,250 @@ async fn main() -> Result<(), Box<dyn Error>> {
     configure_logging();
     
     let opt = Opt::from_args();
-    if let Some(file) = opt.us_states_file {
-        let states = load_us_states_from_csv(file)?;
-        load_us_states_to_database(&states).await?;
+    let mut settings = Settings::load(&opt.config_args)?;
+    match &opt.cmd {
+        Some(Command::Load(load_opt)) => load_opt.override_settings(&mut settings.load),
+        Some(Command::Watch(watch_opt)) => {
+            watch_opt.load.override_settings(&mut settings.load);
+            // The outcome of each load is kept in the LoadRun table.
+            settings.load.record_runs = true;
+        }
+        _ => {}
+    }
+    if opt.config_args.print_config {
+        print!("{}", settings.to_redacted_toml()?);
+        return Ok(());
+    }
+
+    let connection_string = settings.connection_string();
+    match opt.cmd {
+        Some(Command::Load(load_opt)) => load_files(load_opt, &connection_string, &settings.load).await,
+        Some(Command::Watch(watch_opt)) => watch::watch(watch_opt, &connection_string, &settings.load).await,
+        Some(Command::Verify(verify_opt)) => {
+            let aliases = verify_opt.aliases_file.as_deref().map(Aliases::from_file).transpose()?.unwrap_or_default();
+            let quarantine = Quarantine::new(verify_opt.quarantine_file)?;
+            let mut db = repository::open(&connection_string).await?;
+            verify::verify(db.as_mut(), verify_opt.turbines_file, aliases, quarantine, &settings.load).await
+        }
+        Some(Command::Export { output, raw_names }) => {
+            let mut db = repository::open(&connection_string).await?;
+            export::export(db.as_mut(), output.as_deref(), raw_names).await
+        }
+        Some(Command::Stats) => {
+            let mut db = repository::open(&connection_string).await?;
+            stats::stats(db.as_mut()).await
+        }
+        Some(Command::Migrate { dry_run, allow_data_loss }) => migrate(&connection_string, dry_run, allow_data_loss).await,
+        None => {
+            Opt::clap().print_help()?;
+            println!();
+            Err("No command given".into())
+        }
+    }
+}
+
+/// Loads the files given to the load command in one transaction, or a batch at
+/// a time if checkpointing, then writes and records the report of the run as
+/// configured. A load that fails with a transient error is retried, resuming
+/// from its last checkpoint if it has one. A dry run is not reported.
+async fn load_files(opt: LoadOpt, connection_string: &str, load_settings: &LoadSettings) -> Result<(), Box<dyn Error>> {
+    if opt.us_states_file.is_none() && opt.turbines_file.is_none() {
+        return Err("Nothing to load: give --us-states-file, --turbines-file or both".into());
+    }
+
+    let mut db = retry::open(connection_string, load_settings).await?;
+    if opt.is_dry_run() {
+        let pending = repository::migrations::migrate(db.as_mut(), true, false).await?;
+        if !pending.is_empty() {
+            return Err(format!("{} migrations have not been applied; run the migrate command before a dry run", pending.len()).into());
+        }
+
+        // The load runs as usual, but against a repository that only records what it would write.
+        let settings = LoadSettings { checkpoint: false, resume: false, ..load_settings.clone() };
+        let mut dry_run = DryRun::new(db.as_mut());
+        load(&mut dry_run, None, &opt, &settings, None).await?;
+        return dry_run::report(&dry_run.into_plan(), opt.plan_json.as_deref());
+    }
+
+    migrate_sqlite(db.as_mut(), connection_string).await?;
+    let mut pool = open_dimension_pool(connection_string, load_settings).await?;
+    let mut report = RunReport::new(opt.us_states_file.as_deref(), opt.turbines_file.as_deref());
+    let mut settings = load_settings.clone();
+    let mut retries = 0;
+    let result = loop {
+        match load_in_transaction(db.as_mut(), pool.as_ref(), &opt, &settings, &mut report).await {
+            Err(err) if retry::is_transient(err.as_ref()) && retries < load_settings.retries => {
+                retries += 1;
+                let delay = retry::delay(load_settings, retries);
+                warn!("Retry {} of {} of the load in {:.1}s", retries, load_settings.retries, delay.as_secs_f64());
+                tokio::time::sleep(delay).await;
+
+                match retry::open(connection_string, load_settings).await {
+                    Ok(new_db) => db = new_db,
+                    Err(err) => break Err(err),
+                }
+                match open_dimension_pool(connection_string, load_settings).await {
+                    Ok(new_pool) => pool = new_pool,
+                    Err(err) => break Err(err),
+                }
+                // A checkpointed load continues from its last batch.
+                settings.resume = settings.checkpoint;
+                report.retry();
+            }
+            result => break result,
+        }
+    };
+
+    // Recorded after the commit or rollback, so that failed loads are kept.
+    report.finish(&result);
+    report.save(db.as_mut(), &settings).await;
+    result
+}
+
+/// Migrates a SQLite database. A SQLite database is created by its first
+/// load, so it is migrated then rather than by a separate migrate command.
+async fn migrate_sqlite(db: &mut dyn Repository, connection_string: &str) -> Result<(), Box<dyn Error>> {
+    if repository::sqlite_path(connection_string).is_some() {
+        for migration in repository::migrations::migrate(db, false, false).await? {
+            info!("Applied migration {} {}", migration.version, migration.name);
+        }
+    }
+    Ok(())
+}
+
+/// Opens the connections dimensions are written on, if they are to be written
+/// several at a time. SQLite allows one writer at a time, so a SQLite load
+/// writes its dimensions in its own transaction. Dimensions written on the
+/// pool are committed before the rest of the load, so it must be allowed.
+async fn open_dimension_pool(connection_string: &str, load_settings: &LoadSettings) -> Result<Option<Pool>, Box<dyn Error>> {
+    if load_settings.dimension_parallelism <= 1 {
+        return Ok(None);
     }
-    if let Some(file) = opt.turbines_file {
-        let turbines = load_turbines_from_csv(file)?;
-        load_all_csv_data_to_database(&turbines).await?;
+    if repository::sqlite_path(connection_string).is_some() {
+        warn!("SQLite allows one writer at a time, so dimensions are written on one connection");
+        return Ok(None);
+    }
+    if !load_settings.commit_dimensions_early {
+        return Err(format!("Dimensions written on {} connections are committed before the rest of the load, which keeps them if it fails; give --commit-dimensions-early to allow this",
+            load_settings.dimension_parallelism).into());
+    }
+
+    Ok(Some(repository::open_pool(connection_string, load_settings.dimension_parallelism).await?))
+}
+
+/// Runs the load in a transaction, committing it if the load succeeds and
+/// rolling it back if not. A checkpointed load commits its batches as it goes,
+/// so only the batch it was loading is rolled back. Dimensions written on a
+/// pool are committed as they are written, so are kept either way.
+async fn load_in_transaction(db: &mut dyn Repository, pool: Option<&Pool>, opt: &LoadOpt, load_settings: &LoadSettings,
+    report: &mut RunReport) -> Result<(), Box<dyn Error>> {
+    db.begin().await?;
+
+    match load(db, pool, opt, load_settings, Some(&mut *report)).await {
+        Ok(()) => {
+            db.commit().await?;
+            report.commit();
+            Ok(())
+        }
+        Err(err) => {
+            report.roll_back();
+            if load_settings.checkpoint {
+                error!("The load failed; the batches loaded before it have been kept, and it can be continued with --resume. Error: {}", err);
+            } else if pool.is_some() {
+                error!("The load failed and has been rolled back, apart from the dimension rows already written on the pool. Error: {}", err);
+            } else {
+                error!("The load failed and has been rolled back; the database still holds the previous data. Error: {}", err);
+            }
+
+            // If the connection was lost the server has already rolled back.
+            // Either way the load's error is the one to return, so that a
+            // transient one is retried.
+            if let Err(rollback_err) = db.rollback().await {
+                error!("Cannot roll back the load: {}", rollback_err);
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Reads the files and loads everything inside the transaction begun by the
+/// caller. The turbines file is streamed rather than read up front. Given a
+/// pool, the dimensions are written on it instead. A dry run has no report,
+/// and only logs the rows that fail validation.
+async fn load(db: &mut dyn Repository, pool: Option<&Pool>, opt: &LoadOpt, load_settings: &LoadSettings, mut report: Option<&mut RunReport>)
+    -> Result<(), Box<dyn Error>> {
+    let mut quarantine = if opt.is_dry_run() { Quarantine::discard() } else { Quarantine::new(opt.quarantine_file.clone())? };
+    let states = opt.us_states_file.clone()
+        .map(|f| load_us_states_from_csv(f, &mut quarantine, load_settings, report.as_deref_mut()))
+        .transpose()?;
+    let release = opt.turbines_file.as_deref().map(Release::from_file).transpose()?;
+    let aliases = opt.aliases_file.as_deref().map(Aliases::from_file).transpose()?;
+
+    if let Some(states) = states {
+        let rows = states.iter().map(rows::state_row).collect::<Vec<_>>();
+        let counts = match pool {
+            // Committed at once, as the counties and projects written on the pool refer to them.
+            Some(pool) => {
+                let mut pooled = pool.lock().await;
+                pooled.begin().await?;
+                match sync_states(&mut **pooled, &rows).await {
+                    Ok(counts) => {
+                        pooled.commit().await?;
+                        if let Some(report) = report.as_deref_mut() {
+                            report.add_committed(repository::dimension::STATE.table, counts);
+                        }
+                        counts
+                    }
+                    Err(err) => {
+                        let _ = pooled.rollback().await;
+                        return Err(err.into());
+                    }
+                }
+            }
+            None => {
+                let counts = sync_states(db, &rows).await?;
+                if let Some(report) = report.as_deref_mut() {
+                    report.add_written(repository::dimension::STATE.table, counts);
+                }
+                counts
+            }
+        };
+        println!("US states: {}", counts);
+    }
+    if let Some(release) = release {
+        if let Some(report) = report.as_deref_mut() {
+            report.set_release(&release);
+        }
+        pipeline::load_turbines(db, pool, &release, aliases, quarantine, load_settings, report).await?;
+    }
+
+    Ok(())
+}
+
+/// Syncs the states, then removes those not in the file, unless it is empty.
+async fn sync_states(db: &mut dyn Repository, rows: &[Vec<Value>]) -> Result<SyncCounts, repository::error::Error> {
+    let mut counts = db.sync_dimension(&repository::dimension::STATE, rows).await?;
+    // An empty states file is more likely a mistake than a wish to delete every state.
+    if !rows.is_empty() {
+        counts.removed = db.prune_dimension(&repository::dimension::STATE, rows).await?;
+    }
+    Ok(counts)
+}
+
+async fn migrate(connection_string: &str, dry_run: bool, allow_data_loss: bool) -> Result<(), Box<dyn Error>> {
+    let tmr = stimer!("MIGRATE");
+
+    let mut repo = repository::open(connection_string).await?;
+    let pending = repository::migrations::migrate(repo.as_mut(), dry_run, allow_data_loss).await?;
+
+    if dry_run {
+        for migration in &pending {
+            println!("-- Migration {} {}", migration.version, migration.name);
+            println!("{}", migration.sql);
+        }
+        finish!(tmr, "{} migrations are pending", pending.len());
+    } else {
+        finish!(tmr, "Applied {} migrations", pending.len());
     }
 
     Ok(())
@@ -79,85 +491,40 @@ impl UsState {
     fn area_in_square_km(&self) -> Option<i32> {
         self.area.map(|a| (a as f32 * 2.58999) as i32)
     }
+
+    /// Return the single letter code stored in the StateType column.
+    fn state_type_code(&self) -> String {
+        self.state_type.chars().nth(0).unwrap().to_ascii_uppercase().to_string()
+    }
 }
 
-fn load_us_states_from_csv(file: PathBuf) -> Result<Vec<UsState>, Box<dyn Error>> {
+fn load_us_states_from_csv(file: PathBuf, quarantine: &mut Quarantine, load_settings: &LoadSettings, mut report: Option<&mut RunReport>) -> Result<Vec<UsState>, Box<dyn Error>> {
     let tmr = stimer!("LOAD_US_STATES_FROM_CSV");
-    let mut rdr = csv::ReaderBuilder::new()
-        .trim(csv::Trim::All)
-        .from_path(file)?;
+    let start = Instant::now();
+    let source = file.display().to_string();
 
     let mut states = Vec::new();
-    for result in rdr.deserialize() {
-        let state: UsState = result?;
-        states.push(state);
+    let summary = validation::read_validated(&mut File::open(&file)?, &source, quarantine, &Progress::default(),
+        |state: &mut UsState| validation::check_state(state),
+        |state| { states.push(state); true })?;
+    if let Some(report) = report.as_deref_mut() {
+        report.rejected_rows += summary.rejected;
     }
+    summary.check(load_settings.max_rejected_percent)?;
 
     finish!(tmr, "Loaded {} US states from CSV", states.len());
-
-    Ok(states)
-}
-
-async fn load_us_states_to_database(states: &[UsState]) -> Result<(), Box<dyn Error>> {
-    let tmr = stimer!("LOAD_US_STATES_TO_DATABASE");
-
-    let mut client = open_ms_sql_connection().await?;
-    for state in states {
-        let stmt = "
-        BEGIN TRANSACTION;
-
-        UPDATE dbo.State WITH (UPDLOCK, SERIALIZABLE) SET Name = @P1, Capital = @P2, Population = @P3, AreaSquareKm = @P4, StateType = @P5
-        WHERE Id = @P6;
-
-        IF @@ROWCOUNT = 0 BEGIN
-            INSERT INTO dbo.State (Id, Name, Capital, Population, AreaSquareKm, StateType)
-            VALUES (@P6, @P1, @P2, @P3, @P4, @P5);
-        END
-
-        COMMIT TRANSACTION;
-        ";
-
-        let state_type = state.state_type.chars().nth(0).unwrap().to_ascii_uppercase().to_string();
-        let _result = client.execute(stmt, 
-            &[&state.name, &state.capital, &state.population, &state.area_in_square_km(), &state_type, &state.abbreviation]).await?;
+    if let Some(report) = report {
+        report.stages.add("LOAD_US_STATES_FROM_CSV", start);
     }
 
-    executing!(tmr, "Loaded {} US states into database", states.len());
-
-    let ids = states.iter()
-        .fold("".to_string(),
-        |a,b| if a.len() == 0 { format!("'{}'", b.abbreviation) } else { format!("{}, '{}'", a, b.abbreviation)});
-        
-    let stmt = format!("DELETE dbo.State WHERE Id NOT IN ({})", ids);
-    let result = client.execute(stmt, &[]).await?;
-    executing!(tmr, "Deleted extraneous {} US states from the database", result.rows_affected()[0]);
-
-    let row = client.simple_query("SELECT COUNT(*) FROM dbo.State").await?.into_row().await?.unwrap();
-    let num_states : i32 = row.get(0).unwrap();
-    finish!(tmr, "There are now {} US states in the database", num_states);
-
-    Ok(())
-}
-
-static CONN_STR: Lazy<String> = Lazy::new(|| {
-    std::env::var("MSSQL_CONNECTION_STRING").unwrap_or_else(|_| {
-        "server=tcp:localhost,1433;User Id=SA;Password=EawRsi2PCfurVZi7dym9;Initial Catalog=UsWindPowerStats;TrustServerCertificate=true".to_owned()
-    })
-});
-
-async fn open_ms_sql_connection() -> Result<Client<Compat<TcpStream>>, Box<dyn std::error::Error>> {
-    let config = tiberius::Config::from_ado_string(&CONN_STR)?;
-    let tcp = TcpStream::connect(config.get_addr()).await?;
-    tcp.set_nodelay(true)?;
-    let client = Client::connect(config, tcp.compat_write()).await?;
-    Ok(client)
+    Ok(states)
 }
 
 #[derive(Debug, Deserialize)]
 struct TurbineCsv {
     case_id: i32,
-    faa_ors: String,
-    faa_asn: String,
+    faa_ors: Option<String>,
+    faa_asn: Option<String>,
     usgs_pr_id: Option<i32>,
     eia_id: Option<i32>,
     t_state: String,
@@ -165,8 +532,9 @@ struct TurbineCsv {
     t_fips: i32,
     p_name: String,
     p_year: Option<i32>,
-    p_tnum: i32,
-    p_cap: Option<f32>,
+    p_tnum: Option<i32>,
+    #[serde(deserialize_with = "decimal_from_str")]
+    p_cap: Option<Decimal>,
     t_manu: String,
     t_model: String,
     t_cap: Option<i32>,
@@ -178,27 +546,15 @@ struct TurbineCsv {
     retrofit_year: Option<i32>,
     t_conf_atr: u8,
     t_conf_loc: u8,
-    t_img_date: String,
+    t_img_date: Option<String>,
     t_img_srce: String,
     xlong: f32,
     ylat: f32,
-}
-
-fn load_turbines_from_csv(file: PathBuf) -> Result<Vec<TurbineCsv>, Box<dyn Error>> {
-    let tmr = stimer!("LOAD_US_TURBINES_FROM_CSV");
-    let mut rdr = csv::ReaderBuilder::new()
-        .trim(csv::Trim::All)
-        .from_path(file)?;
-
-    let mut turbines = Vec::new();
-    for result in rdr.deserialize() {
-        let turbine: TurbineCsv = result?;
-        turbines.push(turbine);
-    }
-
-    finish!(tmr, "Loaded {} US turbines from CSV", turbines.len());
-    Ok(turbines)
-
+    /// The manufacturer and model as given in the file, before aliases are applied.
+    #[serde(skip)]
+    raw_manu: String,
+    #[serde(skip)]
+    raw_model: String,
 }
 
 /// An auxiliary type so we don't have to pass a huge tuple to the database load function.
@@ -215,7 +571,7 @@ struct Model<'a> {
 
 /// We consider models equivalent based on manufacturer and name only.
 /// This means we don't have to worry about the floats being only PartialEq.
-/// We assume things are named uniquely in the spreadsheet.
+/// Turbines of the same model can give different specifications; see `specs`.
 impl<'a> PartialEq for Model<'a> {
     fn eq(&self, other: &Self) -> bool {
         self.t_manu == other.t_manu && self.t_model == other.t_model
@@ -224,8 +580,35 @@ impl<'a> PartialEq for Model<'a> {
 
 impl<'a> Eq for Model<'a> {}
 
+/// An auxiliary type for loading projects. Projects are identified by name,
+/// state and year, as different projects can share a name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Project<'a> {
+    p_name: &'a String,
+    t_state: &'a String,
+    p_year: Option<i32>,
+    p_tnum: Option<i32>,
+    p_cap: Option<Decimal>,
+}
+
+impl<'a> Project<'a> {
+    fn key(&self) -> (String, String, Option<i32>) {
+        (self.p_name.clone(), self.t_state.clone(), self.p_year)
+    }
+}
+
 impl TurbineCsv {
-    fn to_model(&self) -> Model {
+    fn to_project(&self) -> Project<'_> {
+        Project {
+            p_name: &self.p_name,
+            t_state: &self.t_state,
+            p_year: self.p_year,
+            p_tnum: self.p_tnum,
+            p_cap: self.p_cap,
+        }
+    }
+
+    fn to_model(&self) -> Model<'_> {
         Model {
             t_manu: &self.t_manu,
             t_model: &self.t_model,
@@ -245,200 +628,14 @@ impl<'a> std::hash::Hash for Model<'a> {
     }
 }
 
-async fn load_all_csv_data_to_database(turbines: &[TurbineCsv]) -> Result<(), Box<dyn Error>> {
-    let _tmr = stimer!("LOAD_ALL_CSV_DATA_TO_DATABASE");
-
-    let mut client = open_ms_sql_connection().await?;
-
-    let counties = turbines.iter()
-        .map(|t| (&t.t_state, &t.t_county))
-        .unique()
-        .collect::<Vec<_>>();
-
-    load_counties_to_database(&mut client, counties).await?;
-
-    let manufacturers = turbines.iter()
-        .map(|t| &t.t_manu)
-        .unique()
-        .collect::<Vec<_>>();
-
-    load_manufacturers_to_database(&mut client, &manufacturers).await?;
-
-    let models = turbines.iter()
-        .map(|t| t.to_model())
-        .unique()
-        .collect::<Vec<_>>();
-
-    load_turbine_models_to_database(&mut client, &models).await?;
-
-    let image_sources = turbines.iter()
-        .map(|t| &t.t_img_srce)
-        .unique()
-        .collect::<Vec<_>>();
-
-    load_image_sources_to_database(&mut client, &image_sources).await?;
-
-    // Temporarily multiply all capacities by 1000 so that we can convert them to ints
-    // and hence use unique().
-    let projects = turbines.iter()
-        .map(|t| (&t.p_name, t.p_tnum, t.p_cap.map(|c| (c * 1000.0) as i32)))
-        .unique()
-        .map(|(nm, tn, cap)| (nm, tn, cap.map(|c| (c as f32) / 1000.0)))
-        .collect::<Vec<_>>();
-
-    load_projects_to_database(&mut client, &projects).await?;
-    load_turbines_to_database(&mut client, turbines).await?;
-
-    Ok(())
-}
-
-async fn load_counties_to_database(client: &mut Client<Compat<TcpStream>>, counties: Vec<(&String, &String)>) -> Result<(), Box<dyn Error>> {
-    let tmr = stimer!("LOAD_US_COUNTIES_TO_DATABASE");
-
-    for county in &counties {
-        let stmt = "
-        IF NOT EXISTS (SELECT 1 FROM dbo.County C2 WHERE C2.StateId = @P1 and C2.Name = @P2)
-        INSERT INTO dbo.County(StateId, Name)
-        VALUES (@P1, @P2)
-        ";
-
-        let _result = client.execute(stmt, 
-             &[county.0, county.1]).await?;
-    }
-
-    finish!(tmr, "Loaded {} US counties into the database", counties.len());
-    Ok(())
-}
-
-async fn load_manufacturers_to_database(client: &mut Client<Compat<TcpStream>>, manufacturers: &Vec<&String>) -> Result<(), Box<dyn Error>> {
-    let tmr = stimer!("LOAD_MANUFACTURERS_TO_DATABASE");
-
-    // We allow blank names. Easier than dealing with NULL.
-
-    for m in manufacturers {
-        let stmt = "
-        IF NOT EXISTS (SELECT 1 FROM dbo.Manufacturer M2 WHERE M2.Name = @P1)
-        INSERT INTO dbo.Manufacturer(Name)
-        VALUES (@P1)
-        ";
-        
-        let _result = client.execute(stmt, &[*m]).await?;
-    }
-
-    finish!(tmr, "Loaded {} manufacturers into the database", manufacturers.len());
-    Ok(())
-}
-
-async fn load_turbine_models_to_database<'a>(client: &mut Client<Compat<TcpStream>>, models: &Vec<Model<'a>>) -> Result<(), Box<dyn Error>> {
-    let tmr = stimer!("LOAD_TURBINE_MODELS_TO_DATABASE");
-
-    for model in models {
-        let stmt = "
-        EXEC dbo.model_upsert @P1, @P2, @P3, @P4, @P5, @P6, @P7
-        ";
-
-        let params: &[&dyn ToSql] = &[
-            model.t_manu,
-            model.t_model,
-            &model.t_cap,
-            &model.t_hh,
-            &model.t_rd,
-            &model.t_rsa,
-            &model.t_ttlh,
-        ];
-
-        let _result = client.execute(stmt, params).await?;
-    }
-
-    finish!(tmr, "Loaded {} turbine models into the database", models.len());
-    Ok(())
-}
-
-async fn load_image_sources_to_database(client: &mut Client<Compat<TcpStream>>, image_sources: &Vec<&String>) -> Result<(), Box<dyn Error>> {
-    let tmr = stimer!("LOAD_IMAGE_SOURCES_TO_DATABASE");
-
-    // We allow blank names. Easier than dealing with NULL.
-
-    for src in image_sources {
-        let stmt = "
-        IF NOT EXISTS (SELECT 1 FROM dbo.ImageSource S2 WHERE S2.Name = @P1)
-        INSERT INTO dbo.ImageSource(Name)
-        VALUES (@P1)
-        ";
-        
-        let _result = client.execute(stmt, &[*src]).await?;
-    }
-
-    finish!(tmr, "Loaded {} image sources into the database", image_sources.len());
-    Ok(())
-}
-
-async fn load_projects_to_database(client: &mut Client<Compat<TcpStream>>, projects: &[(&String, i32, Option<f32>)]) -> Result<(), Box<dyn Error>> {
-    let tmr = stimer!("LOAD_PROJECTS_TO_DATABASE");
-
-    for p in projects {
-        let stmt = "
-        BEGIN TRANSACTION;
-
-        UPDATE dbo.Project WITH (UPDLOCK, SERIALIZABLE) SET NumTurbines = @P1, CapacityMW = @P2
-        WHERE Name = @P3;
-
-        IF @@ROWCOUNT = 0 BEGIN
-            INSERT INTO dbo.Project(Name, NumTurbines, CapacityMW)
-            VALUES (@P3, @P1, @P2);
-        END
-
-        COMMIT TRANSACTION;
-        ";
-
-        let params: &[&dyn ToSql] = &[
-            &p.1,
-            &p.2,
-            p.0,
-        ];
-        
-        let _result = client.execute(stmt, params).await?;
-    }
-
-    finish!(tmr, "Loaded {} projects into the database", projects.len());
-    Ok(())
-}
-
-async fn load_turbines_to_database(client: &mut Client<Compat<TcpStream>>, turbines: &[TurbineCsv]) -> Result<(), Box<dyn Error>> {
-    let tmr = stimer!("LOAD_TURBINES_TO_DATABASE");
-
-    let stmt = "DELETE dbo.Turbine;";
-    let _result = client.execute(stmt, &[]).await?;
-
-    for (idx, t) in turbines.iter().enumerate() {
-        let stmt = "EXEC dbo.turbine_upsert @P1, @P2, @P3, @P4, @P5, @P6, @P7, @P8, @P9, @P10, @P11, @P12, @P13";
-        let image_date = parse_date(&t.t_img_date);
-
-        let params: &[&dyn ToSql] = &[
-            &t.t_state,
-            &t.t_county,
-            &t.p_name,
-            &t.t_manu,
-            &t.t_model,
-            &t.t_img_srce,
-            &t.retrofit,
-            &t.retrofit_year,
-            &t.t_conf_atr,
-            &t.t_conf_loc,
-            &image_date,
-            &t.ylat,
-            &t.xlong,
-        ];
-        
-        let _result = client.execute(stmt, params).await?;
-
-        if idx % 1000 == 0 {
-            executing!(tmr, "Loaded {} turbines into the database", idx);        
-        }
-    }
-
-    finish!(tmr, "Loaded {} turbines into the database", turbines.len());
-    Ok(())
+/// Reads a decimal from its text, so that capacities such as 1.62 MW are held
+/// exactly rather than as the nearest float.
+fn decimal_from_str<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Decimal>, D::Error> {
+    let text = Option::<String>::deserialize(deserializer)?;
+    text.as_deref()
+        .filter(|t| !t.is_empty())
+        .map(|t| Decimal::from_str(t).map_err(serde::de::Error::custom))
+        .transpose()
 }
 
 fn parse_date(d: &str) -> Option<String> {
@@ -454,3 +651,21 @@ fn parse_date(d: &str) -> Option<String> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn dimensions_are_only_written_on_a_pool_if_committing_them_early_is_allowed() {
+        let mssql = "server=tcp:localhost,1433;User Id=SA";
+        let settings = LoadSettings { dimension_parallelism: 4, ..LoadSettings::default() };
+        let err = open_dimension_pool(mssql, &settings).await.err().unwrap();
+        assert!(err.to_string().contains("give --commit-dimensions-early"), "{}", err);
+
+        // Neither opens a pool, so neither commits early.
+        assert!(open_dimension_pool("sqlite://uswps.db", &settings).await.unwrap().is_none());
+        let settings = LoadSettings { dimension_parallelism: 1, ..LoadSettings::default() };
+        assert!(open_dimension_pool(mssql, &settings).await.unwrap().is_none());
+    }
+}
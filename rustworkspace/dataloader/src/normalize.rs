@@ -0,0 +1,162 @@
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::TurbineCsv;
+
+/// The value USWTDB uses for a missing number, and in older releases for
+/// missing text too. Validation rejects it in the columns that cannot be missing.
+pub const MISSING: f64 = -9999.0;
+const MISSING_TEXT: &str = "-9999";
+
+/// The name given to a manufacturer, model, project or image source that is
+/// blank in the file, so that turbines without one still have a row to refer to.
+pub const UNKNOWN: &str = "Unknown";
+
+/// Replaces the USWTDB markers for missing values with None, or with UNKNOWN
+/// for names, and counts the replacements made in each column. The county
+/// FIPS code and location are required, so are left for validation to reject.
+#[derive(Debug, Default)]
+pub struct Normalizer {
+    missing: BTreeMap<&'static str, usize>,
+    unknown: BTreeMap<&'static str, usize>,
+}
+
+impl Normalizer {
+    /// Normalises the turbine's values, first keeping its manufacturer and
+    /// model as given in `raw_manu` and `raw_model`.
+    pub fn turbine(&mut self, t: &mut TurbineCsv) {
+        t.raw_manu = t.t_manu.clone();
+        t.raw_model = t.t_model.clone();
+
+        self.text("faa_ors", &mut t.faa_ors);
+        self.text("faa_asn", &mut t.faa_asn);
+        self.number("usgs_pr_id", &mut t.usgs_pr_id);
+        self.number("eia_id", &mut t.eia_id);
+        self.number("p_year", &mut t.p_year);
+        self.number("p_tnum", &mut t.p_tnum);
+        self.decimal("p_cap", &mut t.p_cap);
+        self.number("t_cap", &mut t.t_cap);
+        self.number("t_hh", &mut t.t_hh);
+        self.number("t_rd", &mut t.t_rd);
+        self.number("t_rsa", &mut t.t_rsa);
+        self.number("t_ttlh", &mut t.t_ttlh);
+        self.number("retrofit_year", &mut t.retrofit_year);
+        self.text("t_img_date", &mut t.t_img_date);
+
+        self.name("p_name", &mut t.p_name);
+        self.name("t_manu", &mut t.t_manu);
+        self.name("t_model", &mut t.t_model);
+        self.name("t_img_srce", &mut t.t_img_srce);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.unknown.is_empty()
+    }
+
+    fn number<N: Copy + Into<f64>>(&mut self, column: &'static str, value: &mut Option<N>) {
+        if matches!(*value, Some(v) if Into::<f64>::into(v) == MISSING) {
+            *value = None;
+            *self.missing.entry(column).or_default() += 1;
+        }
+    }
+
+    fn decimal(&mut self, column: &'static str, value: &mut Option<Decimal>) {
+        if *value == Some(Decimal::new(MISSING as i64, 0)) {
+            *value = None;
+            *self.missing.entry(column).or_default() += 1;
+        }
+    }
+
+    fn text(&mut self, column: &'static str, value: &mut Option<String>) {
+        if matches!(value.as_deref(), Some(v) if v.is_empty() || v == MISSING_TEXT) {
+            *value = None;
+            *self.missing.entry(column).or_default() += 1;
+        }
+    }
+
+    fn name(&mut self, column: &'static str, value: &mut String) {
+        if value.is_empty() || value == MISSING_TEXT {
+            *value = UNKNOWN.to_string();
+            *self.unknown.entry(column).or_default() += 1;
+        }
+    }
+}
+
+/// Lists the replacements, e.g. "t_hh 120 missing, t_manu 35 unknown".
+impl fmt::Display for Normalizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let missing = self.missing.iter().map(|(column, count)| format!("{} {} missing", column, count));
+        let unknown = self.unknown.iter().map(|(column, count)| format!("{} {} unknown", column, count));
+        write!(f, "{}", missing.chain(unknown).collect::<Vec<_>>().join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::turbine;
+
+    /// The Iowa turbine with every value that can be missing set to -9999 or left blank.
+    const MISSING_ROW: &str = "3000001,,-9999,-9999,-9999,IA,Franklin County,19069,-9999,-9999,-9999,-9999,,-9999,-9999,-9999,-9999,-9999,-9999,0,-9999,3,3,-9999,,-93.2,42.7";
+
+    fn normalized(row: &str) -> (TurbineCsv, Normalizer) {
+        let mut normalizer = Normalizer::default();
+        let mut t = turbine(row);
+        normalizer.turbine(&mut t);
+        (t, normalizer)
+    }
+
+    #[test]
+    fn leaves_a_complete_turbine_alone() {
+        let (t, normalizer) = normalized(crate::testing::IOWA);
+        assert!(normalizer.is_empty());
+        assert_eq!(t.p_tnum, Some(1));
+        assert_eq!(t.p_cap, Some(Decimal::new(15, 1)));
+        assert_eq!((t.t_manu.as_str(), t.raw_manu.as_str()), ("Vestas", "Vestas"));
+    }
+
+    #[test]
+    fn replaces_missing_numbers_and_text_with_none() {
+        let (t, _) = normalized(MISSING_ROW);
+        assert_eq!((t.faa_ors, t.faa_asn), (None, None));
+        assert_eq!((t.usgs_pr_id, t.eia_id), (None, None));
+        assert_eq!((t.p_year, t.p_tnum, t.p_cap), (None, None, None));
+        assert_eq!((t.t_cap, t.t_hh, t.t_rd, t.t_rsa, t.t_ttlh), (None, None, None, None, None));
+        assert_eq!((t.retrofit_year, t.t_img_date), (None, None));
+    }
+
+    #[test]
+    fn replaces_blank_and_missing_names_with_unknown() {
+        let (t, _) = normalized(MISSING_ROW);
+        assert_eq!(t.p_name, UNKNOWN);
+        assert_eq!(t.t_manu, UNKNOWN);
+        assert_eq!(t.t_model, UNKNOWN);
+        assert_eq!(t.t_img_srce, UNKNOWN);
+    }
+
+    #[test]
+    fn keeps_the_manufacturer_and_model_as_given() {
+        let (t, _) = normalized(MISSING_ROW);
+        assert_eq!(t.raw_manu, "");
+        assert_eq!(t.raw_model, "-9999");
+    }
+
+    #[test]
+    fn leaves_the_required_columns_for_validation() {
+        let (t, _) = normalized("3000001,,,,,IA,Franklin County,-9999,Crystal Lake,2008,1,1.5,Vestas,V90,1500,80,90,6362,125,0,,3,3,6/1/2019,NAIP,-9999,-9999");
+        assert_eq!(t.t_fips, -9999);
+        assert_eq!((t.xlong, t.ylat), (-9999.0, -9999.0));
+    }
+
+    #[test]
+    fn counts_the_replacements_in_each_column() {
+        let mut normalizer = Normalizer::default();
+        for row in &[MISSING_ROW, MISSING_ROW] {
+            normalizer.turbine(&mut turbine(row));
+        }
+        assert_eq!(normalizer.to_string(), "eia_id 2 missing, faa_asn 2 missing, p_cap 2 missing, p_tnum 2 missing, p_year 2 missing, \
+            retrofit_year 2 missing, t_cap 2 missing, t_hh 2 missing, t_img_date 2 missing, t_rd 2 missing, t_rsa 2 missing, \
+            t_ttlh 2 missing, usgs_pr_id 2 missing, p_name 2 unknown, t_img_srce 2 unknown, t_manu 2 unknown, t_model 2 unknown");
+    }
+}
@@ -0,0 +1,840 @@
+use itertools::Itertools;
+use log::{info, warn};
+use rust_decimal::Decimal;
+use logging_timer::{executing, finish, stimer};
+use repository::dimension::{self, Dimension, SyncCounts, Value};
+use repository::load::{LoadCheckpoint, ReleaseCounts};
+use repository::{Pool, Repository};
+use settings::LoadSettings;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::aliases::{self, Aliases};
+use crate::changes::{ChangeCounts, TurbineChanges};
+use crate::input;
+use crate::normalize::Normalizer;
+use crate::release::Release;
+use crate::report::{RunReport, Stages};
+use crate::rows;
+use crate::specs::ModelSpecs;
+use crate::validation::{self, Progress, Quarantine, ValidationSummary};
+use crate::TurbineCsv;
+
+/// The reader's half of the pipeline: validation results once the file is read.
+pub type ReaderHandle = JoinHandle<Result<ValidationSummary, String>>;
+
+/// A project's number of turbines and capacity.
+type ProjectSize = (Option<i32>, Option<Decimal>);
+
+/// Reads and validates the turbines file on a blocking thread, sending each
+/// good row down a bounded channel and writing bad ones to the quarantine file.
+/// Manufacturer and model names are replaced by their canonical names, and
+/// the rows read and rejected are counted in `progress` as the file is read.
+/// The channel capacity, with the batch size, bounds the memory used by a
+/// load however large the file.
+pub fn spawn_reader(file: PathBuf, states: HashSet<String>, aliases: Arc<Aliases>, mut quarantine: Quarantine,
+    progress: Arc<Progress>, channel_capacity: usize) -> (mpsc::Receiver<TurbineCsv>, ReaderHandle) {
+    let (tx, rx) = mpsc::channel(channel_capacity.max(1));
+
+    let handle = tokio::task::spawn_blocking(move || {
+        let source = file.display().to_string();
+        let mut normalizer = Normalizer::default();
+        let mut renamed = 0;
+
+        let summary = input::with_csv_reader(&file, |reader| {
+            validation::read_validated(reader, &source, &mut quarantine, &progress,
+                |t: &mut TurbineCsv| {
+                    // Missing values must be None before the checks, -9999 is not a height.
+                    normalizer.turbine(t);
+                    if aliases.apply(t) {
+                        renamed += 1;
+                    }
+                    validation::check_turbine(t, &states)
+                },
+                // A failed send means the writer has stopped, so the load has already failed.
+                |t| tx.blocking_send(t).is_ok())
+        })
+        .map_err(|err| format!("Cannot read {}: {}", source, err))?;
+
+        if !normalizer.is_empty() {
+            info!("Normalised values in {}: {}", source, normalizer);
+        }
+        if renamed > 0 {
+            info!("Renamed the manufacturer or model of {} rows in {} from the aliases file", renamed, source);
+        }
+        Ok(summary)
+    });
+
+    (rx, handle)
+}
+
+/// Receives up to `batch_size` turbines. Returns None once the file is exhausted.
+pub async fn next_batch(rx: &mut mpsc::Receiver<TurbineCsv>, batch_size: usize) -> Option<Vec<TurbineCsv>> {
+    let mut batch = Vec::with_capacity(batch_size);
+
+    while batch.len() < batch_size.max(1) {
+        match rx.recv().await {
+            Some(turbine) => batch.push(turbine),
+            None => break,
+        }
+    }
+
+    if batch.is_empty() { None } else { Some(batch) }
+}
+
+/// The rows of each dimension in a batch that no earlier batch had.
+#[derive(Debug, Default)]
+struct NewRows {
+    counties: Vec<Vec<Value>>,
+    manufacturers: Vec<Vec<Value>>,
+    models: Vec<Vec<Value>>,
+    image_sources: Vec<Vec<Value>>,
+    projects: Vec<Vec<Value>>,
+}
+
+impl NewRows {
+    /// The rows of each dimension, in an order that syncs referenced rows first.
+    fn in_order(&self) -> Vec<(&'static Dimension, &[Vec<Value>])> {
+        vec![
+            (&dimension::COUNTY, &self.counties[..]),
+            (&dimension::MANUFACTURER, &self.manufacturers[..]),
+            (&dimension::MODEL, &self.models[..]),
+            (&dimension::IMAGE_SOURCE, &self.image_sources[..]),
+            (&dimension::PROJECT, &self.projects[..]),
+        ]
+    }
+}
+
+/// The dimension values already written by earlier batches. These grow with
+/// the number of distinct counties, models etc. rather than with the number
+/// of turbines.
+#[derive(Debug, Default)]
+struct SeenDimensions {
+    /// The (state, name) each county was loaded with, keyed by FIPS code.
+    counties: HashMap<i32, (String, String)>,
+    /// The reverse of `counties`, to find names used for two FIPS codes.
+    county_fips: HashMap<(String, String), i32>,
+    /// The number of turbines and capacity each project was loaded with.
+    projects: HashMap<(String, String, Option<i32>), ProjectSize>,
+    manufacturers: HashSet<String>,
+    /// The specifications each turbine gave its model, keyed by (manufacturer, name).
+    models: HashMap<(String, String), ModelSpecs>,
+    image_sources: HashSet<String>,
+    /// Descriptions of the rows whose county or project disagrees with an
+    /// earlier row.
+    conflicts: BTreeSet<String>,
+    /// The changes made to each dimension table, keyed by table name.
+    counts: BTreeMap<&'static str, SyncCounts>,
+    /// The changes not yet added to the report: those committed as they were
+    /// written on the pool, and those written in the load's transaction.
+    committed: BTreeMap<&'static str, SyncCounts>,
+    written: BTreeMap<&'static str, SyncCounts>,
+    /// The time taken to write the dimensions of each batch on the pool, and
+    /// to write the same rows one table at a time on a single connection.
+    concurrent_time: Duration,
+    sequential_time: Duration,
+}
+
+impl SeenDimensions {
+    /// Syncs the rows on the pool if there is one, else in the load's transaction.
+    async fn sync(&mut self, db: &mut dyn Repository, pool: Option<&Pool>, dimension: &Dimension, rows: &[Vec<Value>],
+        stages: &mut Stages) -> Result<(), Box<dyn Error>> {
+        let synced = match pool {
+            Some(pool) => sync_pooled(pool, dimension, rows).await?,
+            None => sync_rows(db, dimension, rows).await?,
+        };
+        self.record(dimension, synced, pool.is_some(), stages);
+        Ok(())
+    }
+
+    fn record(&mut self, dimension: &Dimension, synced: Option<(SyncCounts, Duration)>, pooled: bool, stages: &mut Stages) {
+        if let Some((counts, time)) = synced {
+            stages.add_time("SYNC_DIMENSION", time);
+            self.count(dimension.table, counts, pooled);
+        }
+    }
+
+    fn count(&mut self, table: &'static str, counts: SyncCounts, pooled: bool) {
+        self.counts.entry(table).or_default().add(counts);
+        let unreported = if pooled { &mut self.committed } else { &mut self.written };
+        unreported.entry(table).or_default().add(counts);
+    }
+
+    /// Adds the changes made since the last call to the report.
+    fn report(&mut self, report: Option<&mut RunReport>) {
+        let committed = std::mem::take(&mut self.committed);
+        let written = std::mem::take(&mut self.written);
+        if let Some(report) = report {
+            for (table, counts) in committed {
+                report.add_committed(table, counts);
+            }
+            for (table, counts) in written {
+                report.add_written(table, counts);
+            }
+        }
+    }
+
+    /// Writes the canonical manufacturers and the aliases of manufacturers
+    /// and models, removing the aliases no longer in the file.
+    async fn load_aliases(&mut self, db: &mut dyn Repository, pool: Option<&Pool>, aliases: &Aliases, stages: &mut Stages)
+        -> Result<(), Box<dyn Error>> {
+        let manufacturers = aliases.manufacturers().map(|m| rows::name_row(m)).collect::<Vec<_>>();
+        self.sync(db, pool, &dimension::MANUFACTURER, &manufacturers, stages).await?;
+        let manufacturer_aliases = aliases.manufacturer_aliases().iter()
+            .map(|(alias, manufacturer)| rows::manufacturer_alias_row(alias, manufacturer))
+            .collect::<Vec<_>>();
+        self.sync(db, pool, &dimension::MANUFACTURER_ALIAS, &manufacturer_aliases, stages).await?;
+        let model_aliases = aliases.model_aliases().iter()
+            .map(|(manufacturer, alias, model)| rows::model_alias_row(manufacturer, alias, model))
+            .collect::<Vec<_>>();
+        self.sync(db, pool, &dimension::MODEL_ALIAS, &model_aliases, stages).await?;
+
+        for (dimension, loaded) in &[(&dimension::MANUFACTURER_ALIAS, &manufacturer_aliases), (&dimension::MODEL_ALIAS, &model_aliases)] {
+            let removed = db.prune_dimension(dimension, loaded).await?;
+            self.count(dimension.table, SyncCounts { removed, ..SyncCounts::default() }, false);
+        }
+
+        Ok(())
+    }
+
+    /// Removes the dimension rows no turbine refers to any more.
+    async fn prune(&mut self, db: &mut dyn Repository) -> Result<(), Box<dyn Error>> {
+        for dimension in dimension::PRUNED_BY_TURBINES {
+            let removed = db.prune_dimension(dimension, &[]).await?;
+            self.count(dimension.table, SyncCounts { removed, ..SyncCounts::default() }, false);
+        }
+        Ok(())
+    }
+
+    /// Writes the dimension values in the batch that have not been seen before,
+    /// on the pool if there is one. On the pool they are first written one
+    /// table at a time on a single connection and rolled back, timing the
+    /// writes the pool is to speed up.
+    async fn load(&mut self, db: &mut dyn Repository, pool: Option<&Pool>, batch: &[TurbineCsv], stages: &mut Stages)
+        -> Result<(), Box<dyn Error>> {
+        let rows = self.new_rows(batch);
+
+        match pool {
+            Some(pool) => {
+                let start = Instant::now();
+                self.sequential_time += time_sequentially(pool, &rows).await?;
+                stages.add("TIME_DIMENSIONS_SEQUENTIALLY", start);
+
+                let start = Instant::now();
+                self.sync_concurrently(pool, &rows, stages).await?;
+                self.concurrent_time += start.elapsed();
+                stages.add("LOAD_DIMENSIONS", start);
+            }
+            None => {
+                let start = Instant::now();
+                for (dimension, rows) in rows.in_order() {
+                    self.sync(db, None, dimension, rows, stages).await?;
+                }
+                stages.add("LOAD_DIMENSIONS", start);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes each dimension on a connection of its own, except that models
+    /// refer to their manufacturer so are written once it has been. Each
+    /// table is committed as it is written, so those written are counted even
+    /// if another fails.
+    async fn sync_concurrently(&mut self, pool: &Pool, rows: &NewRows, stages: &mut Stages) -> Result<(), Box<dyn Error>> {
+        let (counties, (manufacturers, models), image_sources, projects) = tokio::join!(
+            sync_pooled(pool, &dimension::COUNTY, &rows.counties),
+            async {
+                match sync_pooled(pool, &dimension::MANUFACTURER, &rows.manufacturers).await {
+                    Ok(manufacturers) => (Ok(manufacturers), sync_pooled(pool, &dimension::MODEL, &rows.models).await),
+                    Err(err) => (Err(err), Ok(None)),
+                }
+            },
+            sync_pooled(pool, &dimension::IMAGE_SOURCE, &rows.image_sources),
+            sync_pooled(pool, &dimension::PROJECT, &rows.projects),
+        );
+
+        let mut result = Ok(());
+        let synced = vec![(&dimension::COUNTY, counties), (&dimension::MANUFACTURER, manufacturers), (&dimension::MODEL, models),
+            (&dimension::IMAGE_SOURCE, image_sources), (&dimension::PROJECT, projects)];
+        for (dimension, synced) in synced {
+            match synced {
+                Ok(synced) => self.record(dimension, synced, true, stages),
+                Err(err) if result.is_ok() => result = Err(err.into()),
+                Err(_) => {}
+            }
+        }
+        result
+    }
+
+    /// How many times faster the dimensions were written on the pool than
+    /// one table at a time on a single connection.
+    fn speedup(&self) -> Option<f64> {
+        if self.concurrent_time.is_zero() {
+            None
+        } else {
+            Some(self.sequential_time.as_secs_f64() / self.concurrent_time.as_secs_f64())
+        }
+    }
+
+    /// Finds the dimension values in the batch that have not been seen before,
+    /// noting any that conflict with earlier rows.
+    fn new_rows(&mut self, batch: &[TurbineCsv]) -> NewRows {
+        let mut counties = Vec::new();
+        for (state, name, fips) in batch.iter().map(|t| (&t.t_state, &t.t_county, t.t_fips)).unique() {
+            if let Some((loaded_state, loaded_name)) = self.counties.get(&fips) {
+                if loaded_state != state || loaded_name != name {
+                    self.conflict(format!("County FIPS {:05} is both '{}, {}' and '{}, {}'; using the first",
+                        fips, loaded_name, loaded_state, name, state));
+                }
+                continue;
+            }
+
+            // County names are unique within a state, so a name used for two
+            // FIPS codes is loaded the second time with the code appended.
+            let mut key = (state.clone(), name.clone());
+            if let Some(other) = self.county_fips.get(&key) {
+                self.conflict(format!("County '{}, {}' has FIPS codes {:05} and {:05}; loading the second as '{} ({:05})'",
+                    name, state, other, fips, name, fips));
+                key.1 = format!("{} ({:05})", name, fips);
+            }
+
+            self.county_fips.insert(key.clone(), fips);
+            self.counties.insert(fips, key.clone());
+            counties.push(rows::county_row(&key.0, &key.1, fips));
+        }
+
+        let manufacturers = batch.iter()
+            .map(|t| &t.t_manu)
+            .unique()
+            .filter(|m| self.manufacturers.insert((*m).clone()))
+            .map(|m| rows::name_row(m))
+            .collect::<Vec<_>>();
+
+        // A model is loaded with the values of its first turbine, and given
+        // the consensus of all of them once the whole file has been read.
+        let mut models = Vec::new();
+        for model in batch.iter().map(|t| t.to_model()) {
+            self.models.entry((model.t_manu.clone(), model.t_model.clone()))
+                .or_insert_with(|| {
+                    models.push(rows::model_row(&model));
+                    ModelSpecs::default()
+                })
+                .add(&model);
+        }
+
+        let image_sources = batch.iter()
+            .map(|t| &t.t_img_srce)
+            .unique()
+            .filter(|i| self.image_sources.insert((*i).clone()))
+            .map(|i| rows::name_row(i))
+            .collect::<Vec<_>>();
+
+        let mut projects = Vec::new();
+        for project in batch.iter().map(|t| t.to_project()).unique() {
+            let key = project.key();
+            if let Some(&(tnum, cap)) = self.projects.get(&key) {
+                if tnum != project.p_tnum || cap != project.p_cap {
+                    self.conflict(format!("Project '{}' in {} ({}) has both {} turbines of {} MW and {} turbines of {} MW; using the first",
+                        key.0, key.1, year_text(key.2), count_text(tnum), capacity_text(cap), count_text(project.p_tnum), capacity_text(project.p_cap)));
+                }
+                continue;
+            }
+
+            self.projects.insert(key, (project.p_tnum, project.p_cap));
+            projects.push(rows::project_row(&project));
+        }
+
+        NewRows { counties, manufacturers, models, image_sources, projects }
+    }
+
+    /// Updates the models whose consensus specifications differ from the ones
+    /// they were loaded with.
+    async fn load_consensus_models(&mut self, db: &mut dyn Repository) -> Result<(), Box<dyn Error>> {
+        let models = self.models.iter()
+            .filter(|(_, specs)| specs.differs_from_first())
+            .map(|((manufacturer, name), specs)| rows::model_row(&specs.consensus(manufacturer, name)))
+            .collect::<Vec<_>>();
+
+        // These rows were inserted by this load, so count as updated only once.
+        if !models.is_empty() {
+            let counts = db.sync_dimension(&dimension::MODEL, &models).await?;
+            self.count(dimension::MODEL.table, SyncCounts { updated: counts.updated, ..SyncCounts::default() }, false);
+        }
+
+        Ok(())
+    }
+
+    /// Describes each model whose turbines disagree on its specifications,
+    /// ordered by manufacturer and name.
+    fn spec_conflicts(&self) -> Vec<String> {
+        self.models.iter()
+            .filter_map(|((manufacturer, name), specs)| specs.conflicts()
+                .map(|conflicts| (manufacturer, name, conflicts)))
+            .sorted()
+            .map(|(manufacturer, name, conflicts)| format!("{} {}: {}", manufacturer, name, conflicts))
+            .collect()
+    }
+
+    /// Suggests an existing name for each new manufacturer and model that is
+    /// spelled much like one, as the new name may need adding to the aliases file.
+    fn alias_suggestions(&self, aliases: &Aliases, manufacturers: &HashSet<String>, models: &HashSet<(String, String)>) -> Vec<String> {
+        let known_manufacturers = manufacturers.iter()
+            .chain(aliases.manufacturers())
+            .chain(self.manufacturers.iter())
+            .unique()
+            .collect::<Vec<_>>();
+
+        // The canonical names in the aliases file are loaded first, so are not new.
+        let canonical = aliases.manufacturers().collect::<HashSet<_>>();
+        let mut suggestions = self.manufacturers.iter()
+            .filter(|m| !manufacturers.contains(*m) && !canonical.contains(m))
+            .filter_map(|m| aliases::suggest(m, known_manufacturers.iter().copied())
+                .map(|like| format!("Manufacturer '{}' is like '{}'", m, like)))
+            .collect::<Vec<_>>();
+
+        for (manufacturer, model) in self.models.keys().filter(|m| !models.contains(*m)) {
+            let known_models = models.iter()
+                .chain(self.models.keys())
+                .filter(|(m, _)| m == manufacturer)
+                .map(|(_, name)| name);
+
+            if let Some(like) = aliases::suggest(model, known_models) {
+                suggestions.push(format!("Model '{} {}' is like '{} {}'", manufacturer, model, manufacturer, like));
+            }
+        }
+
+        suggestions.sort();
+        suggestions
+    }
+
+    fn conflict(&mut self, conflict: String) {
+        if self.conflicts.insert(conflict.clone()) {
+            warn!("Conflict: {}", conflict);
+        }
+    }
+}
+
+/// Syncs the rows of a dimension, if there are any, giving the changes made
+/// and the time taken.
+async fn sync_rows(db: &mut dyn Repository, dimension: &Dimension, rows: &[Vec<Value>])
+    -> Result<Option<(SyncCounts, Duration)>, repository::error::Error> {
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let tmr = stimer!("SYNC_DIMENSION");
+    let start = Instant::now();
+    let counts = db.sync_dimension(dimension, rows).await?;
+    finish!(tmr, "Synced {} {} rows: {}", rows.len(), dimension.table, counts);
+    Ok(Some((counts, start.elapsed())))
+}
+
+/// Writes the rows one table at a time on a connection from the pool, as a
+/// load without a pool would, giving the time taken. The writes are rolled
+/// back, to be made again on the pool.
+async fn time_sequentially(pool: &Pool, rows: &NewRows) -> Result<Duration, Box<dyn Error>> {
+    let mut db = pool.lock().await;
+    db.begin().await?;
+
+    let start = Instant::now();
+    let written = async {
+        for (dimension, rows) in rows.in_order() {
+            if !rows.is_empty() {
+                db.sync_dimension(dimension, rows).await?;
+            }
+        }
+        Ok::<_, repository::error::Error>(start.elapsed())
+    }.await;
+
+    // The write's error is the one worth reporting.
+    let rolled_back = db.rollback().await;
+    let elapsed = written?;
+    rolled_back?;
+    Ok(elapsed)
+}
+
+/// Syncs the rows of a dimension on a connection from the pool, committing
+/// them at once so that the other connections can see them.
+async fn sync_pooled(pool: &Pool, dimension: &Dimension, rows: &[Vec<Value>])
+    -> Result<Option<(SyncCounts, Duration)>, repository::error::Error> {
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let mut db = pool.lock().await;
+    db.begin().await?;
+    match sync_rows(&mut **db, dimension, rows).await {
+        Ok(synced) => {
+            db.commit().await?;
+            Ok(synced)
+        }
+        Err(err) => {
+            // The sync's error is the one worth reporting.
+            let _ = db.rollback().await;
+            Err(err)
+        }
+    }
+}
+
+fn year_text(year: Option<i32>) -> String {
+    year.map_or_else(|| "no year".to_string(), |y| y.to_string())
+}
+
+fn count_text(count: Option<i32>) -> String {
+    count.map_or_else(|| "an unknown number of".to_string(), |c| c.to_string())
+}
+
+fn capacity_text(capacity: Option<Decimal>) -> String {
+    capacity.map_or_else(|| "unknown".to_string(), |c| c.to_string())
+}
+
+/// Writes the turbine changes and their history.
+async fn apply_changes(db: &mut dyn Repository, release_id: i32, changes: &TurbineChanges, stages: &mut Stages) -> Result<(), Box<dyn Error>> {
+    let tmr = stimer!("APPLY_TURBINE_CHANGES_TO_DATABASE");
+    let start = Instant::now();
+
+    db.upsert_turbines(&changes.upserts).await?;
+    db.delete_turbines(&changes.removed).await?;
+    db.record_history(release_id, &changes.history).await?;
+
+    let applied = changes.upserts.len() + changes.removed.len();
+    let rate = applied as f64 / start.elapsed().as_secs_f64();
+    finish!(tmr, "Applied {} turbine changes to the database ({:.0} rows/sec)", applied, rate);
+    stages.add("APPLY_TURBINE_CHANGES_TO_DATABASE", start);
+    Ok(())
+}
+
+/// The checkpoint to resume the load of the file from, if there is one and
+/// the load is to be resumed.
+async fn find_checkpoint(db: &mut dyn Repository, release: &Release, settings: &LoadSettings) -> Result<Option<LoadCheckpoint>, Box<dyn Error>> {
+    if !settings.checkpoint {
+        return Ok(None);
+    }
+
+    match db.get_load_checkpoint(&release.checksum).await? {
+        Some(checkpoint) if settings.resume => {
+            info!("Resuming the load of {} after {} turbines", release.file_name, checkpoint.turbines);
+            Ok(Some(checkpoint))
+        }
+        Some(checkpoint) => {
+            warn!("An earlier load of {} stopped after {} turbines; loading it from the start. Give --resume to continue it instead",
+                release.file_name, checkpoint.turbines);
+            Ok(None)
+        }
+        None => {
+            if settings.resume {
+                warn!("There is no checkpoint to resume the load of {} from; loading it from the start", release.file_name);
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Commits the batches loaded so far, with a checkpoint to resume the load from.
+async fn save_checkpoint(db: &mut dyn Repository, release: &Release, release_id: i32, turbines: usize, counts: &ChangeCounts,
+    stages: &mut Stages) -> Result<(), Box<dyn Error>> {
+    let tmr = stimer!("SAVE_CHECKPOINT");
+    let start = Instant::now();
+
+    let checkpoint = LoadCheckpoint {
+        checksum: release.checksum.clone(),
+        release_id,
+        turbines,
+        new: counts.new,
+        changed: counts.changed,
+        unchanged: counts.unchanged,
+    };
+    db.save_load_checkpoint(&checkpoint).await?;
+    db.commit().await?;
+    db.begin().await?;
+
+    finish!(tmr, "Committed the first {} turbines", turbines);
+    stages.add("SAVE_CHECKPOINT", start);
+    Ok(())
+}
+
+/// Streams the turbines file into the database. The file is read and validated
+/// on another thread while earlier batches are written; each batch has its
+/// dimensions written first, then its turbines are compared with the database
+/// and the differences applied. Turbines that were not in the file are removed
+/// at the end, once the number of rejected rows is known to be acceptable.
+/// Given a pool, the dimensions of each batch are written on it several at a
+/// time, each committed as it is written. The timings, rejected rows and
+/// counts are added to `report`, if there is one.
+pub async fn load_turbines(db: &mut dyn Repository, pool: Option<&Pool>, release: &Release, aliases: Option<Aliases>,
+    quarantine: Quarantine, settings: &LoadSettings, mut report: Option<&mut RunReport>) -> Result<(), Box<dyn Error>> {
+    let tmr = stimer!("LOAD_TURBINES_TO_DATABASE");
+    let start = Instant::now();
+    let mut unreported = Stages::default();
+
+    // The names that were known before this load, to find the new ones afterwards.
+    let manufacturer_names = db.get_manufacturer_names().await?;
+    let model_names = db.get_model_names().await?;
+
+    let mut dimensions = SeenDimensions::default();
+    // Even an empty aliases file is synced, so the aliases taken out of it are removed.
+    if let Some(aliases) = &aliases {
+        let loaded = dimensions.load_aliases(db, pool, aliases, stages(&mut report, &mut unreported)).await;
+        dimensions.report(report.as_deref_mut());
+        loaded?;
+    }
+
+    let aliases = Arc::new(aliases.unwrap_or_default());
+    let states = db.get_state_ids().await?;
+    let progress = Arc::new(Progress::default());
+    let (mut rx, reader) = spawn_reader(release.path.clone(), states, Arc::clone(&aliases), quarantine, Arc::clone(&progress),
+        settings.channel_capacity);
+    let existing = db.get_case_ids().await?;
+    let checkpoint = find_checkpoint(db, release, settings).await?;
+    let release_id = match &checkpoint {
+        Some(checkpoint) => checkpoint.release_id,
+        None => db.create_release(&release.to_new_release()).await?,
+    };
+
+    let mut seen = HashSet::with_capacity(existing.len());
+    let mut counts = ChangeCounts::default();
+    let mut turbine_count = 0;
+    // The number of turbines loaded by the run being resumed.
+    let mut skip = 0;
+    if let Some(checkpoint) = &checkpoint {
+        counts.new = checkpoint.new;
+        counts.changed = checkpoint.changed;
+        counts.unchanged = checkpoint.unchanged;
+        skip = checkpoint.turbines;
+        if let Some(report) = report.as_deref_mut() {
+            report.resumed_after = Some(skip);
+        }
+    }
+
+    while let Some(batch) = next_batch(&mut rx, settings.batch_size).await {
+        // Dimensions are synced again when resuming, as the later batches
+        // and the consensus of each model depend on them.
+        let loaded = dimensions.load(db, pool, &batch, stages(&mut report, &mut unreported)).await;
+        dimensions.report(report.as_deref_mut());
+        loaded?;
+
+        // The turbines already loaded only need marking as seen. The batch
+        // size may have changed, so a batch can be partly loaded.
+        let done = skip.saturating_sub(turbine_count).min(batch.len());
+        seen.extend(batch[..done].iter().map(|t| t.case_id));
+        turbine_count += batch.len();
+        if let Some(report) = report.as_deref_mut() {
+            report.turbines_read = turbine_count;
+        }
+
+        let batch = &batch[done..];
+        if !batch.is_empty() {
+            let case_ids = batch.iter()
+                .map(|t| t.case_id)
+                .filter(|case_id| existing.contains(case_id))
+                .collect::<Vec<_>>();
+            let snapshots = db.get_turbine_rows(&case_ids).await?;
+
+            let changes = TurbineChanges::compute(batch, &snapshots, &mut seen);
+            apply_changes(db, release_id, &changes, stages(&mut report, &mut unreported)).await?;
+            counts.add(&changes);
+            if let Some(report) = report.as_deref_mut() {
+                report.add_written("Turbine", sync_counts(&changes));
+            }
+
+            if settings.checkpoint {
+                // Each batch is committed, so must not be if too many of the
+                // rows up to it were rejected.
+                progress.check(&release.path.display().to_string(), turbine_count, settings.max_rejected_percent)?;
+                save_checkpoint(db, release, release_id, turbine_count, &counts, stages(&mut report, &mut unreported)).await?;
+                if let Some(report) = report.as_deref_mut() {
+                    report.commit();
+                }
+            }
+        }
+        executing!(tmr, "Processed {} turbines: {}", turbine_count, counts);
+    }
+
+    // The channel closes when the reader finishes, whether or not it succeeded.
+    let summary = reader.await??;
+    if let Some(report) = report.as_deref_mut() {
+        report.rejected_rows += summary.rejected;
+    }
+    summary.check(settings.max_rejected_percent)?;
+    dimensions.load_consensus_models(db).await?;
+
+    // A rejected row may be a bad update to a turbine that still exists.
+    seen.extend(summary.rejected_case_ids.iter().copied());
+    let removals = TurbineChanges::removals(&existing, &seen);
+    apply_changes(db, release_id, &removals, stages(&mut report, &mut unreported)).await?;
+    counts.add(&removals);
+    dimensions.prune(db).await?;
+    if let Some(report) = report.as_deref_mut() {
+        report.add_written("Turbine", sync_counts(&removals));
+    }
+    dimensions.report(report.as_deref_mut());
+
+    let release_counts = ReleaseCounts {
+        turbines: turbine_count,
+        new: counts.new,
+        changed: counts.changed,
+        decommissioned: counts.removed,
+    };
+    db.finish_release(release_id, &release_counts).await?;
+    if settings.checkpoint {
+        // Committed by the caller with the rest of the load.
+        db.delete_load_checkpoint(&release.checksum).await?;
+    }
+    finish!(tmr, "Loaded {} turbines from USWTDB {}", turbine_count, release.version);
+    println!("USWTDB {} turbines: {}", release.version, counts);
+    for (table, counts) in &dimensions.counts {
+        println!("    {}: {}", table, counts);
+    }
+
+    let speedup = dimensions.speedup();
+    if let (Some(pool), Some(speedup)) = (pool, speedup) {
+        println!("Dimensions were written on {} connections in {:.1}s, a speedup of {:.1} over {:.1}s on one connection",
+            pool.size(), dimensions.concurrent_time.as_secs_f64(), speedup, dimensions.sequential_time.as_secs_f64());
+    }
+
+    if !dimensions.conflicts.is_empty() {
+        println!("{} rows conflict with earlier rows:", dimensions.conflicts.len());
+        for conflict in &dimensions.conflicts {
+            println!("    {}", conflict);
+        }
+    }
+
+    let suggestions = dimensions.alias_suggestions(&aliases, &manufacturer_names, &model_names);
+    if !suggestions.is_empty() {
+        println!("{} new names are spelled like existing ones; add them to the aliases file if they are the same:", suggestions.len());
+        for suggestion in &suggestions {
+            println!("    {}", suggestion);
+        }
+    }
+
+    let spec_conflicts = dimensions.spec_conflicts();
+    if !spec_conflicts.is_empty() {
+        println!("{} models have conflicting specifications; each was given the value used by most turbines:", spec_conflicts.len());
+        for conflict in &spec_conflicts {
+            println!("    {}", conflict);
+        }
+    }
+
+    if let Some(report) = report {
+        report.stages.add("LOAD_TURBINES_TO_DATABASE", start);
+        report.dimension_parallelism = pool.map_or(1, Pool::size);
+        report.dimension_speedup = speedup;
+        report.warnings.extend(dimensions.conflicts.iter().cloned());
+        report.warnings.extend(suggestions);
+        report.warnings.extend(spec_conflicts);
+    }
+
+    Ok(())
+}
+
+/// The turbine changes as the counts of the Turbine table.
+fn sync_counts(changes: &TurbineChanges) -> SyncCounts {
+    SyncCounts { inserted: changes.new, updated: changes.changed, removed: changes.removed.len() }
+}
+
+/// The stages of the report, or `unreported` for a load without one.
+fn stages<'a>(report: &'a mut Option<&mut RunReport>, unreported: &'a mut Stages) -> &'a mut Stages {
+    match report {
+        Some(report) => &mut report.stages,
+        None => unreported,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::iowa;
+
+    #[tokio::test]
+    async fn batches_are_at_most_batch_size() {
+        let (tx, mut rx) = mpsc::channel(10);
+        for case_id in 1..=5 {
+            tx.send(iowa(|t| t.case_id = case_id)).await.unwrap();
+        }
+        drop(tx);
+
+        let mut sizes = Vec::new();
+        while let Some(batch) = next_batch(&mut rx, 2).await {
+            sizes.push(batch.len());
+        }
+        assert_eq!(sizes, vec![2, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn zero_batch_size_still_makes_progress() {
+        let (tx, mut rx) = mpsc::channel(1);
+        tx.send(iowa(|_| {})).await.unwrap();
+        drop(tx);
+
+        assert_eq!(next_batch(&mut rx, 0).await.map(|b| b.len()), Some(1));
+        assert!(next_batch(&mut rx, 0).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn empty_file_has_no_batches() {
+        let (tx, mut rx) = mpsc::channel::<TurbineCsv>(1);
+        drop(tx);
+
+        assert!(next_batch(&mut rx, 1).await.is_none());
+    }
+
+    #[test]
+    fn speedup_is_the_sequential_time_over_the_concurrent_time() {
+        let mut dimensions = SeenDimensions::default();
+        assert_eq!(dimensions.speedup(), None);
+
+        dimensions.concurrent_time = Duration::from_millis(400);
+        dimensions.sequential_time = Duration::from_millis(1_000);
+        assert_eq!(dimensions.speedup(), Some(2.5));
+    }
+
+    #[test]
+    fn new_rows_skips_counties_already_seen() {
+        let mut dimensions = SeenDimensions::default();
+        let first = dimensions.new_rows(&[iowa(|_| {}), iowa(|t| t.case_id = 2)]);
+        assert_eq!(first.counties, vec![rows::county_row("IA", "Franklin County", 19069)]);
+
+        let second = dimensions.new_rows(&[iowa(|_| {}), iowa(|t| { t.t_county = "Wright County".to_string(); t.t_fips = 19197; })]);
+        assert_eq!(second.counties, vec![rows::county_row("IA", "Wright County", 19197)]);
+        assert!(dimensions.conflicts.is_empty());
+    }
+
+    #[test]
+    fn new_rows_keeps_the_first_name_of_a_fips_code() {
+        let mut dimensions = SeenDimensions::default();
+        dimensions.new_rows(&[iowa(|_| {})]);
+        let rows = dimensions.new_rows(&[iowa(|t| t.t_county = "Franklin".to_string())]);
+
+        assert!(rows.counties.is_empty());
+        assert_eq!(dimensions.conflicts.iter().collect::<Vec<_>>(),
+            vec!["County FIPS 19069 is both 'Franklin County, IA' and 'Franklin, IA'; using the first"]);
+    }
+
+    #[test]
+    fn new_rows_appends_the_fips_code_to_a_name_used_twice() {
+        let mut dimensions = SeenDimensions::default();
+        dimensions.new_rows(&[iowa(|_| {})]);
+        let rows = dimensions.new_rows(&[iowa(|t| t.t_fips = 19070)]);
+
+        assert_eq!(rows.counties, vec![rows::county_row("IA", "Franklin County (19070)", 19070)]);
+        assert_eq!(dimensions.conflicts.iter().collect::<Vec<_>>(),
+            vec!["County 'Franklin County, IA' has FIPS codes 19069 and 19070; loading the second as 'Franklin County (19070)'"]);
+    }
+
+    #[test]
+    fn new_rows_keeps_the_first_size_of_a_project() {
+        let mut dimensions = SeenDimensions::default();
+        dimensions.new_rows(&[iowa(|_| {})]);
+        let rows = dimensions.new_rows(&[iowa(|t| { t.p_tnum = None; t.p_cap = None; })]);
+
+        assert!(rows.projects.is_empty());
+        assert_eq!(dimensions.conflicts.iter().collect::<Vec<_>>(),
+            vec!["Project 'Crystal Lake' in IA (2008) has both 1 turbines of 1.5 MW and an unknown number of turbines of unknown MW; using the first"]);
+    }
+}
@@ -0,0 +1,110 @@
+use chrono::NaiveDate;
+use log::warn;
+use repository::load::NewRelease;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::input;
+
+/// Identifies the USWTDB file being loaded. Files are published with names
+/// like `uswtdb_v4_1_20210721.csv`, giving a version of "4.1" and a release
+/// date of 2021-07-21. The checksum is of the file as given, zipped or not.
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub path: PathBuf,
+    pub file_name: String,
+    pub version: String,
+    pub release_date: Option<String>,
+    pub checksum: String,
+}
+
+impl Release {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file_name = path.file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        // The version is in the name of the CSV, which for the zipped
+        // distribution is the entry inside the zip rather than the zip itself.
+        let csv_name = input::csv_name(path)?;
+        let stem = Path::new(&csv_name).file_stem()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let (version, release_date) = match parse_file_stem(&stem) {
+            Some((version, date)) => (version, Some(date)),
+            None => {
+                warn!("Cannot get the USWTDB version and date from the file name '{}'", csv_name);
+                (stem, None)
+            }
+        };
+
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut File::open(path)?, &mut hasher)?;
+        let checksum = format!("{:x}", hasher.finalize());
+
+        Ok(Release { path: path.to_path_buf(), file_name, version, release_date, checksum })
+    }
+
+    /// The release as recorded in the Release table.
+    pub fn to_new_release(&self) -> NewRelease<'_> {
+        NewRelease {
+            file_name: &self.file_name,
+            version: &self.version,
+            release_date: self.release_date.as_deref(),
+            checksum: &self.checksum,
+        }
+    }
+}
+
+/// Splits `uswtdb_v4_1_20210721` into ("4.1", "2021-07-21").
+fn parse_file_stem(stem: &str) -> Option<(String, String)> {
+    let parts = stem.split('_').collect::<Vec<_>>();
+    let (date, rest) = parts.split_last()?;
+    let date = NaiveDate::parse_from_str(date, "%Y%m%d").ok()?;
+
+    let start = rest.iter().position(|p| p.starts_with('v'))?;
+    let version = rest[start..].join(".");
+    let version = version.trim_start_matches('v');
+    if version.is_empty() {
+        return None;
+    }
+
+    Some((version.to_string(), date.format("%Y-%m-%d").to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn parses_version_and_date() {
+        assert_eq!(parse_file_stem("uswtdb_v4_1_20210721"), Some(("4.1".to_string(), "2021-07-21".to_string())));
+        assert_eq!(parse_file_stem("uswtdb_v3_3_1_20210114"), Some(("3.3.1".to_string(), "2021-01-14".to_string())));
+        assert_eq!(parse_file_stem("uswtdb_v5_20220101"), Some(("5".to_string(), "2022-01-01".to_string())));
+    }
+
+    #[test]
+    fn rejects_other_names() {
+        assert_eq!(parse_file_stem("uswtdb"), None);
+        assert_eq!(parse_file_stem("uswtdb_v4_1"), None);
+        assert_eq!(parse_file_stem("uswtdb_4_1_20210721"), None);
+        assert_eq!(parse_file_stem("uswtdb_v4_1_20211341"), None);
+        assert_eq!(parse_file_stem("uswtdb_v_20210721"), None);
+    }
+
+    #[test]
+    fn reads_release_from_file() {
+        let dir = TempDir::new("release");
+        let path = dir.write("uswtdb_v4_1_20210721.csv", "case_id\n");
+
+        let release = Release::from_file(&path).unwrap();
+        assert_eq!(release.file_name, "uswtdb_v4_1_20210721.csv");
+        assert_eq!(release.version, "4.1");
+        assert_eq!(release.release_date.as_deref(), Some("2021-07-21"));
+        assert_eq!(release.checksum, "1982e02ac7a62be9bb4423ddcbaf6a4a77a8206da36f2b8608b54eac2f6a2046");
+    }
+}
@@ -0,0 +1,266 @@
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use repository::dimension::SyncCounts;
+use repository::load::NewLoadRun;
+use repository::Repository;
+use serde::Serialize;
+use settings::LoadSettings;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::release::Release;
+
+/// The time spent in a stage of a load. Stages run once per batch or per
+/// table are totalled.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct StageTime {
+    pub runs: usize,
+    pub seconds: f64,
+}
+
+/// The time spent in each stage of a load, keyed by the name of its `stimer!`.
+#[derive(Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct Stages(BTreeMap<&'static str, StageTime>);
+
+impl Stages {
+    /// Adds the time since `start` to the stage.
+    pub fn add(&mut self, stage: &'static str, start: Instant) {
+        self.add_time(stage, start.elapsed());
+    }
+
+    /// Adds a time measured elsewhere to the stage.
+    pub fn add_time(&mut self, stage: &'static str, duration: Duration) {
+        let time = self.0.entry(stage).or_default();
+        time.runs += 1;
+        time.seconds += duration.as_secs_f64();
+    }
+}
+
+/// What a run of the load command did, written as JSON to the report file
+/// and recorded in the LoadRun table. The table counts are of the changes
+/// committed, so a failed load only has those of the batches and the pooled
+/// dimension rows committed before it failed.
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub succeeded: bool,
+    pub error: Option<String>,
+    pub us_states_file: Option<String>,
+    pub turbines_file: Option<String>,
+    /// The SHA-256 of the turbines file.
+    pub checksum: Option<String>,
+    pub uswtdb_version: Option<String>,
+    pub release_date: Option<String>,
+    /// The number of times the load was retried after a transient error.
+    pub retries: u32,
+    /// The turbines loaded by an earlier run, if the load was resumed from a
+    /// checkpoint.
+    pub resumed_after: Option<usize>,
+    /// The turbine rows that passed validation.
+    pub turbines_read: usize,
+    pub rejected_rows: usize,
+    /// Over every attempt at the load.
+    pub stages: Stages,
+    /// The number of connections the dimensions were written on.
+    pub dimension_parallelism: usize,
+    /// How many times faster the dimensions were written on the pool than
+    /// one table at a time on one connection, if any were written on a pool.
+    pub dimension_speedup: Option<f64>,
+    /// The rows inserted, updated and removed in each table by the commits
+    /// of every attempt at the load.
+    pub tables: BTreeMap<&'static str, SyncCounts>,
+    /// The changes written in the load's transaction since it last committed.
+    #[serde(skip)]
+    written: BTreeMap<&'static str, SyncCounts>,
+    /// The conflicts, suggested aliases and disputed specifications the load
+    /// printed.
+    pub warnings: Vec<String>,
+}
+
+impl RunReport {
+    pub fn new(us_states_file: Option<&Path>, turbines_file: Option<&Path>) -> Self {
+        RunReport {
+            started_at: Utc::now(),
+            finished_at: None,
+            succeeded: false,
+            error: None,
+            us_states_file: us_states_file.map(|f| f.display().to_string()),
+            turbines_file: turbines_file.map(|f| f.display().to_string()),
+            checksum: None,
+            uswtdb_version: None,
+            release_date: None,
+            retries: 0,
+            resumed_after: None,
+            turbines_read: 0,
+            rejected_rows: 0,
+            stages: Stages::default(),
+            dimension_parallelism: 1,
+            dimension_speedup: None,
+            tables: BTreeMap::new(),
+            written: BTreeMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn set_release(&mut self, release: &Release) {
+        self.checksum = Some(release.checksum.clone());
+        self.uswtdb_version = Some(release.version.clone());
+        self.release_date = release.release_date.clone();
+    }
+
+    /// Adds changes written in the load's transaction, which count once it commits.
+    pub fn add_written(&mut self, table: &'static str, counts: SyncCounts) {
+        self.written.entry(table).or_default().add(counts);
+    }
+
+    /// Adds changes that have already been committed, such as the dimension
+    /// rows written on the pool.
+    pub fn add_committed(&mut self, table: &'static str, counts: SyncCounts) {
+        self.tables.entry(table).or_default().add(counts);
+    }
+
+    /// Counts the changes written since the last commit as committed.
+    pub fn commit(&mut self) {
+        for (table, counts) in std::mem::take(&mut self.written) {
+            self.add_committed(table, counts);
+        }
+    }
+
+    /// Forgets the changes written since the last commit, as they have been
+    /// rolled back.
+    pub fn roll_back(&mut self) {
+        self.written.clear();
+    }
+
+    /// Starts the report again for a retry of the load, keeping its start
+    /// time, the time spent so far and the changes committed. Those written
+    /// but not committed were rolled back.
+    pub fn retry(&mut self) {
+        *self = RunReport {
+            started_at: self.started_at,
+            us_states_file: self.us_states_file.take(),
+            turbines_file: self.turbines_file.take(),
+            retries: self.retries + 1,
+            stages: std::mem::take(&mut self.stages),
+            tables: std::mem::take(&mut self.tables),
+            ..RunReport::new(None, None)
+        };
+    }
+
+    pub fn finish(&mut self, result: &Result<(), Box<dyn Error>>) {
+        self.finished_at = Some(Utc::now());
+        self.succeeded = result.is_ok();
+        self.error = result.as_ref().err().map(|err| err.to_string());
+    }
+
+    /// Writes the report to the configured file and records it in the LoadRun
+    /// table, as configured. Failures are logged rather than returned, as the
+    /// load itself is over.
+    pub async fn save(&self, db: &mut dyn Repository, settings: &LoadSettings) {
+        if let Some(path) = &settings.report_file {
+            match self.write(path) {
+                Ok(()) => info!("Wrote the run report to {}", path.display()),
+                Err(err) => error!("Cannot write the run report to {}: {}", path.display(), err),
+            }
+        }
+
+        if settings.record_runs {
+            match self.record(db).await {
+                Ok(id) => info!("Recorded the run as LoadRun {}", id),
+                Err(err) => error!("Cannot record the run in the LoadRun table: {}", err),
+            }
+        }
+    }
+
+    fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer_pretty(File::create(path)?, self)?;
+        Ok(())
+    }
+
+    async fn record(&self, db: &mut dyn Repository) -> Result<i32, Box<dyn Error>> {
+        let report = serde_json::to_string(self)?;
+        let started_at = timestamp(self.started_at);
+        let finished_at = timestamp(self.finished_at.unwrap_or_else(Utc::now));
+        let file_name = self.turbines_file.as_deref()
+            .or(self.us_states_file.as_deref())
+            .and_then(|f| Path::new(f).file_name())
+            .map(|f| f.to_string_lossy().to_string());
+
+        let run = NewLoadRun {
+            started_at: &started_at,
+            finished_at: &finished_at,
+            succeeded: self.succeeded,
+            file_name: file_name.as_deref(),
+            version: self.uswtdb_version.as_deref(),
+            checksum: self.checksum.as_deref(),
+            rejected_rows: self.rejected_rows,
+            warnings: self.warnings.len(),
+            error_message: self.error.as_deref(),
+            report: &report,
+        };
+        Ok(db.record_load_run(&run).await?)
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inserted(inserted: usize) -> SyncCounts {
+        SyncCounts { inserted, ..SyncCounts::default() }
+    }
+
+    fn table_inserts(report: &RunReport) -> Vec<(&'static str, usize)> {
+        report.tables.iter().map(|(table, counts)| (*table, counts.inserted)).collect()
+    }
+
+    #[test]
+    fn counts_the_changes_written_once_they_are_committed() {
+        let mut report = RunReport::new(None, None);
+        report.add_written("Turbine", inserted(2));
+        report.add_committed("Model", inserted(1));
+        assert_eq!(table_inserts(&report), vec![("Model", 1)]);
+
+        report.commit();
+        report.add_written("Turbine", inserted(3));
+        report.commit();
+        assert_eq!(table_inserts(&report), vec![("Model", 1), ("Turbine", 5)]);
+    }
+
+    #[test]
+    fn forgets_the_changes_rolled_back() {
+        let mut report = RunReport::new(None, None);
+        report.add_written("Turbine", inserted(2));
+        report.commit();
+        report.add_written("Turbine", inserted(3));
+        report.add_written("County", inserted(1));
+        report.roll_back();
+        report.commit();
+        assert_eq!(table_inserts(&report), vec![("Turbine", 2)]);
+    }
+
+    #[test]
+    fn a_retry_keeps_the_changes_committed() {
+        let mut report = RunReport::new(None, Some(Path::new("uswtdb.csv")));
+        report.add_committed("Model", inserted(1));
+        report.add_written("Turbine", inserted(2));
+        report.rejected_rows = 4;
+        report.stages.add_time("SAVE_CHECKPOINT", Duration::from_secs(1));
+
+        report.retry();
+        report.commit();
+        assert_eq!(table_inserts(&report), vec![("Model", 1)]);
+        assert_eq!((report.retries, report.rejected_rows), (1, 0));
+        assert_eq!(report.turbines_file.as_deref(), Some("uswtdb.csv"));
+        assert_eq!(report.stages.0["SAVE_CHECKPOINT"].runs, 1);
+    }
+}
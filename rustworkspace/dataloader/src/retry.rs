@@ -0,0 +1,59 @@
+use log::warn;
+use repository::Repository;
+use settings::LoadSettings;
+use std::error::Error;
+use std::time::Duration;
+
+/// Whether an error may not recur if the load is tried again, such as a
+/// dropped connection.
+pub fn is_transient(err: &(dyn Error + 'static)) -> bool {
+    matches!(err.downcast_ref::<repository::error::Error>(), Some(repository::error::Error::Transient(_)))
+}
+
+/// The wait before retry number `retry`, counting from 1: the configured
+/// delay, doubled for each retry before it.
+pub fn delay(settings: &LoadSettings, retry: u32) -> Duration {
+    let factor = 1u64 << (retry.max(1) - 1).min(16);
+    Duration::from_millis(settings.retry_delay_ms.saturating_mul(factor))
+}
+
+/// Opens the database, retrying if the connection cannot be made.
+pub async fn open(connection_string: &str, settings: &LoadSettings) -> Result<Box<dyn Repository>, Box<dyn Error>> {
+    let mut retry = 0;
+
+    loop {
+        match repository::open(connection_string).await {
+            Ok(db) => return Ok(db),
+            Err(err @ repository::error::Error::Transient(_)) if retry < settings.retries => {
+                retry += 1;
+                let delay = delay(settings, retry);
+                warn!("Cannot open the database: {}. Retry {} of {} in {:.1}s", err, retry, settings.retries, delay.as_secs_f64());
+                tokio::time::sleep(delay).await;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_the_delay_for_each_retry() {
+        let settings = LoadSettings { retry_delay_ms: 500, ..LoadSettings::default() };
+        let delays = (1..=4).map(|retry| delay(&settings, retry).as_millis()).collect::<Vec<_>>();
+        assert_eq!(delays, vec![500, 1_000, 2_000, 4_000]);
+        assert_eq!(delay(&settings, 100), delay(&settings, 17));
+    }
+
+    #[test]
+    fn only_transient_repository_errors_are_retried() {
+        let transient: Box<dyn Error> = repository::error::Error::Transient("connection reset".to_string()).into();
+        let not_found: Box<dyn Error> = repository::error::Error::NotFound.into();
+        let other: Box<dyn Error> = "bad row".into();
+        assert!(is_transient(transient.as_ref()));
+        assert!(!is_transient(not_found.as_ref()));
+        assert!(!is_transient(other.as_ref()));
+    }
+}
@@ -0,0 +1,59 @@
+use repository::dimension::Value;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+
+use crate::{Model, Project, UsState};
+
+/// Heights, lengths and areas are stored to two decimal places.
+fn measure(m: Option<f32>) -> Value {
+    Value::Decimal(m.and_then(Decimal::from_f32).map(|d| d.round_dp(2)))
+}
+
+pub fn state_row(s: &UsState) -> Vec<Value> {
+    vec![
+        Value::text(&s.abbreviation),
+        Value::text(&s.name),
+        Value::Text(s.capital.clone()),
+        Value::Int(s.population),
+        Value::Int(s.area_in_square_km()),
+        Value::text(&s.state_type_code()),
+    ]
+}
+
+pub fn county_row(state: &str, name: &str, fips: i32) -> Vec<Value> {
+    vec![Value::Int(Some(fips)), Value::text(state), Value::text(name)]
+}
+
+pub fn name_row(name: &str) -> Vec<Value> {
+    vec![Value::text(name)]
+}
+
+pub fn manufacturer_alias_row(alias: &str, manufacturer: &str) -> Vec<Value> {
+    vec![Value::text(alias), Value::text(manufacturer)]
+}
+
+pub fn model_alias_row(manufacturer: &str, alias: &str, model: &str) -> Vec<Value> {
+    vec![Value::text(manufacturer), Value::text(alias), Value::text(model)]
+}
+
+pub fn model_row(m: &Model<'_>) -> Vec<Value> {
+    vec![
+        Value::text(m.t_manu),
+        Value::text(m.t_model),
+        Value::Int(m.t_cap),
+        measure(m.t_hh),
+        measure(m.t_rd),
+        measure(m.t_rsa),
+        measure(m.t_ttlh),
+    ]
+}
+
+pub fn project_row(p: &Project<'_>) -> Vec<Value> {
+    vec![
+        Value::text(p.p_name),
+        Value::text(p.t_state),
+        Value::Int(p.p_year),
+        Value::Int(p.p_tnum),
+        Value::Decimal(p.p_cap),
+    ]
+}
@@ -0,0 +1,71 @@
+use logging_timer::{finish, stimer};
+use repository::models::{County, ImageSource, Manufacturer, Model, Project, State, Turbine};
+use repository::Repository;
+use std::collections::HashMap;
+use std::error::Error;
+use std::hash::Hash;
+
+/// Every table in the database, read once and keyed by id, for the commands
+/// that report on the whole database rather than load into it.
+pub struct Snapshot {
+    pub states: HashMap<String, State>,
+    pub counties: HashMap<i32, County>,
+    pub projects: HashMap<i32, Project>,
+    pub manufacturers: HashMap<i32, Manufacturer>,
+    pub models: HashMap<i32, Model>,
+    pub image_sources: HashMap<u8, ImageSource>,
+    /// In case_id order.
+    pub turbines: Vec<Turbine>,
+}
+
+/// A turbine with the rows it refers to.
+pub struct TurbineDetail<'a> {
+    pub turbine: &'a Turbine,
+    pub county: &'a County,
+    pub project: &'a Project,
+    pub manufacturer: &'a Manufacturer,
+    pub model: &'a Model,
+    pub image_source: &'a ImageSource,
+}
+
+impl Snapshot {
+    pub async fn read(db: &mut dyn Repository) -> Result<Self, Box<dyn Error>> {
+        let tmr = stimer!("READ_SNAPSHOT");
+
+        let mut turbines = db.get_all_turbines().await?;
+        turbines.sort_by_key(|t| t.case_id);
+
+        let snapshot = Snapshot {
+            states: db.get_all_states().await?.into_iter().map(|s| (s.id.clone(), s)).collect(),
+            counties: db.get_all_counties().await?.into_iter().map(|c| (c.id, c)).collect(),
+            projects: db.get_all_projects().await?.into_iter().map(|p| (p.id, p)).collect(),
+            manufacturers: db.get_all_manufacturers().await?.into_iter().map(|m| (m.id, m)).collect(),
+            models: db.get_all_models().await?.into_iter().map(|m| (m.id, m)).collect(),
+            image_sources: db.get_all_image_sources().await?.into_iter().map(|i| (i.id, i)).collect(),
+            turbines,
+        };
+
+        finish!(tmr, "Read {} turbines", snapshot.turbines.len());
+        Ok(snapshot)
+    }
+
+    /// Looks up the rows each turbine refers to, in case_id order.
+    pub fn details(&self) -> impl Iterator<Item = Result<TurbineDetail<'_>, String>> {
+        self.turbines.iter().map(move |turbine| {
+            let model = lookup(&self.models, &turbine.model_id, "model", turbine)?;
+            Ok(TurbineDetail {
+                turbine,
+                county: lookup(&self.counties, &turbine.county_id, "county", turbine)?,
+                project: lookup(&self.projects, &turbine.project_id, "project", turbine)?,
+                manufacturer: lookup(&self.manufacturers, &model.manufacturer_id, "manufacturer", turbine)?,
+                model,
+                image_source: lookup(&self.image_sources, &turbine.image_source_id, "image source", turbine)?,
+            })
+        })
+    }
+}
+
+/// The foreign keys mean a missing row can only come from a damaged database.
+fn lookup<'a, K: Eq + Hash + std::fmt::Display, V>(rows: &'a HashMap<K, V>, id: &K, what: &str, turbine: &Turbine) -> Result<&'a V, String> {
+    rows.get(id).ok_or_else(|| format!("Turbine {} refers to {} {}, which does not exist", turbine.case_id, what, id))
+}
@@ -0,0 +1,200 @@
+use std::fmt::Display;
+
+use crate::Model;
+
+/// The number of turbines giving each value of an attribute, in the order the
+/// values were first seen.
+#[derive(Debug)]
+struct Tally<T>(Vec<(T, usize)>);
+
+impl<T> Default for Tally<T> {
+    fn default() -> Self {
+        Tally(Vec::new())
+    }
+}
+
+impl<T: Copy + PartialEq> Tally<T> {
+    fn add(&mut self, value: T) {
+        match self.0.iter_mut().find(|(v, _)| *v == value) {
+            Some((_, count)) => *count += 1,
+            None => self.0.push((value, 1)),
+        }
+    }
+
+    fn first(&self) -> T {
+        self.0[0].0
+    }
+
+    /// The value given by the most turbines. A tie goes to the value seen first.
+    fn consensus(&self) -> T {
+        let mut best = &self.0[0];
+        for value in &self.0[1..] {
+            if value.1 > best.1 {
+                best = value;
+            }
+        }
+        best.0
+    }
+
+    fn is_divergent(&self) -> bool {
+        self.0.len() > 1
+    }
+}
+
+fn describe<T: Copy + Display>(attribute: &str, unit: &str, tally: &Tally<Option<T>>) -> String {
+    let variants = tally.0.iter()
+        .map(|(value, count)| {
+            let value = value.map_or_else(|| "unknown".to_string(), |v| format!("{} {}", v, unit));
+            format!("{} ({} turbines)", value, count)
+        })
+        .collect::<Vec<_>>();
+
+    format!("{} {}", attribute, variants.join(", "))
+}
+
+/// The specifications given for one model by the turbines in the file. The
+/// CSV repeats them on every turbine and they do not always agree, so the
+/// model is given the value of each attribute used by the most turbines.
+#[derive(Debug, Default)]
+pub struct ModelSpecs {
+    t_cap: Tally<Option<i32>>,
+    t_hh: Tally<Option<f32>>,
+    t_rd: Tally<Option<f32>>,
+    t_rsa: Tally<Option<f32>>,
+    t_ttlh: Tally<Option<f32>>,
+}
+
+impl ModelSpecs {
+    pub fn add(&mut self, model: &Model<'_>) {
+        self.t_cap.add(model.t_cap);
+        self.t_hh.add(model.t_hh);
+        self.t_rd.add(model.t_rd);
+        self.t_rsa.add(model.t_rsa);
+        self.t_ttlh.add(model.t_ttlh);
+    }
+
+    /// The model with the consensus value of each attribute.
+    pub fn consensus<'a>(&self, t_manu: &'a String, t_model: &'a String) -> Model<'a> {
+        Model {
+            t_manu,
+            t_model,
+            t_cap: self.t_cap.consensus(),
+            t_hh: self.t_hh.consensus(),
+            t_rd: self.t_rd.consensus(),
+            t_rsa: self.t_rsa.consensus(),
+            t_ttlh: self.t_ttlh.consensus(),
+        }
+    }
+
+    /// Whether the consensus differs from the values of the first turbine,
+    /// which are the ones the model was loaded with.
+    pub fn differs_from_first(&self) -> bool {
+        self.t_cap.consensus() != self.t_cap.first()
+            || self.t_hh.consensus() != self.t_hh.first()
+            || self.t_rd.consensus() != self.t_rd.first()
+            || self.t_rsa.consensus() != self.t_rsa.first()
+            || self.t_ttlh.consensus() != self.t_ttlh.first()
+    }
+
+    /// Describes the attributes given more than one value, e.g. "capacity
+    /// 2000 kW (120 turbines), 1800 kW (3 turbines)", or None if they agree.
+    /// Hub and tip heights vary between installations and are stored on each
+    /// turbine, so they are not conflicts.
+    pub fn conflicts(&self) -> Option<String> {
+        let mut conflicts = Vec::new();
+
+        if self.t_cap.is_divergent() {
+            conflicts.push(describe("capacity", "kW", &self.t_cap));
+        }
+        if self.t_rd.is_divergent() {
+            conflicts.push(describe("rotor diameter", "m", &self.t_rd));
+        }
+        if self.t_rsa.is_divergent() {
+            conflicts.push(describe("rotor swept area", "m²", &self.t_rsa));
+        }
+
+        if conflicts.is_empty() { None } else { Some(conflicts.join("; ")) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::iowa;
+    use crate::TurbineCsv;
+
+    fn specs(turbines: &[TurbineCsv]) -> ModelSpecs {
+        let mut specs = ModelSpecs::default();
+        for t in turbines {
+            specs.add(&t.to_model());
+        }
+        specs
+    }
+
+    #[test]
+    fn consensus_is_the_most_common_value() {
+        let mut tally = Tally::default();
+        for value in &[1, 2, 2, 3] {
+            tally.add(*value);
+        }
+        assert_eq!((tally.first(), tally.consensus()), (1, 2));
+    }
+
+    #[test]
+    fn tie_goes_to_the_value_seen_first() {
+        let mut tally = Tally::default();
+        for value in &[3, 1, 1, 3] {
+            tally.add(*value);
+        }
+        assert_eq!(tally.consensus(), 3);
+        assert!(tally.is_divergent());
+    }
+
+    #[test]
+    fn agreeing_turbines_have_no_conflicts() {
+        let specs = specs(&[iowa(|_| {}), iowa(|_| {})]);
+        assert_eq!(specs.conflicts(), None);
+        assert!(!specs.differs_from_first());
+    }
+
+    #[test]
+    fn model_takes_the_consensus_of_its_turbines() {
+        // Three turbines give 1500 kW, and two each give 90 m and 100 m.
+        let turbines = [
+            iowa(|t| { t.t_cap = Some(1800); t.t_rd = Some(100.0); }),
+            iowa(|_| {}),
+            iowa(|_| {}),
+            iowa(|t| t.t_rd = Some(100.0)),
+            iowa(|t| t.t_rd = None),
+        ];
+        let specs = specs(&turbines);
+
+        let model = specs.consensus(&turbines[0].t_manu, &turbines[0].t_model);
+        assert_eq!((model.t_cap, model.t_rd), (Some(1500), Some(100.0)));
+        assert!(specs.differs_from_first());
+    }
+
+    #[test]
+    fn ties_keep_the_values_the_model_was_loaded_with() {
+        let turbines = [iowa(|t| { t.t_cap = Some(1800); t.t_rd = Some(100.0); }), iowa(|_| {})];
+        let specs = specs(&turbines);
+
+        let model = specs.consensus(&turbines[0].t_manu, &turbines[0].t_model);
+        assert_eq!((model.t_cap, model.t_rd), (Some(1800), Some(100.0)));
+        assert!(!specs.differs_from_first());
+        assert!(specs.conflicts().is_some());
+    }
+
+    #[test]
+    fn describes_each_divergent_attribute() {
+        let specs = specs(&[iowa(|_| {}), iowa(|t| t.t_cap = Some(1800)), iowa(|t| t.t_rd = None), iowa(|_| {})]);
+        assert_eq!(specs.conflicts().unwrap(),
+            "capacity 1500 kW (3 turbines), 1800 kW (1 turbines); rotor diameter 90 m (3 turbines), unknown (1 turbines)");
+    }
+
+    #[test]
+    fn heights_are_not_conflicts() {
+        let specs = specs(&[iowa(|_| {}), iowa(|t| { t.t_hh = Some(95.0); t.t_ttlh = Some(140.0); })]);
+        assert_eq!(specs.conflicts(), None);
+    }
+}
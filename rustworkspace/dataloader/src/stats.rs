@@ -0,0 +1,87 @@
+use logging_timer::{finish, stimer};
+use repository::Repository;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::snapshot::Snapshot;
+
+/// The number of turbines in a group and their total capacity. Turbines of
+/// models with no known capacity are counted but add nothing to the capacity.
+#[derive(Debug, Clone, Copy, Default)]
+struct Totals {
+    turbines: usize,
+    capacity_kw: i64,
+    unknown_capacity: usize,
+}
+
+impl Totals {
+    fn add(&mut self, capacity_kw: Option<i32>) {
+        self.turbines += 1;
+        match capacity_kw {
+            Some(kw) => self.capacity_kw += i64::from(kw),
+            None => self.unknown_capacity += 1,
+        }
+    }
+}
+
+impl fmt::Display for Totals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>8} {:>12.1}", self.turbines, self.capacity_kw as f64 / 1000.0)?;
+        if self.unknown_capacity > 0 {
+            write!(f, "  ({} of unknown capacity)", self.unknown_capacity)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints the number of turbines and their capacity in MW by state,
+/// manufacturer and project year.
+pub async fn stats(db: &mut dyn Repository) -> Result<(), Box<dyn Error>> {
+    let snapshot = Snapshot::read(db).await?;
+    let tmr = stimer!("STATS");
+
+    let mut total = Totals::default();
+    let mut by_state = BTreeMap::<String, Totals>::new();
+    let mut by_manufacturer = BTreeMap::<String, Totals>::new();
+    let mut by_year = BTreeMap::<Option<i16>, Totals>::new();
+
+    for detail in snapshot.details() {
+        let detail = detail?;
+        let capacity_kw = detail.model.capacity_kw;
+
+        let state = match snapshot.states.get(&detail.county.state_id) {
+            Some(state) => format!("{} {}", state.id, state.name),
+            None => detail.county.state_id.clone(),
+        };
+
+        total.add(capacity_kw);
+        by_state.entry(state).or_default().add(capacity_kw);
+        by_manufacturer.entry(detail.manufacturer.name.clone()).or_default().add(capacity_kw);
+        by_year.entry(detail.project.year).or_default().add(capacity_kw);
+    }
+
+    print_table("State", by_state.iter().map(|(state, totals)| (state.clone(), totals)));
+
+    // The largest manufacturers first.
+    let mut manufacturers = by_manufacturer.iter().collect::<Vec<_>>();
+    manufacturers.sort_by_key(|(_, totals)| std::cmp::Reverse(totals.capacity_kw));
+    print_table("Manufacturer", manufacturers.into_iter().map(|(name, totals)| (name.clone(), totals)));
+
+    print_table("Year", by_year.iter().map(|(year, totals)| {
+        (year.map(|y| y.to_string()).unwrap_or_else(|| "Unknown".to_string()), totals)
+    }));
+
+    println!("{:<40} {}", "Total", total);
+
+    finish!(tmr, "Summarised {} turbines", total.turbines);
+    Ok(())
+}
+
+fn print_table<'a>(heading: &str, rows: impl Iterator<Item = (String, &'a Totals)>) {
+    println!("{:<40} {:>8} {:>12}", heading, "Turbines", "Capacity MW");
+    for (name, totals) in rows {
+        println!("{:<40} {}", name, totals);
+    }
+    println!();
+}
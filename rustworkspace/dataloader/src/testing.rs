@@ -0,0 +1,54 @@
+//! Rows for the unit tests, written as they appear in the USWTDB.
+
+use std::path::PathBuf;
+
+use crate::TurbineCsv;
+
+const HEADER: &str = "case_id,faa_ors,faa_asn,usgs_pr_id,eia_id,t_state,t_county,t_fips,p_name,p_year,p_tnum,p_cap,t_manu,t_model,t_cap,t_hh,t_rd,t_rsa,t_ttlh,retrofit,retrofit_year,t_conf_atr,t_conf_loc,t_img_date,t_img_srce,xlong,ylat";
+
+/// A turbine in Franklin County, Iowa.
+pub const IOWA: &str = "3000001,,2013-WTE-2956-OE,,56291,IA,Franklin County,19069,Crystal Lake,2008,1,1.5,Vestas,V90,1500,80,90,6362,125,0,,3,3,6/1/2019,NAIP,-93.2,42.7";
+
+/// Reads a turbine from a row of the USWTDB.
+pub fn turbine(row: &str) -> TurbineCsv {
+    let csv = format!("{}\n{}\n", HEADER, row);
+    csv::Reader::from_reader(csv.as_bytes()).deserialize().next().unwrap().unwrap()
+}
+
+/// Reads a turbine from `IOWA`, changed by `edit`.
+pub fn iowa(edit: impl FnOnce(&mut TurbineCsv)) -> TurbineCsv {
+    let mut turbine = turbine(IOWA);
+    edit(&mut turbine);
+    turbine
+}
+
+/// A directory for the files of one test, removed with them when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty directory, named for the test and this process.
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("uswps-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    /// The path of a file in the directory.
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.0.join(file)
+    }
+
+    /// Writes a file in the directory, returning its path.
+    pub fn write(&self, file: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.path(file);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
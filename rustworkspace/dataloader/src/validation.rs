@@ -0,0 +1,396 @@
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::normalize;
+use crate::{TurbineCsv, UsState};
+
+/// Boxes of (min lat, max lat, min long, max long) that cover the USA and its
+/// territories: the 50 states, Puerto Rico and the US Virgin Islands in the
+/// first, Guam and the Northern Mariana Islands in the second.
+const US_BOUNDS: &[(f32, f32, f32, f32)] = &[
+    (17.5, 71.5, -180.0, -64.0),
+    (13.0, 21.0, 144.0, 146.5),
+];
+
+/// Checks the values in a turbine row, returning every problem found.
+pub fn check_turbine(t: &TurbineCsv, states: &HashSet<String>) -> Result<(), String> {
+    let mut problems = Vec::new();
+
+    if f64::from(t.ylat) == normalize::MISSING || f64::from(t.xlong) == normalize::MISSING {
+        problems.push("location is missing".to_string());
+    } else if !US_BOUNDS.iter().any(|(min_lat, max_lat, min_long, max_long)|
+        (*min_lat..=*max_lat).contains(&t.ylat) && (*min_long..=*max_long).contains(&t.xlong)) {
+        problems.push(format!("location ({}, {}) is outside the US", t.ylat, t.xlong));
+    }
+    if f64::from(t.t_fips) == normalize::MISSING {
+        problems.push("county FIPS code is missing".to_string());
+    }
+    if !states.contains(&t.t_state) {
+        problems.push(format!("unknown state '{}'", t.t_state));
+    }
+    if let (Some(hh), Some(ttlh)) = (t.t_hh, t.t_ttlh) {
+        if hh >= ttlh {
+            problems.push(format!("hub height {} is not below the tip height {}", hh, ttlh));
+        }
+    }
+    if let Some(cap) = t.t_cap.filter(|c| *c <= 0) {
+        problems.push(format!("turbine capacity {} is not positive", cap));
+    }
+    if let Some(cap) = t.p_cap.filter(|c| c.is_zero() || c.is_sign_negative()) {
+        problems.push(format!("project capacity {} is not positive", cap));
+    }
+    if !(1..=3).contains(&t.t_conf_atr) {
+        problems.push(format!("attribute confidence {} is not 1, 2 or 3", t.t_conf_atr));
+    }
+    if !(1..=3).contains(&t.t_conf_loc) {
+        problems.push(format!("location confidence {} is not 1, 2 or 3", t.t_conf_loc));
+    }
+
+    if problems.is_empty() { Ok(()) } else { Err(problems.join("; ")) }
+}
+
+/// Checks the values in a US state row, returning every problem found.
+pub fn check_state(s: &UsState) -> Result<(), String> {
+    let mut problems = Vec::new();
+
+    if s.abbreviation.len() != 2 || !s.abbreviation.chars().all(|c| c.is_ascii_uppercase()) {
+        problems.push(format!("abbreviation '{}' is not two capital letters", s.abbreviation));
+    }
+    if s.name.is_empty() {
+        problems.push("name is blank".to_string());
+    }
+    if !matches!(s.state_type.chars().next().map(|c| c.to_ascii_uppercase()), Some('S' | 'T' | 'F')) {
+        problems.push(format!("unknown state type '{}'", s.state_type));
+    }
+    if let Some(population) = s.population.filter(|p| *p < 0) {
+        problems.push(format!("population {} is negative", population));
+    }
+    if let Some(area) = s.area.filter(|a| *a <= 0) {
+        problems.push(format!("area {} is not positive", area));
+    }
+
+    if problems.is_empty() { Ok(()) } else { Err(problems.join("; ")) }
+}
+
+/// A CSV file of the rows that failed validation, with the file and line they
+/// came from and the reason. The file is only created if a row is rejected,
+/// and one left by an earlier load is removed.
+pub struct Quarantine {
+    /// None if the rejected rows are only logged.
+    path: Option<PathBuf>,
+    writer: Option<csv::Writer<File>>,
+}
+
+impl Quarantine {
+    pub fn new(path: PathBuf) -> io::Result<Self> {
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        Ok(Quarantine { path: Some(path), writer: None })
+    }
+
+    /// A quarantine that logs the rejected rows without writing them, for a
+    /// dry run.
+    pub fn discard() -> Self {
+        Quarantine { path: None, writer: None }
+    }
+
+    pub fn reject(&mut self, source: &str, line: u64, reason: &str, record: &csv::StringRecord) -> Result<(), Box<dyn Error>> {
+        warn!("Rejected line {} of {}: {}", line, source, reason);
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        if self.writer.is_none() {
+            // Rejected rows keep their own columns, so the rows are of varying length.
+            let mut writer = csv::WriterBuilder::new().flexible(true).from_path(path)?;
+            writer.write_record(["source", "line", "reason", "record"])?;
+            self.writer = Some(writer);
+        }
+
+        let writer = self.writer.as_mut().unwrap();
+        let line = line.to_string();
+        writer.write_record([source, line.as_str(), reason].iter().copied().chain(record.iter()))?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// The outcome of validating one input file.
+#[derive(Debug, Default)]
+pub struct ValidationSummary {
+    pub source: String,
+    pub rows: usize,
+    pub rejected: usize,
+    /// The case_ids of rejected turbines, so that they are not treated as
+    /// decommissioned.
+    pub rejected_case_ids: Vec<i32>,
+}
+
+impl ValidationSummary {
+    pub fn new(source: &str) -> Self {
+        ValidationSummary { source: source.to_string(), ..Default::default() }
+    }
+
+    /// Fails if more than `max_rejected_percent` of the rows were rejected.
+    pub fn check(&self, max_rejected_percent: f64) -> Result<(), Box<dyn Error>> {
+        if self.rejected == 0 {
+            return Ok(());
+        }
+
+        let percent = 100.0 * self.rejected as f64 / self.rows as f64;
+        info!("{} of {} rows in {} were rejected ({:.2}%)", self.rejected, self.rows, self.source, percent);
+
+        if percent > max_rejected_percent {
+            return Err(format!("{:.2}% of the rows in {} failed validation, more than the limit of {}%",
+                percent, self.source, max_rejected_percent).into());
+        }
+        Ok(())
+    }
+}
+
+/// The rows rejected so far, shared with the writer of a checkpointed load so
+/// that it can check the limit on rejected rows before committing each batch.
+#[derive(Debug, Default)]
+pub struct Progress {
+    /// The number of rows accepted before each rejected row.
+    rejected_after: Mutex<Vec<usize>>,
+}
+
+impl Progress {
+    /// Fails if more than `max_rejected_percent` of the rows up to the
+    /// `accepted`th accepted row were rejected. The reader records a rejected
+    /// row before passing on the rows after it, so the result does not depend
+    /// on how far ahead of the writer the reader is.
+    pub fn check(&self, source: &str, accepted: usize, max_rejected_percent: f64) -> Result<(), Box<dyn Error>> {
+        let rejected = self.rejected_after.lock().unwrap().iter().take_while(|&&before| before < accepted).count();
+        let summary = ValidationSummary { rows: accepted + rejected, rejected, ..ValidationSummary::new(source) };
+        summary.check(max_rejected_percent)
+    }
+}
+
+/// Reads a CSV, passing each row that deserializes and passes `validate` to
+/// `accept` and writing the others to the quarantine file, as are rows that
+/// are not valid UTF-8. `validate` may tidy the row before checking it.
+/// Reading stops early if `accept` returns false. The rejected rows are also
+/// recorded in `progress` as they are read.
+pub fn read_validated<T, V, A>(reader: &mut dyn Read, source: &str, quarantine: &mut Quarantine, progress: &Progress,
+    mut validate: V, mut accept: A) -> Result<ValidationSummary, Box<dyn Error>>
+where
+    T: DeserializeOwned,
+    V: FnMut(&mut T) -> Result<(), String>,
+    A: FnMut(T) -> bool,
+{
+    // Flexible, so that a row with the wrong number of fields is rejected
+    // like any other bad row rather than ending the load.
+    let mut rdr = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(reader);
+
+    let headers = rdr.headers()?.clone();
+    let case_id_column = headers.iter().position(|h| h == "case_id");
+    let mut summary = ValidationSummary::new(source);
+
+    // Read as bytes, so that a row that is not UTF-8 is rejected rather than
+    // ending the load. Only an I/O error does that.
+    let mut bytes = csv::ByteRecord::new();
+    while rdr.read_byte_record(&mut bytes)? {
+        summary.rows += 1;
+
+        let (record, row) = match csv::StringRecord::from_byte_record(bytes.clone()) {
+            Ok(record) => {
+                let row = record.deserialize::<T>(Some(&headers))
+                    .map_err(|e| e.to_string())
+                    .and_then(|mut row| validate(&mut row).map(|_| row));
+                (record, row)
+            }
+            Err(err) => (csv::StringRecord::from_byte_record_lossy(bytes.clone()), Err(err.to_string())),
+        };
+
+        match row {
+            Ok(row) => {
+                if !accept(row) {
+                    break;
+                }
+            }
+            Err(reason) => {
+                progress.rejected_after.lock().unwrap().push(summary.rows - 1 - summary.rejected);
+                summary.rejected += 1;
+                if let Some(case_id) = case_id_column.and_then(|c| record.get(c)).and_then(|c| c.parse().ok()) {
+                    summary.rejected_case_ids.push(case_id);
+                }
+
+                let line = bytes.position().map_or(0, |p| p.line());
+                quarantine.reject(source, line, &reason, &record)?;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{iowa, TempDir};
+    use rust_decimal::Decimal;
+
+    fn states() -> HashSet<String> {
+        ["IA", "GU"].iter().map(|s| s.to_string()).collect()
+    }
+
+    fn state(state_type: &str, abbreviation: &str) -> UsState {
+        UsState {
+            state_type: state_type.to_string(),
+            name: "Iowa".to_string(),
+            abbreviation: abbreviation.to_string(),
+            capital: None,
+            population: Some(3_155_070),
+            area: Some(56_273),
+        }
+    }
+
+    #[test]
+    fn accepts_a_good_turbine() {
+        assert_eq!(check_turbine(&iowa(|_| {}), &states()), Ok(()));
+    }
+
+    #[test]
+    fn accepts_locations_in_the_pacific_territories() {
+        let guam = iowa(|t| {
+            t.t_state = "GU".to_string();
+            t.ylat = 13.44;
+            t.xlong = 144.79;
+        });
+        assert_eq!(check_turbine(&guam, &states()), Ok(()));
+    }
+
+    #[test]
+    fn rejects_locations_outside_the_us() {
+        let problem = check_turbine(&iowa(|t| t.xlong = 10.0), &states()).unwrap_err();
+        assert_eq!(problem, "location (42.7, 10) is outside the US");
+    }
+
+    #[test]
+    fn rejects_a_missing_location() {
+        let problem = check_turbine(&iowa(|t| t.ylat = -9999.0), &states()).unwrap_err();
+        assert_eq!(problem, "location is missing");
+    }
+
+    #[test]
+    fn rejects_a_missing_county_fips_code() {
+        let problem = check_turbine(&iowa(|t| t.t_fips = -9999), &states()).unwrap_err();
+        assert_eq!(problem, "county FIPS code is missing");
+    }
+
+    #[test]
+    fn lists_every_problem() {
+        let turbine = iowa(|t| {
+            t.t_state = "XX".to_string();
+            t.t_hh = Some(130.0);
+            t.t_cap = Some(0);
+            t.p_cap = Some(Decimal::new(-15, 1));
+            t.t_conf_atr = 0;
+            t.t_conf_loc = 4;
+        });
+
+        let problems = check_turbine(&turbine, &states()).unwrap_err();
+        assert_eq!(problems.split("; ").collect::<Vec<_>>(), vec![
+            "unknown state 'XX'",
+            "hub height 130 is not below the tip height 125",
+            "turbine capacity 0 is not positive",
+            "project capacity -1.5 is not positive",
+            "attribute confidence 0 is not 1, 2 or 3",
+            "location confidence 4 is not 1, 2 or 3",
+        ]);
+    }
+
+    #[test]
+    fn checks_states() {
+        assert_eq!(check_state(&state("State", "IA")), Ok(()));
+        assert_eq!(check_state(&state("federal capital", "DC")), Ok(()));
+        assert_eq!(check_state(&state("County", "Ia")).unwrap_err(),
+            "abbreviation 'Ia' is not two capital letters; unknown state type 'County'");
+    }
+
+    #[test]
+    fn threshold_allows_up_to_the_limit() {
+        let summary = |rejected| ValidationSummary { rows: 200, rejected, ..ValidationSummary::new("test.csv") };
+
+        assert!(summary(0).check(0.0).is_ok());
+        assert!(summary(2).check(1.0).is_ok());
+        let err = summary(3).check(1.0).unwrap_err();
+        assert_eq!(err.to_string(), "1.50% of the rows in test.csv failed validation, more than the limit of 1%");
+    }
+
+    #[test]
+    fn quarantines_rejected_rows_with_their_line_and_reason() {
+        let dir = TempDir::new("validation");
+        let path = dir.path("quarantine.csv");
+
+        let csv = "case_id,value\n1,5\n2,-1\nnot a number,3\n4\n5,7\n";
+        let mut quarantine = Quarantine::new(path.clone()).unwrap();
+        let progress = Progress::default();
+        let mut accepted = Vec::new();
+        let summary = read_validated(&mut csv.as_bytes(), "test.csv", &mut quarantine, &progress,
+            |row: &mut (i32, i32)| if row.1 < 0 { Err(format!("value {} is negative", row.1)) } else { Ok(()) },
+            |row| { accepted.push(row.0); true })
+            .unwrap();
+
+        assert_eq!(accepted, vec![1, 5]);
+        assert_eq!((summary.rows, summary.rejected), (5, 3));
+        assert_eq!(summary.rejected_case_ids, vec![2, 4]);
+        // Up to the first accepted row, then up to the second.
+        assert!(progress.check("test.csv", 1, 0.0).is_ok());
+        assert_eq!(progress.check("test.csv", 2, 50.0).unwrap_err().to_string(),
+            "60.00% of the rows in test.csv failed validation, more than the limit of 50%");
+
+        let quarantined = std::fs::read_to_string(&path).unwrap();
+        let lines = quarantined.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "source,line,reason,record");
+        assert_eq!(lines[1], "test.csv,3,value -1 is negative,2,-1");
+        assert!(lines[2].starts_with("test.csv,4,"));
+        assert!(lines[3].starts_with("test.csv,5,") && lines[3].ends_with(",4"));
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn quarantines_rows_that_are_not_utf8() {
+        let dir = TempDir::new("utf8-quarantine");
+        let path = dir.path("quarantine.csv");
+        let mut quarantine = Quarantine::new(path.clone()).unwrap();
+        let mut accepted = Vec::new();
+        let csv = b"case_id,name\n1,Adair\n2,Ad\xffair\n3,Wright\n";
+        let summary = read_validated(&mut &csv[..], "test.csv", &mut quarantine, &Progress::default(),
+            |_: &mut (i32, String)| Ok(()),
+            |row| { accepted.push(row.0); true })
+            .unwrap();
+
+        assert_eq!(accepted, vec![1, 3]);
+        assert_eq!((summary.rows, summary.rejected), (3, 1));
+        assert_eq!(summary.rejected_case_ids, vec![2]);
+        let quarantined = std::fs::read_to_string(&path).unwrap();
+        assert!(quarantined.lines().nth(1).unwrap().starts_with("test.csv,3,invalid utf-8"));
+    }
+
+    #[test]
+    fn quarantine_file_is_only_created_for_a_reject() {
+        // One left by an earlier load is removed, so that it is not taken for this load's.
+        let dir = TempDir::new("no-quarantine");
+        let path = dir.write("quarantine.csv", "source,line,reason,record\n");
+
+        let mut quarantine = Quarantine::new(path.clone()).unwrap();
+        read_validated(&mut "case_id\n1\n".as_bytes(), "test.csv", &mut quarantine, &Progress::default(),
+            |_: &mut (i32,)| Ok(()), |_| true).unwrap();
+        assert!(!path.exists());
+    }
+}
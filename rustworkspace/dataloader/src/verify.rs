@@ -0,0 +1,112 @@
+use itertools::Itertools;
+use logging_timer::{finish, stimer};
+use repository::load::TurbineRow;
+use repository::Repository;
+use settings::LoadSettings;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::aliases::Aliases;
+use crate::changes::{self, Difference};
+use crate::pipeline;
+use crate::validation::Quarantine;
+
+/// How the turbines in the database differ from a USWTDB file.
+#[derive(Debug, Default)]
+struct Mismatches {
+    matching: usize,
+    /// In the file but not the database.
+    missing: Vec<i32>,
+    /// In the database but not the file.
+    extra: Vec<i32>,
+    /// The attributes that differ, with the database's value then the file's.
+    different: Vec<(i32, Vec<Difference>)>,
+    rejected: usize,
+}
+
+impl Mismatches {
+    fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.different.is_empty()
+    }
+
+    fn print_details(&self) {
+        for case_id in &self.missing {
+            println!("Turbine {} is in the file but not the database", case_id);
+        }
+        for case_id in &self.extra {
+            println!("Turbine {} is in the database but not the file", case_id);
+        }
+        for (case_id, differences) in &self.different {
+            let differences = differences.iter()
+                .map(|(attribute, db_value, file_value)| format!("{} is {} in the database, {} in the file",
+                    attribute, db_value.as_deref().unwrap_or("NULL"), file_value.as_deref().unwrap_or("NULL")))
+                .join("; ");
+            println!("Turbine {}: {}", case_id, differences);
+        }
+    }
+}
+
+impl fmt::Display for Mismatches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} matching, {} different, {} missing from the database, {} not in the file, {} rejected by validation",
+            self.matching, self.different.len(), self.missing.len(), self.extra.len(), self.rejected)
+    }
+}
+
+/// Compares the turbines in the database with a USWTDB file, read and
+/// validated as a load would read it, and prints every difference. The
+/// aliases should be those the database was loaded with. Fails if the
+/// database does not match, so that scripts can check the exit code.
+pub async fn verify(db: &mut dyn Repository, file: PathBuf, aliases: Aliases, quarantine: Quarantine,
+    settings: &LoadSettings) -> Result<(), Box<dyn Error>> {
+    let tmr = stimer!("VERIFY_TURBINES");
+    let source = file.display().to_string();
+
+    let states = db.get_state_ids().await?;
+    let (mut rx, reader) = pipeline::spawn_reader(file, states, Arc::new(aliases), quarantine, Arc::default(), settings.channel_capacity);
+    let existing = db.get_case_ids().await?;
+
+    let mut seen = HashSet::with_capacity(existing.len());
+    let mut mismatches = Mismatches::default();
+
+    while let Some(batch) = pipeline::next_batch(&mut rx, settings.batch_size).await {
+        let case_ids = batch.iter()
+            .map(|t| t.case_id)
+            .filter(|case_id| existing.contains(case_id))
+            .collect::<Vec<_>>();
+        let rows = db.get_turbine_rows(&case_ids).await?;
+
+        for t in &batch {
+            // A load ignores all but the first row with a case_id, so verify does too.
+            if !seen.insert(t.case_id) {
+                continue;
+            }
+
+            let expected = TurbineRow::from(t);
+            match rows.get(&t.case_id) {
+                None => mismatches.missing.push(t.case_id),
+                Some(actual) if *actual == expected => mismatches.matching += 1,
+                Some(actual) => mismatches.different.push((t.case_id, changes::differences(actual, &expected))),
+            }
+        }
+    }
+
+    let summary = reader.await??;
+    mismatches.rejected = summary.rejected;
+    // A rejected row is not evidence that the turbine should not exist.
+    seen.extend(summary.rejected_case_ids.iter().copied());
+    mismatches.extra = existing.difference(&seen).copied().sorted().collect();
+
+    mismatches.print_details();
+    println!("Turbines: {}", mismatches);
+    finish!(tmr, "Verified {} turbines", seen.len());
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("The database does not match {}", source).into())
+    }
+}
@@ -0,0 +1,256 @@
+use chrono::NaiveDate;
+use log::{error, info};
+use repository::models;
+use repository::Repository;
+use settings::LoadSettings;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::release::Release;
+use crate::retry;
+use crate::{LoadOpt, WatchOpt};
+
+/// A file changed more recently than this may still be downloading, so is
+/// left for a later scan.
+const SETTLE_TIME: Duration = Duration::from_secs(60);
+
+/// What became of a file found in the watched directory, which names the
+/// subdirectory of the archive it is moved to.
+#[derive(Debug, Clone, Copy)]
+enum Outcome {
+    Loaded,
+    Failed,
+    /// Already loaded, or no newer than the last release loaded.
+    Skipped,
+}
+
+impl Outcome {
+    fn dir_name(self) -> &'static str {
+        match self {
+            Outcome::Loaded => "loaded",
+            Outcome::Failed => "failed",
+            Outcome::Skipped => "skipped",
+        }
+    }
+}
+
+/// Loads each new USWTDB file that appears in the directory, oldest release
+/// first, and moves it to the archive. Files already loaded, or no newer than
+/// the last release loaded, are archived without loading. Each load runs as
+/// the load command would and is recorded in the LoadRun table. Scans until
+/// stopped unless `--once` is given.
+pub async fn watch(opt: WatchOpt, connection_string: &str, settings: &LoadSettings) -> Result<(), Box<dyn Error>> {
+    if opt.load.turbines_file.is_some() || opt.load.dry_run || opt.load.plan_json.is_some() {
+        return Err("watch loads the files it finds, so --turbines-file, --dry-run and --plan-json cannot be given".into());
+    }
+
+    let archive = opt.archive_dir.clone().unwrap_or_else(|| opt.dir.join("processed"));
+    // The files that could not be moved out of the directory, so are not looked at again.
+    let mut handled = HashSet::new();
+    info!("Watching {} for new USWTDB files", opt.dir.display());
+
+    loop {
+        match scan(&opt, &archive, &mut handled, connection_string, settings).await {
+            Ok(0) => {}
+            Ok(failed) if opt.once => return Err(format!("{} files failed to load", failed).into()),
+            Ok(failed) => error!("{} files failed to load; they have been moved to {}", failed, archive.join(Outcome::Failed.dir_name()).display()),
+            Err(err) if opt.once => return Err(err),
+            Err(err) => error!("Cannot scan {}: {}", opt.dir.display(), err),
+        }
+
+        if opt.once {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(opt.interval)).await;
+    }
+}
+
+/// Processes the files in the directory, returning the number that failed to load.
+async fn scan(opt: &WatchOpt, archive: &Path, handled: &mut HashSet<PathBuf>, connection_string: &str,
+    settings: &LoadSettings) -> Result<usize, Box<dyn Error>> {
+    let mut failed = 0;
+    let mut releases = Vec::new();
+    let paths = find_files(&opt.dir)?.into_iter().filter(|path| !handled.contains(path)).collect::<Vec<_>>();
+    for path in paths {
+        match Release::from_file(&path) {
+            Ok(release) => releases.push(release),
+            Err(err) => {
+                error!("Cannot read {}: {}", path.display(), err);
+                archive_file(&path, archive, Outcome::Failed, handled);
+                failed += 1;
+            }
+        }
+    }
+    if releases.is_empty() {
+        return Ok(failed);
+    }
+
+    // The oldest first, so that each is newer than the one loaded before it.
+    releases.sort_by_key(|r| (r.release_date.clone(), version_key(&r.version)));
+
+    let mut db = retry::open(connection_string, settings).await?;
+    crate::migrate_sqlite(db.as_mut(), connection_string).await?;
+
+    for release in releases {
+        if let Some(reason) = skip_reason(db.as_mut(), &release).await? {
+            info!("Skipping {}: {}", release.file_name, reason);
+            archive_file(&release.path, archive, Outcome::Skipped, handled);
+            continue;
+        }
+
+        info!("Loading {}, USWTDB {}", release.file_name, release.version);
+        let load_opt = LoadOpt { turbines_file: Some(release.path.clone()), ..opt.load.clone() };
+        let outcome = match crate::load_files(load_opt, connection_string, settings).await {
+            Ok(()) => Outcome::Loaded,
+            Err(err) => {
+                error!("The load of {} failed: {}", release.file_name, err);
+                failed += 1;
+                Outcome::Failed
+            }
+        };
+        archive_file(&release.path, archive, outcome, handled);
+    }
+
+    Ok(failed)
+}
+
+/// The uswtdb*.zip and uswtdb*.csv files in the directory that are not still
+/// being written.
+fn find_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+        if !name.starts_with("uswtdb") || !(name.ends_with(".zip") || name.ends_with(".csv")) {
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        if matches!(metadata.modified()?.elapsed(), Ok(age) if age < SETTLE_TIME) {
+            info!("Leaving {} until it has finished downloading", entry.path().display());
+            continue;
+        }
+        files.push(entry.path());
+    }
+
+    Ok(files)
+}
+
+/// Why the release should not be loaded, if it should not: it has been
+/// loaded already, or is no newer than the last release loaded.
+async fn skip_reason(db: &mut dyn Repository, release: &Release) -> Result<Option<String>, Box<dyn Error>> {
+    let loaded = db.get_all_releases().await?;
+
+    // A checkpointed load that stopped part way leaves its release behind,
+    // so the file still needs loading.
+    if let Some(same) = loaded.iter().find(|r| r.checksum == release.checksum) {
+        if db.get_load_checkpoint(&release.checksum).await?.is_none() {
+            return Ok(Some(format!("it was loaded as release {} at {}", same.id, same.loaded_at)));
+        }
+    }
+
+    let latest = loaded.iter()
+        .filter(|r| r.checksum != release.checksum)
+        .max_by_key(|r| (r.release_date, version_key(&r.version)));
+    match latest {
+        Some(latest) if !is_newer(release, latest) => Ok(Some(format!("USWTDB {} is no newer than the last release loaded, USWTDB {} ({})",
+            release.version, latest.version, latest.file_name))),
+        _ => Ok(None),
+    }
+}
+
+/// Whether the release is newer than a loaded one, going by their release
+/// dates then their versions, or only their versions if either has no date.
+fn is_newer(release: &Release, loaded: &models::Release) -> bool {
+    let release_date = release.release_date.as_deref()
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+    let version = version_key(&release.version);
+    let loaded_version = version_key(&loaded.version);
+
+    match (release_date, loaded.release_date) {
+        (Some(date), Some(loaded_date)) => (date, version) > (loaded_date, loaded_version),
+        _ => version > loaded_version,
+    }
+}
+
+/// A version such as "4.1" as numbers, so that 4.10 comes after 4.9.
+fn version_key(version: &str) -> Vec<u32> {
+    version.split('.').map(|part| part.parse().unwrap_or(0)).collect()
+}
+
+/// Moves the file into the archive's subdirectory for its outcome. A file that
+/// cannot be moved is noted in `handled` so that it is not processed again.
+fn archive_file(path: &Path, archive: &Path, outcome: Outcome, handled: &mut HashSet<PathBuf>) {
+    let dir = archive.join(outcome.dir_name());
+    let target = dir.join(path.file_name().unwrap_or_default());
+
+    match fs::create_dir_all(&dir).and_then(|()| fs::rename(path, &target)) {
+        Ok(()) => info!("Moved {} to {}", path.display(), target.display()),
+        Err(err) => {
+            error!("Cannot move {} to {}: {}", path.display(), target.display(), err);
+            handled.insert(path.to_path_buf());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(version: &str, date: Option<&str>) -> Release {
+        Release {
+            path: PathBuf::from("uswtdb.zip"),
+            file_name: "uswtdb.zip".to_string(),
+            version: version.to_string(),
+            release_date: date.map(str::to_string),
+            checksum: "new".to_string(),
+        }
+    }
+
+    fn loaded(version: &str, date: Option<&str>) -> models::Release {
+        models::Release {
+            id: 1,
+            version: version.to_string(),
+            release_date: date.map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap()),
+            file_name: "uswtdb_loaded.zip".to_string(),
+            checksum: "loaded".to_string(),
+            turbine_count: 0,
+            new_turbines: 0,
+            changed_turbines: 0,
+            decommissioned_turbines: 0,
+            loaded_at: chrono::NaiveDateTime::parse_from_str("2021-06-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        }
+    }
+
+    #[test]
+    fn version_key_orders_numerically() {
+        assert!(version_key("4.10") > version_key("4.9"));
+        assert!(version_key("5") > version_key("4.9"));
+        assert_eq!(version_key("4.x"), vec![4, 0]);
+    }
+
+    #[test]
+    fn later_date_is_newer() {
+        assert!(is_newer(&release("4.0", Some("2021-05-28")), &loaded("4.1", Some("2021-01-08"))));
+        assert!(!is_newer(&release("4.1", Some("2021-01-08")), &loaded("4.0", Some("2021-05-28"))));
+    }
+
+    #[test]
+    fn reissue_on_same_date_is_newer_by_version() {
+        assert!(is_newer(&release("4.2", Some("2021-05-28")), &loaded("4.1", Some("2021-05-28"))));
+        assert!(!is_newer(&release("4.1", Some("2021-05-28")), &loaded("4.1", Some("2021-05-28"))));
+    }
+
+    #[test]
+    fn without_dates_compares_versions() {
+        assert!(is_newer(&release("4.10", None), &loaded("4.9", Some("2021-05-28"))));
+        assert!(!is_newer(&release("4.1", Some("2021-05-28")), &loaded("4.1", None)));
+    }
+}
@@ -0,0 +1,516 @@
+//! Runs the dataloader against a SQLite database in a scratch directory and
+//! checks what it leaves in the database.
+
+use repository::models::ChangeType;
+use repository::Repository;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const STATES: &str = "\
+StateType,Name,Abbreviation,Capital,Population,Area
+State, California ,CA ,Sacramento ,39512223,163696
+State, Iowa ,IA ,Des Moines ,3155070,56273
+";
+
+const TURBINES_HEADER: &str = "case_id,faa_ors,faa_asn,usgs_pr_id,eia_id,t_state,t_county,t_fips,p_name,p_year,p_tnum,p_cap,t_manu,t_model,t_cap,t_hh,t_rd,t_rsa,t_ttlh,retrofit,retrofit_year,t_conf_atr,t_conf_loc,t_img_date,t_img_srce,xlong,ylat\n";
+
+const KERN_1: &str = "3073403,,,5818,52161,CA,Kern County,6029,251 Wind,1987,2,1.5,Vestas,V17,95,,,,,0,,2,3,5/8/2018,Digital Globe,-118.352219,35.088993\n";
+const KERN_2: &str = "3072704,,,5146,52161,CA,Kern County,6029,251 Wind,1987,2,1.5,Vestas,V17,95,,,,,0,,2,3,5/8/2018,Digital Globe,-118.364197,35.077644\n";
+const IOWA: &str = "3000001,,2013-WTE-2956-OE,,56291,IA,Franklin County,19069,Crystal Lake,2008,1,1.5,Vestas,V90,1500,80,90,6362,125,0,,3,3,6/1/2019,NAIP,-93.2,42.7\n";
+/// KERN_1 with a hub height.
+const KERN_1_HH: &str = "3073403,,,5818,52161,CA,Kern County,6029,251 Wind,1987,2,1.5,Vestas,V17,95,25,,,,0,,2,3,5/8/2018,Digital Globe,-118.352219,35.088993\n";
+/// Outside the US, so rejected.
+const OFFSHORE: &str = "3099999,,,,,CA,Kern County,6029,251 Wind,1987,2,1.5,Vestas,V17,95,,,,,0,,2,3,5/8/2018,Digital Globe,10.0,35.0\n";
+
+/// A directory of input files and a SQLite database for one test.
+struct Scratch {
+    dir: PathBuf,
+}
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("load").join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("uswindpowerstats.toml"), "").unwrap();
+        Scratch { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    fn write(&self, name: &str, contents: &str) -> String {
+        let path = self.path(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path.display().to_string()
+    }
+
+    fn turbines(&self, name: &str, rows: &[&str]) -> String {
+        self.write(name, &format!("{}{}", TURBINES_HEADER, rows.concat()))
+    }
+
+    fn connection_string(&self) -> String {
+        format!("sqlite://{}", self.path("uswps.db").display())
+    }
+
+    /// Runs the dataloader in the directory, with its configuration file and database.
+    fn run(&self, args: &[&str]) -> Output {
+        let output = Command::new(env!("CARGO_BIN_EXE_dataloader"))
+            .current_dir(&self.dir)
+            .env_remove("USWPS_CONFIG")
+            .arg("--database-url")
+            .arg(self.connection_string())
+            .args(args)
+            .output()
+            .unwrap();
+        println!("{}", String::from_utf8_lossy(&output.stdout));
+        println!("{}", String::from_utf8_lossy(&output.stderr));
+        output
+    }
+
+    fn load(&self, args: &[&str]) -> Output {
+        let output = self.run(&[&["load"], args].concat());
+        assert!(output.status.success(), "load {:?} failed", args);
+        output
+    }
+
+    async fn db(&self) -> Box<dyn Repository> {
+        repository::open(&self.connection_string()).await.unwrap()
+    }
+}
+
+async fn case_ids(db: &mut dyn Repository) -> Vec<i32> {
+    let mut case_ids = db.get_all_turbines().await.unwrap().iter().map(|t| t.case_id).collect::<Vec<_>>();
+    case_ids.sort_unstable();
+    case_ids
+}
+
+#[tokio::test]
+async fn load_creates_a_sqlite_database() {
+    let scratch = Scratch::new("create");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2, IOWA]);
+
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines]);
+
+    let mut db = scratch.db().await;
+    assert_eq!(db.get_all_states().await.unwrap().len(), 2);
+    assert_eq!(case_ids(db.as_mut()).await, vec![3000001, 3072704, 3073403]);
+    assert_eq!(db.get_all_counties().await.unwrap().len(), 2);
+    assert_eq!(db.get_all_projects().await.unwrap().len(), 2);
+    assert_eq!(db.get_turbines_by_faa_asn("2013-WTE-2956-OE").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn failed_load_keeps_the_previous_data() {
+    let scratch = Scratch::new("rollback");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("v1/uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines]);
+
+    // Half the rows are rejected, more than the 1% allowed, after the good row has been written.
+    let turbines = scratch.turbines("v2/uswtdb_v4_2_20211001.csv", &[IOWA, OFFSHORE]);
+    let output = scratch.run(&["load", "--turbines-file", &turbines]);
+    assert!(!output.status.success());
+
+    let mut db = scratch.db().await;
+    assert_eq!(case_ids(db.as_mut()).await, vec![3072704, 3073403]);
+    assert_eq!(db.get_all_counties().await.unwrap().len(), 1);
+    assert_eq!(db.get_all_releases().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn load_applies_the_differences_from_the_last_release() {
+    let scratch = Scratch::new("incremental");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("v1/uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines]);
+    let before = scratch.db().await.get_all_turbines().await.unwrap();
+
+    let turbines = scratch.turbines("v2/uswtdb_v4_2_20211001.csv", &[KERN_1_HH, IOWA]);
+    let output = scratch.load(&["--turbines-file", &turbines]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("1 new, 1 changed, 1 decommissioned, 0 unchanged"));
+
+    let mut db = scratch.db().await;
+    assert_eq!(case_ids(db.as_mut()).await, vec![3000001, 3073403]);
+    // The changed turbine is updated in place rather than replaced.
+    let kern = db.get_all_turbines().await.unwrap().into_iter().find(|t| t.case_id == 3073403).unwrap();
+    assert_eq!(kern.id, before.iter().find(|t| t.case_id == 3073403).unwrap().id);
+    assert_eq!(kern.hub_height, Some("25".parse().unwrap()));
+
+    let releases = db.get_all_releases().await.unwrap();
+    let release = releases.iter().find(|r| r.version == "4.2").unwrap();
+    assert_eq!((release.new_turbines, release.changed_turbines, release.decommissioned_turbines), (1, 1, 1));
+    // The unreferenced county and project are removed with the turbine.
+    assert_eq!(db.get_all_counties().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn same_release_loaded_again_changes_nothing() {
+    let scratch = Scratch::new("reload");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2, IOWA]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines]);
+    let before = scratch.db().await.get_all_turbines().await.unwrap();
+
+    let output = scratch.load(&["--turbines-file", &turbines]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("0 new, 0 changed, 0 decommissioned, 3 unchanged"));
+
+    let after = scratch.db().await.get_all_turbines().await.unwrap();
+    assert_eq!(after.iter().map(|t| t.id).collect::<Vec<_>>(), before.iter().map(|t| t.id).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn history_is_kept_by_case_id() {
+    let scratch = Scratch::new("history");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("v1/uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines]);
+    let turbines = scratch.turbines("v2/uswtdb_v4_2_20211001.csv", &[KERN_1_HH]);
+    scratch.load(&["--turbines-file", &turbines]);
+
+    let mut db = scratch.db().await;
+    let history = db.get_turbine_history(3072704).await.unwrap();
+    assert_eq!(history.iter().map(|h| h.change_type).collect::<Vec<_>>(), vec![ChangeType::New, ChangeType::Decommissioned]);
+
+    let history = db.get_turbine_history(3073403).await.unwrap();
+    let changes = history.iter()
+        .map(|h| (h.change_type, h.attribute.as_deref(), h.old_value.as_deref(), h.new_value.as_deref()))
+        .collect::<Vec<_>>();
+    assert_eq!(changes, vec![(ChangeType::New, None, None, None), (ChangeType::Changed, Some("HubHeight"), None, Some("25.00"))]);
+
+    assert!(db.get_turbine_history(1).await.is_err());
+}
+
+#[tokio::test]
+async fn small_batches_load_the_same_turbines() {
+    let scratch = Scratch::new("batches");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2, IOWA]);
+
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines, "--batch-size", "1"]);
+
+    let mut db = scratch.db().await;
+    assert_eq!(case_ids(db.as_mut()).await, vec![3000001, 3072704, 3073403]);
+    assert_eq!(db.get_all_projects().await.unwrap().len(), 2);
+    assert_eq!(db.get_all_models().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn load_keeps_the_usgs_identifiers() {
+    let scratch = Scratch::new("identifiers");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2, IOWA]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines]);
+
+    let mut db = scratch.db().await;
+    let mut kern = db.get_turbines_by_eia_id(52161).await.unwrap().iter().map(|t| (t.case_id, t.usgs_pr_id)).collect::<Vec<_>>();
+    kern.sort_unstable();
+    assert_eq!(kern, vec![(3072704, Some(5146)), (3073403, Some(5818))]);
+
+    let iowa = db.get_turbines_by_faa_asn("2013-WTE-2956-OE").await.unwrap().remove(0);
+    assert_eq!((iowa.case_id, iowa.eia_id, iowa.faa_ors, iowa.usgs_pr_id), (3000001, Some(56291), None, None));
+    assert!(db.get_turbines_by_eia_id(1).await.unwrap().is_empty());
+
+    let mut years = db.get_all_projects().await.unwrap().iter().map(|p| (p.name.clone(), p.year)).collect::<Vec<_>>();
+    years.sort();
+    assert_eq!(years, vec![("251 Wind".to_string(), Some(1987)), ("Crystal Lake".to_string(), Some(2008))]);
+}
+
+#[tokio::test]
+async fn project_capacity_reads_back_exactly() {
+    let scratch = Scratch::new("capacity");
+    let states = scratch.write("states.csv", STATES);
+    let iowa = IOWA.replace(",1,1.5,", ",1,1.65,");
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[&iowa]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines]);
+
+    let mut db = scratch.db().await;
+    let project = db.get_all_projects().await.unwrap().remove(0);
+    assert_eq!(project.capacity_mw, Some("1.650".parse().unwrap()));
+    assert_eq!(project.capacity_mw.unwrap().to_string(), "1.650");
+}
+
+#[tokio::test]
+async fn load_keeps_the_aliases_in_the_file() {
+    let scratch = Scratch::new("aliases");
+    let states = scratch.write("states.csv", STATES);
+    let aliases = scratch.write("v1/aliases.csv", "manufacturer,model,alias\nVestas,,Vestas Wind Systems\nVestas,,VWS\nVestas,V90,V-90\nVestas,V17,V-17\n");
+    let iowa = IOWA.replace("Vestas,V90", "VESTAS WIND SYSTEMS,v-90");
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1, &iowa]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines, "--aliases-file", &aliases]);
+
+    let mut db = scratch.db().await;
+    let turbine = db.get_turbines_by_faa_asn("2013-WTE-2956-OE").await.unwrap().remove(0);
+    assert_eq!((turbine.raw_manufacturer.as_deref(), turbine.raw_model.as_deref()), (Some("VESTAS WIND SYSTEMS"), Some("v-90")));
+    assert_eq!(db.get_all_manufacturers().await.unwrap().len(), 1);
+    assert_eq!(db.get_all_manufacturer_aliases().await.unwrap().len(), 2);
+    assert_eq!(db.get_all_model_aliases().await.unwrap().len(), 2);
+
+    // The next load's file drops an alias of each.
+    let aliases = scratch.write("v2/aliases.csv", "manufacturer,model,alias\nVestas,,Vestas Wind Systems\nVestas,V90,V-90\n");
+    scratch.load(&["--turbines-file", &turbines, "--aliases-file", &aliases]);
+
+    let mut db = scratch.db().await;
+    let manufacturer_aliases = db.get_all_manufacturer_aliases().await.unwrap();
+    assert_eq!(manufacturer_aliases.iter().map(|a| a.alias.as_str()).collect::<Vec<_>>(), vec!["Vestas Wind Systems"]);
+    let model_aliases = db.get_all_model_aliases().await.unwrap();
+    assert_eq!(model_aliases.iter().map(|a| (a.alias.as_str(), a.model_name.as_str())).collect::<Vec<_>>(), vec![("V-90", "V90")]);
+
+    // A load without the file leaves the aliases alone.
+    scratch.load(&["--turbines-file", &turbines]);
+    assert_eq!(scratch.db().await.get_all_model_aliases().await.unwrap().len(), 1);
+
+    // One with an emptied file removes them all.
+    let aliases = scratch.write("v3/aliases.csv", "manufacturer,model,alias\n");
+    scratch.load(&["--turbines-file", &turbines, "--aliases-file", &aliases]);
+    let mut db = scratch.db().await;
+    assert!(db.get_all_manufacturer_aliases().await.unwrap().is_empty());
+    assert!(db.get_all_model_aliases().await.unwrap().is_empty());
+}
+
+/// The whitespace separated columns of the line of a table that starts with `name`.
+fn table_row(stdout: &str, name: &str) -> Vec<String> {
+    let line = stdout.lines().find(|line| line.starts_with(name)).unwrap_or_else(|| panic!("no {} row", name));
+    line[name.len()..].split_whitespace().map(str::to_string).collect()
+}
+
+#[tokio::test]
+async fn verify_lists_the_differences_from_the_file() {
+    let scratch = Scratch::new("verify");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2, IOWA]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines]);
+
+    let output = scratch.run(&["verify", "--turbines-file", &turbines]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Turbines: 3 matching, 0 different, 0 missing from the database, 0 not in the file"));
+
+    let other = scratch.turbines("other.csv", &[KERN_1_HH, KERN_2, OFFSHORE]);
+    let output = scratch.run(&["verify", "--turbines-file", &other]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Turbine 3073403: HubHeight is NULL in the database, 25.00 in the file"), "{}", stdout);
+    assert!(stdout.contains("Turbine 3000001 is in the database but not the file"), "{}", stdout);
+    assert!(stdout.contains("Turbines: 1 matching, 1 different, 0 missing from the database, 1 not in the file, 1 rejected by validation"), "{}", stdout);
+}
+
+#[tokio::test]
+async fn exported_turbines_verify_against_the_database() {
+    let scratch = Scratch::new("export");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1_HH, KERN_2, IOWA]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines]);
+
+    let exported = scratch.path("exported.csv");
+    let output = scratch.run(&["export", "--output", exported.to_str().unwrap()]);
+    assert!(output.status.success());
+
+    let csv = fs::read_to_string(&exported).unwrap();
+    assert_eq!(csv.lines().next().unwrap(), TURBINES_HEADER.trim_end());
+    assert_eq!(csv.lines().count(), 4);
+    assert!(csv.contains("3073403,,,5818,52161,CA,Kern County,6029,251 Wind,1987,2,1.5,Vestas,V17,95,25,,,,0,,2,3,5/8/2018,Digital Globe,-118.352219,35.088993\n"), "{}", csv);
+
+    let output = scratch.run(&["verify", "--turbines-file", exported.to_str().unwrap()]);
+    assert!(output.status.success());
+}
+
+#[tokio::test]
+async fn stats_totals_the_turbines_by_state_manufacturer_and_year() {
+    let scratch = Scratch::new("stats");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2, IOWA]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines]);
+
+    let output = scratch.run(&["stats"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(table_row(&stdout, "CA California"), vec!["2", "0.2"]);
+    assert_eq!(table_row(&stdout, "IA Iowa"), vec!["1", "1.5"]);
+    assert_eq!(table_row(&stdout, "Vestas"), vec!["3", "1.7"]);
+    assert_eq!(table_row(&stdout, "1987"), vec!["2", "0.2"]);
+    assert_eq!(table_row(&stdout, "Total"), vec!["3", "1.7"]);
+}
+
+#[tokio::test]
+async fn dry_run_plans_the_changes_without_writing_anything() {
+    let scratch = Scratch::new("dry_run");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("v1/uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines]);
+    let before = scratch.db().await.get_all_turbines().await.unwrap();
+
+    let turbines = scratch.turbines("v2/uswtdb_v4_2_20211001.csv", &[KERN_1_HH, IOWA, OFFSHORE]);
+    let plan = scratch.path("plan.json");
+    let output = scratch.load(&["--turbines-file", &turbines, "--plan-json", plan.to_str().unwrap(),
+        "--max-rejected-percent", "50", "--report-file", "report.json", "--record-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Dry run: nothing has been written"), "{}", stdout);
+    assert!(stdout.contains("    Turbine: 1 inserted, 1 updated, 1 removed"), "{}", stdout);
+
+    let plan: serde_json::Value = serde_json::from_str(&fs::read_to_string(&plan).unwrap()).unwrap();
+    assert_eq!(plan["Turbine"]["inserted"], serde_json::json!(["3000001"]));
+    assert_eq!(plan["Turbine"]["removed"], serde_json::json!(["3072704"]));
+
+    // Neither the rejected row nor the report is written.
+    assert!(!scratch.path("quarantine.csv").exists());
+    assert!(!scratch.path("report.json").exists());
+
+    let mut db = scratch.db().await;
+    assert_eq!(db.get_all_turbines().await.unwrap(), before);
+    assert_eq!(db.get_all_releases().await.unwrap().len(), 1);
+    assert_eq!(db.get_all_counties().await.unwrap().len(), 1);
+    assert!(db.get_all_load_runs().await.unwrap().is_empty());
+}
+
+fn report_tables(scratch: &Scratch) -> serde_json::Value {
+    let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(scratch.path("report.json")).unwrap()).unwrap();
+    assert_eq!(report["succeeded"], false);
+    report["tables"].clone()
+}
+
+#[tokio::test]
+async fn failed_load_reports_the_changes_it_committed() {
+    let scratch = Scratch::new("report_committed");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2, OFFSHORE]);
+
+    // Rolled back as a whole, states included.
+    let output = scratch.run(&["load", "--us-states-file", &states, "--turbines-file", &turbines, "--report-file", "report.json"]);
+    assert!(!output.status.success());
+    assert_eq!(report_tables(&scratch), serde_json::json!({}));
+
+    // Each batch is committed before the rejected row fails the load.
+    scratch.load(&["--us-states-file", &states]);
+    let output = scratch.run(&["load", "--turbines-file", &turbines, "--report-file", "report.json", "--checkpoint", "--batch-size", "1"]);
+    assert!(!output.status.success());
+    let tables = report_tables(&scratch);
+    assert_eq!(tables["Turbine"], serde_json::json!({"inserted": 2, "updated": 0, "removed": 0}));
+    assert_eq!(tables["County"]["inserted"], 1);
+    assert_eq!(case_ids(scratch.db().await.as_mut()).await, vec![3072704, 3073403]);
+}
+
+#[tokio::test]
+async fn checkpointed_load_resumes_after_its_last_batch() {
+    let scratch = Scratch::new("resume");
+    let states = scratch.write("states.csv", STATES);
+    scratch.load(&["--us-states-file", &states]);
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2, OFFSHORE]);
+
+    // The rejected row fails the load after both batches were committed.
+    let output = scratch.run(&["load", "--turbines-file", &turbines, "--checkpoint", "--batch-size", "1"]);
+    assert!(!output.status.success());
+    assert_eq!(case_ids(scratch.db().await.as_mut()).await, vec![3072704, 3073403]);
+
+    let output = scratch.load(&["--turbines-file", &turbines, "--resume", "--batch-size", "1", "--max-rejected-percent", "50"]);
+    // The counts include the turbines loaded before the failure.
+    assert!(String::from_utf8_lossy(&output.stdout).contains("USWTDB 4.1 turbines: 2 new, 0 changed, 0 decommissioned, 0 unchanged"));
+
+    let mut db = scratch.db().await;
+    assert_eq!(case_ids(db.as_mut()).await, vec![3072704, 3073403]);
+    let releases = db.get_all_releases().await.unwrap();
+    assert_eq!(releases.iter().map(|r| (r.turbine_count, r.new_turbines)).collect::<Vec<_>>(), vec![(2, 2)]);
+
+    // The completed load removed its checkpoint, so the file is loaded from the start.
+    let output = scratch.load(&["--turbines-file", &turbines, "--resume", "--max-rejected-percent", "50"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("USWTDB 4.1 turbines: 0 new, 0 changed, 0 decommissioned, 2 unchanged"));
+}
+
+#[tokio::test]
+async fn checkpointed_load_over_the_limit_commits_nothing() {
+    let scratch = Scratch::new("checkpoint_limit");
+    let states = scratch.write("states.csv", STATES);
+    scratch.load(&["--us-states-file", &states]);
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[OFFSHORE, KERN_1, KERN_2]);
+
+    // Half the rows up to the first batch were rejected, so it is not committed.
+    let output = scratch.run(&["load", "--turbines-file", &turbines, "--checkpoint", "--batch-size", "1"]);
+    assert!(!output.status.success());
+
+    let mut db = scratch.db().await;
+    assert!(case_ids(db.as_mut()).await.is_empty());
+    assert!(db.get_all_releases().await.unwrap().is_empty());
+    assert!(db.get_all_counties().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn sqlite_writes_its_dimensions_in_the_load_transaction() {
+    let scratch = Scratch::new("parallelism");
+    let states = scratch.write("states.csv", STATES);
+    let turbines = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2, IOWA]);
+
+    // SQLite has no pool to commit on, so needs no --commit-dimensions-early.
+    let output = scratch.load(&["--us-states-file", &states, "--turbines-file", &turbines, "--dimension-parallelism", "4"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("a speedup of"));
+
+    let mut db = scratch.db().await;
+    assert_eq!(case_ids(db.as_mut()).await, vec![3000001, 3072704, 3073403]);
+    assert_eq!(db.get_all_models().await.unwrap().len(), 2);
+}
+
+/// Backdates a file, as the watch command leaves files changed in the last minute.
+fn settle(path: &str) {
+    let file = fs::OpenOptions::new().write(true).open(path).unwrap();
+    file.set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(120)).unwrap();
+}
+
+fn archived(scratch: &Scratch, outcome: &str) -> Vec<String> {
+    let dir = scratch.path("incoming/processed").join(outcome);
+    let mut names = fs::read_dir(dir).map_or_else(|_| Vec::new(), |entries| {
+        entries.map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect::<Vec<_>>()
+    });
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn watch_loads_the_new_releases_and_archives_the_rest() {
+    let scratch = Scratch::new("watch");
+    let states = scratch.write("states.csv", STATES);
+    let loaded = scratch.turbines("uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2]);
+    scratch.load(&["--us-states-file", &states, "--turbines-file", &loaded]);
+
+    let older = scratch.turbines("incoming/uswtdb_v4_0_20210501.csv", &[KERN_1]);
+    let newer = scratch.turbines("incoming/uswtdb_v4_2_20211001.csv", &[KERN_1_HH, IOWA]);
+    let again = scratch.turbines("incoming/uswtdb_v4_1_20210721.csv", &[KERN_1, KERN_2]);
+    let downloading = scratch.turbines("incoming/uswtdb_v4_3_20220101.csv", &[KERN_1]);
+    for path in &[&older, &newer, &again] {
+        settle(path);
+    }
+
+    let output = scratch.run(&["watch", scratch.path("incoming").to_str().unwrap(), "--once"]);
+    assert!(output.status.success());
+
+    assert_eq!(archived(&scratch, "loaded"), vec!["uswtdb_v4_2_20211001.csv"]);
+    assert_eq!(archived(&scratch, "skipped"), vec!["uswtdb_v4_0_20210501.csv", "uswtdb_v4_1_20210721.csv"]);
+    assert!(archived(&scratch, "failed").is_empty());
+    assert!(PathBuf::from(&downloading).exists());
+
+    let mut db = scratch.db().await;
+    assert_eq!(case_ids(db.as_mut()).await, vec![3000001, 3073403]);
+    let runs = db.get_all_load_runs().await.unwrap();
+    assert_eq!(runs.iter().map(|r| (r.file_name.as_deref(), r.succeeded)).collect::<Vec<_>>(), vec![(Some("uswtdb_v4_2_20211001.csv"), true)]);
+}
+
+#[tokio::test]
+async fn watch_once_fails_if_a_file_fails_to_load() {
+    let scratch = Scratch::new("watch_failed");
+    let states = scratch.write("states.csv", STATES);
+    scratch.load(&["--us-states-file", &states]);
+
+    let rejected = scratch.turbines("incoming/uswtdb_v4_1_20210721.csv", &[KERN_1, OFFSHORE]);
+    settle(&rejected);
+
+    let output = scratch.run(&["watch", scratch.path("incoming").to_str().unwrap(), "--once"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 files failed to load"));
+    assert_eq!(archived(&scratch, "failed"), vec!["uswtdb_v4_1_20210721.csv"]);
+
+    let mut db = scratch.db().await;
+    assert!(case_ids(db.as_mut()).await.is_empty());
+    let runs = db.get_all_load_runs().await.unwrap();
+    assert_eq!(runs.iter().map(|r| r.succeeded).collect::<Vec<_>>(), vec![false]);
+}
@@ -0,0 +1,149 @@
+-- The original schema. Every object is guarded so that this can also be used
+-- to baseline a database that was created by hand before migrations existed.
+
+IF OBJECT_ID(N'dbo.State', N'U') IS NULL
+CREATE TABLE dbo.State (
+    Id CHAR(2) NOT NULL CONSTRAINT PK_State PRIMARY KEY,
+    Name NVARCHAR(50) NOT NULL,
+    Capital NVARCHAR(50) NULL,
+    Population INT NULL,
+    AreaSquareKm INT NULL,
+    StateType CHAR(1) NOT NULL CONSTRAINT CK_State_StateType CHECK (StateType IN ('S', 'T', 'F'))
+);
+
+IF OBJECT_ID(N'dbo.County', N'U') IS NULL
+CREATE TABLE dbo.County (
+    Id INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_County PRIMARY KEY,
+    StateId CHAR(2) NOT NULL CONSTRAINT FK_County_State REFERENCES dbo.State(Id),
+    Name NVARCHAR(100) NOT NULL,
+    CONSTRAINT UQ_County_StateId_Name UNIQUE (StateId, Name)
+);
+
+IF OBJECT_ID(N'dbo.Manufacturer', N'U') IS NULL
+CREATE TABLE dbo.Manufacturer (
+    Id INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_Manufacturer PRIMARY KEY,
+    Name NVARCHAR(100) NOT NULL CONSTRAINT UQ_Manufacturer_Name UNIQUE
+);
+
+IF OBJECT_ID(N'dbo.Model', N'U') IS NULL
+CREATE TABLE dbo.Model (
+    Id INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_Model PRIMARY KEY,
+    ManufacturerId INT NOT NULL CONSTRAINT FK_Model_Manufacturer REFERENCES dbo.Manufacturer(Id),
+    Name NVARCHAR(100) NOT NULL,
+    CapacityKW INT NULL,
+    HubHeight DECIMAL(6, 2) NULL,
+    RotorDiameter DECIMAL(6, 2) NULL,
+    RotorSweptArea DECIMAL(9, 2) NULL,
+    TotalHeightToTip DECIMAL(6, 2) NULL,
+    CONSTRAINT UQ_Model_ManufacturerId_Name UNIQUE (ManufacturerId, Name)
+);
+
+IF OBJECT_ID(N'dbo.Project', N'U') IS NULL
+CREATE TABLE dbo.Project (
+    Id INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_Project PRIMARY KEY,
+    Name NVARCHAR(200) NOT NULL CONSTRAINT UQ_Project_Name UNIQUE,
+    NumTurbines SMALLINT NULL,
+    CapacityMW DECIMAL(9, 3) NULL
+);
+
+IF OBJECT_ID(N'dbo.ImageSource', N'U') IS NULL
+CREATE TABLE dbo.ImageSource (
+    Id TINYINT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_ImageSource PRIMARY KEY,
+    Name NVARCHAR(50) NOT NULL CONSTRAINT UQ_ImageSource_Name UNIQUE
+);
+
+IF OBJECT_ID(N'dbo.Turbine', N'U') IS NULL
+CREATE TABLE dbo.Turbine (
+    Id INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_Turbine PRIMARY KEY,
+    CountyId INT NOT NULL CONSTRAINT FK_Turbine_County REFERENCES dbo.County(Id),
+    ProjectId INT NOT NULL CONSTRAINT FK_Turbine_Project REFERENCES dbo.Project(Id),
+    ModelId INT NOT NULL CONSTRAINT FK_Turbine_Model REFERENCES dbo.Model(Id),
+    ImageSourceId TINYINT NOT NULL CONSTRAINT FK_Turbine_ImageSource REFERENCES dbo.ImageSource(Id),
+    Retrofit BIT NOT NULL,
+    RetrofitYear SMALLINT NULL,
+    AttributesConfidenceLevel TINYINT NOT NULL CONSTRAINT CK_Turbine_AttributesConfidenceLevel CHECK (AttributesConfidenceLevel BETWEEN 1 AND 3),
+    LocationConfidenceLevel TINYINT NOT NULL CONSTRAINT CK_Turbine_LocationConfidenceLevel CHECK (LocationConfidenceLevel BETWEEN 1 AND 3),
+    ImageDate DATE NULL,
+    Latitude DECIMAL(9, 6) NOT NULL,
+    Longitude DECIMAL(9, 6) NOT NULL
+);
+GO
+
+-- Inserts or updates a model, identified by its manufacturer and name.
+CREATE OR ALTER PROCEDURE dbo.model_upsert
+    @ManufacturerName NVARCHAR(100),
+    @Name NVARCHAR(100),
+    @CapacityKW INT,
+    @HubHeight DECIMAL(6, 2),
+    @RotorDiameter DECIMAL(6, 2),
+    @RotorSweptArea DECIMAL(9, 2),
+    @TotalHeightToTip DECIMAL(6, 2)
+AS
+BEGIN
+    SET NOCOUNT ON;
+
+    DECLARE @ManufacturerId INT = (SELECT Id FROM dbo.Manufacturer WHERE Name = @ManufacturerName);
+
+    BEGIN TRANSACTION;
+
+    UPDATE dbo.Model WITH (UPDLOCK, SERIALIZABLE)
+    SET CapacityKW = @CapacityKW, HubHeight = @HubHeight, RotorDiameter = @RotorDiameter,
+        RotorSweptArea = @RotorSweptArea, TotalHeightToTip = @TotalHeightToTip
+    WHERE ManufacturerId = @ManufacturerId AND Name = @Name;
+
+    IF @@ROWCOUNT = 0 BEGIN
+        INSERT INTO dbo.Model (ManufacturerId, Name, CapacityKW, HubHeight, RotorDiameter, RotorSweptArea, TotalHeightToTip)
+        VALUES (@ManufacturerId, @Name, @CapacityKW, @HubHeight, @RotorDiameter, @RotorSweptArea, @TotalHeightToTip);
+    END
+
+    COMMIT TRANSACTION;
+END
+GO
+
+-- Inserts or updates a turbine, identified by its location. The dimensions
+-- are passed by name and resolved to their surrogate keys here.
+CREATE OR ALTER PROCEDURE dbo.turbine_upsert
+    @StateId CHAR(2),
+    @CountyName NVARCHAR(100),
+    @ProjectName NVARCHAR(200),
+    @ManufacturerName NVARCHAR(100),
+    @ModelName NVARCHAR(100),
+    @ImageSourceName NVARCHAR(50),
+    @Retrofit BIT,
+    @RetrofitYear SMALLINT,
+    @AttributesConfidenceLevel TINYINT,
+    @LocationConfidenceLevel TINYINT,
+    @ImageDate DATE,
+    @Latitude DECIMAL(9, 6),
+    @Longitude DECIMAL(9, 6)
+AS
+BEGIN
+    SET NOCOUNT ON;
+
+    DECLARE @CountyId INT = (SELECT Id FROM dbo.County WHERE StateId = @StateId AND Name = @CountyName);
+    DECLARE @ProjectId INT = (SELECT Id FROM dbo.Project WHERE Name = @ProjectName);
+    DECLARE @ModelId INT = (
+        SELECT M.Id
+        FROM dbo.Model M
+        INNER JOIN dbo.Manufacturer MF ON MF.Id = M.ManufacturerId
+        WHERE MF.Name = @ManufacturerName AND M.Name = @ModelName);
+    DECLARE @ImageSourceId TINYINT = (SELECT Id FROM dbo.ImageSource WHERE Name = @ImageSourceName);
+
+    BEGIN TRANSACTION;
+
+    UPDATE dbo.Turbine WITH (UPDLOCK, SERIALIZABLE)
+    SET CountyId = @CountyId, ProjectId = @ProjectId, ModelId = @ModelId, ImageSourceId = @ImageSourceId,
+        Retrofit = @Retrofit, RetrofitYear = @RetrofitYear,
+        AttributesConfidenceLevel = @AttributesConfidenceLevel, LocationConfidenceLevel = @LocationConfidenceLevel,
+        ImageDate = @ImageDate
+    WHERE Latitude = @Latitude AND Longitude = @Longitude;
+
+    IF @@ROWCOUNT = 0 BEGIN
+        INSERT INTO dbo.Turbine (CountyId, ProjectId, ModelId, ImageSourceId, Retrofit, RetrofitYear,
+            AttributesConfidenceLevel, LocationConfidenceLevel, ImageDate, Latitude, Longitude)
+        VALUES (@CountyId, @ProjectId, @ModelId, @ImageSourceId, @Retrofit, @RetrofitYear,
+            @AttributesConfidenceLevel, @LocationConfidenceLevel, @ImageDate, @Latitude, @Longitude);
+    END
+
+    COMMIT TRANSACTION;
+END
@@ -0,0 +1,13 @@
+-- Turbines are identified by the USWTDB case_id, so that loads can apply just
+-- the differences between releases. Turbines loaded before this have no
+-- case_id; they are removed here and come back as new on the next load, so
+-- the migration only runs on a database with turbines given --allow-data-loss.
+
+ALTER TABLE dbo.Turbine ADD CaseId INT NULL;
+GO
+
+DELETE dbo.Turbine WHERE CaseId IS NULL;
+
+ALTER TABLE dbo.Turbine ALTER COLUMN CaseId INT NOT NULL;
+
+CREATE UNIQUE INDEX UQ_Turbine_CaseId ON dbo.Turbine (CaseId);
@@ -0,0 +1,28 @@
+-- Each load of a USWTDB file is recorded as a release, and every turbine that
+-- is added, changed or decommissioned by it gets rows in TurbineHistory.
+-- History is keyed on CaseId because decommissioned turbines are deleted.
+
+CREATE TABLE dbo.Release (
+    Id INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_Release PRIMARY KEY,
+    Version NVARCHAR(50) NOT NULL,
+    ReleaseDate DATE NULL,
+    FileName NVARCHAR(260) NOT NULL,
+    Checksum CHAR(64) NOT NULL,
+    TurbineCount INT NOT NULL,
+    NewTurbines INT NOT NULL,
+    ChangedTurbines INT NOT NULL,
+    DecommissionedTurbines INT NOT NULL,
+    LoadedAt DATETIME2 NOT NULL CONSTRAINT DF_Release_LoadedAt DEFAULT SYSUTCDATETIME()
+);
+
+CREATE TABLE dbo.TurbineHistory (
+    Id INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_TurbineHistory PRIMARY KEY,
+    ReleaseId INT NOT NULL CONSTRAINT FK_TurbineHistory_Release REFERENCES dbo.Release(Id),
+    CaseId INT NOT NULL,
+    ChangeType CHAR(1) NOT NULL CONSTRAINT CK_TurbineHistory_ChangeType CHECK (ChangeType IN ('N', 'C', 'D')),
+    Attribute NVARCHAR(50) NULL,
+    OldValue NVARCHAR(400) NULL,
+    NewValue NVARCHAR(400) NULL
+);
+
+CREATE INDEX IX_TurbineHistory_CaseId ON dbo.TurbineHistory (CaseId);
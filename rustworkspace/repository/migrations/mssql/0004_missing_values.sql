@@ -0,0 +1,23 @@
+-- The USWTDB marks missing numbers with -9999 and missing names with blanks.
+-- The loader now stores NULL and 'Unknown' instead; bring earlier loads into line.
+
+UPDATE dbo.Model SET CapacityKW = NULL WHERE CapacityKW = -9999;
+UPDATE dbo.Model SET HubHeight = NULL WHERE HubHeight = -9999;
+UPDATE dbo.Model SET RotorDiameter = NULL WHERE RotorDiameter = -9999;
+UPDATE dbo.Model SET RotorSweptArea = NULL WHERE RotorSweptArea = -9999;
+UPDATE dbo.Model SET TotalHeightToTip = NULL WHERE TotalHeightToTip = -9999;
+UPDATE dbo.Project SET CapacityMW = NULL WHERE CapacityMW = -9999;
+UPDATE dbo.Turbine SET RetrofitYear = NULL WHERE RetrofitYear = -9999;
+
+UPDATE dbo.Manufacturer SET Name = 'Unknown'
+WHERE Name = '' AND NOT EXISTS (SELECT 1 FROM dbo.Manufacturer M2 WHERE M2.Name = 'Unknown');
+
+UPDATE M SET Name = 'Unknown'
+FROM dbo.Model M
+WHERE M.Name = '' AND NOT EXISTS (SELECT 1 FROM dbo.Model M2 WHERE M2.ManufacturerId = M.ManufacturerId AND M2.Name = 'Unknown');
+
+UPDATE dbo.Project SET Name = 'Unknown'
+WHERE Name = '' AND NOT EXISTS (SELECT 1 FROM dbo.Project P2 WHERE P2.Name = 'Unknown');
+
+UPDATE dbo.ImageSource SET Name = 'Unknown'
+WHERE Name = '' AND NOT EXISTS (SELECT 1 FROM dbo.ImageSource S2 WHERE S2.Name = 'Unknown');
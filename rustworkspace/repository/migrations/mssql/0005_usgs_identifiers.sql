@@ -0,0 +1,18 @@
+-- Identifiers from the USWTDB that cross-reference other datasets: the FAA
+-- obstruction filings (ORS and ASN), the USGS record, the EIA plant and the
+-- county FIPS code. Filled in for existing rows by the next load.
+
+ALTER TABLE dbo.Turbine ADD
+    FaaOrs NVARCHAR(20) NULL,
+    FaaAsn NVARCHAR(30) NULL,
+    UsgsPrId INT NULL,
+    EiaId INT NULL;
+
+ALTER TABLE dbo.County ADD Fips INT NULL;
+
+ALTER TABLE dbo.Project ADD Year SMALLINT NULL;
+GO
+
+CREATE INDEX IX_Turbine_EiaId ON dbo.Turbine (EiaId);
+
+CREATE INDEX IX_Turbine_FaaAsn ON dbo.Turbine (FaaAsn);
@@ -0,0 +1,17 @@
+-- Counties are identified by their 5-digit FIPS code rather than by name, so
+-- that spelling variants in the USWTDB do not create duplicates. Counties that
+-- share a FIPS code are merged into the one with the lowest Id.
+
+UPDATE T SET CountyId = K.KeepId
+FROM dbo.Turbine T
+INNER JOIN dbo.County C ON C.Id = T.CountyId
+INNER JOIN (SELECT Fips, MIN(Id) AS KeepId FROM dbo.County WHERE Fips IS NOT NULL GROUP BY Fips) K ON K.Fips = C.Fips
+WHERE T.CountyId <> K.KeepId;
+
+DELETE C
+FROM dbo.County C
+WHERE C.Fips IS NOT NULL
+AND C.Id <> (SELECT MIN(C2.Id) FROM dbo.County C2 WHERE C2.Fips = C.Fips);
+
+-- Counties loaded before FIPS codes were kept have none until the next load.
+CREATE UNIQUE INDEX UQ_County_Fips ON dbo.County (Fips) WHERE Fips IS NOT NULL;
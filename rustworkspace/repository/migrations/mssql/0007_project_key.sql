@@ -0,0 +1,15 @@
+-- Projects are identified by name, state and year rather than by name alone,
+-- so that projects that share a name no longer overwrite each other. Existing
+-- projects take the state of their turbines.
+
+ALTER TABLE dbo.Project DROP CONSTRAINT UQ_Project_Name;
+
+ALTER TABLE dbo.Project ADD StateId CHAR(2) NULL CONSTRAINT FK_Project_State REFERENCES dbo.State(Id);
+GO
+
+UPDATE P SET StateId = (
+    SELECT MIN(C.StateId) FROM dbo.Turbine T INNER JOIN dbo.County C ON C.Id = T.CountyId WHERE T.ProjectId = P.Id
+)
+FROM dbo.Project P;
+
+CREATE UNIQUE INDEX UQ_Project_Name_StateId_Year ON dbo.Project (Name, StateId, Year);
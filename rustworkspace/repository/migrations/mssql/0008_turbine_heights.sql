@@ -0,0 +1,13 @@
+-- Hub height, and so the height to the blade tip, vary between installations
+-- of the same model. They are now held on each turbine, starting from the
+-- model's values; the next load replaces them with the turbine's own.
+
+ALTER TABLE dbo.Turbine ADD
+    HubHeight DECIMAL(6, 2) NULL,
+    TotalHeightToTip DECIMAL(6, 2) NULL;
+GO
+
+UPDATE T
+SET T.HubHeight = M.HubHeight, T.TotalHeightToTip = M.TotalHeightToTip
+FROM dbo.Turbine T
+INNER JOIN dbo.Model M ON M.Id = T.ModelId;
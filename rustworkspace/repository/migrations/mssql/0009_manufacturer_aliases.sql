@@ -0,0 +1,33 @@
+-- Other names used in the USWTDB for each manufacturer, e.g. "GE Energy" for
+-- "GE Wind", and for each model, e.g. "GE 1.5 SLE" for its "1.5sle", as
+-- listed in the dataloader's aliases file. A model alias names its model
+-- rather than referencing it, as Model rows are removed once no turbine uses
+-- them but the aliases file still applies.
+-- Turbines keep the manufacturer and model names exactly as given in the
+-- file; existing turbines were loaded before any aliases were applied, so
+-- these are the current names.
+
+CREATE TABLE dbo.ManufacturerAlias (
+    Id INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_ManufacturerAlias PRIMARY KEY,
+    ManufacturerId INT NOT NULL CONSTRAINT FK_ManufacturerAlias_Manufacturer REFERENCES dbo.Manufacturer(Id),
+    Alias NVARCHAR(100) NOT NULL CONSTRAINT UQ_ManufacturerAlias_Alias UNIQUE
+);
+
+CREATE TABLE dbo.ModelAlias (
+    Id INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_ModelAlias PRIMARY KEY,
+    ManufacturerId INT NOT NULL CONSTRAINT FK_ModelAlias_Manufacturer REFERENCES dbo.Manufacturer(Id),
+    Alias NVARCHAR(100) NOT NULL,
+    ModelName NVARCHAR(100) NOT NULL,
+    CONSTRAINT UQ_ModelAlias_ManufacturerId_Alias UNIQUE (ManufacturerId, Alias)
+);
+
+ALTER TABLE dbo.Turbine ADD
+    RawManufacturer NVARCHAR(100) NULL,
+    RawModel NVARCHAR(100) NULL;
+GO
+
+UPDATE T
+SET T.RawManufacturer = MF.Name, T.RawModel = M.Name
+FROM dbo.Turbine T
+INNER JOIN dbo.Model M ON M.Id = T.ModelId
+INNER JOIN dbo.Manufacturer MF ON MF.Id = M.ManufacturerId;
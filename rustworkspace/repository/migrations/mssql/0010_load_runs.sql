@@ -0,0 +1,18 @@
+-- Each run of the dataloader's load command can record itself here, whether
+-- or not the load succeeded, so that operators can see when the data was last
+-- refreshed. A failed load is rolled back, so a run has no Release to refer to.
+-- Report holds the full JSON run report.
+
+CREATE TABLE dbo.LoadRun (
+    Id INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_LoadRun PRIMARY KEY,
+    StartedAt DATETIME2 NOT NULL,
+    FinishedAt DATETIME2 NOT NULL,
+    Succeeded BIT NOT NULL,
+    FileName NVARCHAR(260) NULL,
+    Version NVARCHAR(50) NULL,
+    Checksum CHAR(64) NULL,
+    RejectedRows INT NOT NULL,
+    Warnings INT NOT NULL,
+    ErrorMessage NVARCHAR(MAX) NULL,
+    Report NVARCHAR(MAX) NOT NULL
+);
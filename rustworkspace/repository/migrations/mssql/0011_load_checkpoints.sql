@@ -0,0 +1,14 @@
+-- How far a checkpointed load of a file has got. The row is saved in the
+-- transaction of each batch the load commits, so it always matches the
+-- turbines in the database, and removed when the load completes. A rerun with
+-- --resume reuses the Release and skips the turbines already loaded.
+
+CREATE TABLE dbo.LoadCheckpoint (
+    Checksum CHAR(64) NOT NULL CONSTRAINT PK_LoadCheckpoint PRIMARY KEY,
+    ReleaseId INT NOT NULL CONSTRAINT FK_LoadCheckpoint_Release REFERENCES dbo.Release(Id),
+    Turbines INT NOT NULL,
+    NewTurbines INT NOT NULL,
+    ChangedTurbines INT NOT NULL,
+    UnchangedTurbines INT NOT NULL,
+    SavedAt DATETIME2 NOT NULL CONSTRAINT DF_LoadCheckpoint_SavedAt DEFAULT SYSUTCDATETIME()
+);
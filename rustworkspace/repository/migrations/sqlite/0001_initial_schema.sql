@@ -0,0 +1,62 @@
+-- The original schema. Mirrors the MS SQL one, except that decimals are
+-- stored as REALs and dates as 'YYYY-MM-DD' strings.
+
+CREATE TABLE IF NOT EXISTS State (
+    Id TEXT NOT NULL PRIMARY KEY,
+    Name TEXT NOT NULL,
+    Capital TEXT NULL,
+    Population INTEGER NULL,
+    AreaSquareKm INTEGER NULL,
+    StateType TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS County (
+    Id INTEGER NOT NULL PRIMARY KEY,
+    StateId TEXT NOT NULL REFERENCES State(Id),
+    Name TEXT NOT NULL,
+    UNIQUE (StateId, Name)
+);
+
+CREATE TABLE IF NOT EXISTS Manufacturer (
+    Id INTEGER NOT NULL PRIMARY KEY,
+    Name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS Model (
+    Id INTEGER NOT NULL PRIMARY KEY,
+    ManufacturerId INTEGER NOT NULL REFERENCES Manufacturer(Id),
+    Name TEXT NOT NULL,
+    CapacityKW INTEGER NULL,
+    HubHeight REAL NULL,
+    RotorDiameter REAL NULL,
+    RotorSweptArea REAL NULL,
+    TotalHeightToTip REAL NULL,
+    UNIQUE (ManufacturerId, Name)
+);
+
+CREATE TABLE IF NOT EXISTS Project (
+    Id INTEGER NOT NULL PRIMARY KEY,
+    Name TEXT NOT NULL UNIQUE,
+    NumTurbines INTEGER NULL,
+    CapacityMW REAL NULL
+);
+
+CREATE TABLE IF NOT EXISTS ImageSource (
+    Id INTEGER NOT NULL PRIMARY KEY CHECK (Id BETWEEN 0 AND 255),
+    Name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS Turbine (
+    Id INTEGER NOT NULL PRIMARY KEY,
+    CountyId INTEGER NOT NULL REFERENCES County(Id),
+    ProjectId INTEGER NOT NULL REFERENCES Project(Id),
+    ModelId INTEGER NOT NULL REFERENCES Model(Id),
+    ImageSourceId INTEGER NOT NULL REFERENCES ImageSource(Id),
+    Retrofit INTEGER NOT NULL,
+    RetrofitYear INTEGER NULL,
+    AttributesConfidenceLevel INTEGER NOT NULL,
+    LocationConfidenceLevel INTEGER NOT NULL,
+    ImageDate TEXT NULL,
+    Latitude REAL NOT NULL,
+    Longitude REAL NOT NULL
+);
@@ -0,0 +1,11 @@
+-- Turbines are identified by the USWTDB case_id, so that loads can apply just
+-- the differences between releases. Turbines loaded before this have no
+-- case_id; they are removed here and come back as new on the next load, so
+-- the migration only runs on a database with turbines given --allow-data-loss.
+-- SQLite cannot make an existing column NOT NULL, the loader always sets it.
+
+ALTER TABLE Turbine ADD COLUMN CaseId INTEGER NULL;
+
+DELETE FROM Turbine WHERE CaseId IS NULL;
+
+CREATE UNIQUE INDEX UQ_Turbine_CaseId ON Turbine (CaseId);
@@ -0,0 +1,28 @@
+-- Each load of a USWTDB file is recorded as a release, and every turbine that
+-- is added, changed or decommissioned by it gets rows in TurbineHistory.
+-- History is keyed on CaseId because decommissioned turbines are deleted.
+
+CREATE TABLE Release (
+    Id INTEGER NOT NULL PRIMARY KEY,
+    Version TEXT NOT NULL,
+    ReleaseDate TEXT NULL,
+    FileName TEXT NOT NULL,
+    Checksum TEXT NOT NULL,
+    TurbineCount INTEGER NOT NULL,
+    NewTurbines INTEGER NOT NULL,
+    ChangedTurbines INTEGER NOT NULL,
+    DecommissionedTurbines INTEGER NOT NULL,
+    LoadedAt TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE TurbineHistory (
+    Id INTEGER NOT NULL PRIMARY KEY,
+    ReleaseId INTEGER NOT NULL REFERENCES Release(Id),
+    CaseId INTEGER NOT NULL,
+    ChangeType TEXT NOT NULL CHECK (ChangeType IN ('N', 'C', 'D')),
+    Attribute TEXT NULL,
+    OldValue TEXT NULL,
+    NewValue TEXT NULL
+);
+
+CREATE INDEX IX_TurbineHistory_CaseId ON TurbineHistory (CaseId);
@@ -0,0 +1,22 @@
+-- The USWTDB marks missing numbers with -9999 and missing names with blanks.
+-- The loader now stores NULL and 'Unknown' instead; bring earlier loads into line.
+
+UPDATE Model SET CapacityKW = NULL WHERE CapacityKW = -9999;
+UPDATE Model SET HubHeight = NULL WHERE HubHeight = -9999;
+UPDATE Model SET RotorDiameter = NULL WHERE RotorDiameter = -9999;
+UPDATE Model SET RotorSweptArea = NULL WHERE RotorSweptArea = -9999;
+UPDATE Model SET TotalHeightToTip = NULL WHERE TotalHeightToTip = -9999;
+UPDATE Project SET CapacityMW = NULL WHERE CapacityMW = -9999;
+UPDATE Turbine SET RetrofitYear = NULL WHERE RetrofitYear = -9999;
+
+UPDATE Manufacturer SET Name = 'Unknown'
+WHERE Name = '' AND NOT EXISTS (SELECT 1 FROM Manufacturer M2 WHERE M2.Name = 'Unknown');
+
+UPDATE Model SET Name = 'Unknown'
+WHERE Name = '' AND NOT EXISTS (SELECT 1 FROM Model M2 WHERE M2.ManufacturerId = Model.ManufacturerId AND M2.Name = 'Unknown');
+
+UPDATE Project SET Name = 'Unknown'
+WHERE Name = '' AND NOT EXISTS (SELECT 1 FROM Project P2 WHERE P2.Name = 'Unknown');
+
+UPDATE ImageSource SET Name = 'Unknown'
+WHERE Name = '' AND NOT EXISTS (SELECT 1 FROM ImageSource S2 WHERE S2.Name = 'Unknown');
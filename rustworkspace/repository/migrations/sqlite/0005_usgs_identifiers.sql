@@ -0,0 +1,16 @@
+-- Identifiers from the USWTDB that cross-reference other datasets: the FAA
+-- obstruction filings (ORS and ASN), the USGS record, the EIA plant and the
+-- county FIPS code. Filled in for existing rows by the next load.
+
+ALTER TABLE Turbine ADD COLUMN FaaOrs TEXT NULL;
+ALTER TABLE Turbine ADD COLUMN FaaAsn TEXT NULL;
+ALTER TABLE Turbine ADD COLUMN UsgsPrId INTEGER NULL;
+ALTER TABLE Turbine ADD COLUMN EiaId INTEGER NULL;
+
+ALTER TABLE County ADD COLUMN Fips INTEGER NULL;
+
+ALTER TABLE Project ADD COLUMN Year INTEGER NULL;
+
+CREATE INDEX IX_Turbine_EiaId ON Turbine (EiaId);
+
+CREATE INDEX IX_Turbine_FaaAsn ON Turbine (FaaAsn);
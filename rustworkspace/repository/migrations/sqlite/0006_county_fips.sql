@@ -0,0 +1,18 @@
+-- Counties are identified by their 5-digit FIPS code rather than by name, so
+-- that spelling variants in the USWTDB do not create duplicates. Counties that
+-- share a FIPS code are merged into the one with the lowest Id.
+
+UPDATE Turbine SET CountyId = (
+    SELECT MIN(C2.Id) FROM County C1 INNER JOIN County C2 ON C2.Fips = C1.Fips WHERE C1.Id = Turbine.CountyId
+)
+WHERE CountyId IN (
+    SELECT C.Id FROM County C
+    WHERE C.Fips IS NOT NULL AND C.Id <> (SELECT MIN(C2.Id) FROM County C2 WHERE C2.Fips = C.Fips)
+);
+
+DELETE FROM County
+WHERE Fips IS NOT NULL
+AND Id <> (SELECT MIN(C2.Id) FROM County C2 WHERE C2.Fips = County.Fips);
+
+-- Counties loaded before FIPS codes were kept have none until the next load.
+CREATE UNIQUE INDEX UQ_County_Fips ON County (Fips) WHERE Fips IS NOT NULL;
@@ -0,0 +1,38 @@
+-- Projects are identified by name, state and year rather than by name alone,
+-- so that projects that share a name no longer overwrite each other. Existing
+-- projects take the state of their turbines.
+-- Capacities are now stored as text rather than REALs, so that a capacity
+-- reads back exactly as it was loaded, as it does from the DECIMAL column of
+-- MS SQL. Existing capacities are written with the trailing zeros removed, as
+-- the loader writes them.
+-- SQLite cannot drop the UNIQUE constraint on Name, so the table is rebuilt.
+-- Deferring the foreign keys lets Project be dropped while turbines refer to
+-- it; the check at commit passes once the rows are copied back.
+
+PRAGMA defer_foreign_keys = ON;
+
+CREATE TABLE Project_Old AS SELECT * FROM Project;
+
+DROP TABLE Project;
+
+CREATE TABLE Project (
+    Id INTEGER NOT NULL PRIMARY KEY,
+    Name TEXT NOT NULL,
+    StateId TEXT NULL REFERENCES State(Id),
+    Year INTEGER NULL,
+    NumTurbines INTEGER NULL,
+    CapacityMW TEXT NULL
+);
+
+INSERT INTO Project (Id, Name, StateId, Year, NumTurbines, CapacityMW)
+SELECT P.Id, P.Name,
+    (SELECT MIN(C.StateId) FROM Turbine T INNER JOIN County C ON C.Id = T.CountyId WHERE T.ProjectId = P.Id),
+    P.Year, P.NumTurbines,
+    CASE WHEN P.CapacityMW IS NULL THEN NULL ELSE RTRIM(RTRIM(PRINTF('%.3f', P.CapacityMW), '0'), '.') END
+FROM Project_Old P;
+
+DROP TABLE Project_Old;
+
+-- Unlike MS SQL, SQLite treats NULLs as distinct in a unique index, so they
+-- are indexed as values no project has to treat them as equal.
+CREATE UNIQUE INDEX UQ_Project_Name_StateId_Year ON Project (Name, IFNULL(StateId, ''), IFNULL(Year, -1));
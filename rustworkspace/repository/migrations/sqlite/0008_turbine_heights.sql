@@ -0,0 +1,10 @@
+-- Hub height, and so the height to the blade tip, vary between installations
+-- of the same model. They are now held on each turbine, starting from the
+-- model's values; the next load replaces them with the turbine's own.
+
+ALTER TABLE Turbine ADD COLUMN HubHeight REAL NULL;
+ALTER TABLE Turbine ADD COLUMN TotalHeightToTip REAL NULL;
+
+UPDATE Turbine SET
+    HubHeight = (SELECT M.HubHeight FROM Model M WHERE M.Id = Turbine.ModelId),
+    TotalHeightToTip = (SELECT M.TotalHeightToTip FROM Model M WHERE M.Id = Turbine.ModelId);
@@ -0,0 +1,31 @@
+-- Other names used in the USWTDB for each manufacturer, e.g. "GE Energy" for
+-- "GE Wind", and for each model, e.g. "GE 1.5 SLE" for its "1.5sle", as
+-- listed in the dataloader's aliases file. A model alias names its model
+-- rather than referencing it, as Model rows are removed once no turbine uses
+-- them but the aliases file still applies.
+-- Turbines keep the manufacturer and model names exactly as given in the
+-- file; existing turbines were loaded before any aliases were applied, so
+-- these are the current names.
+
+CREATE TABLE ManufacturerAlias (
+    Id INTEGER NOT NULL PRIMARY KEY,
+    ManufacturerId INTEGER NOT NULL REFERENCES Manufacturer(Id),
+    Alias TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE ModelAlias (
+    Id INTEGER NOT NULL PRIMARY KEY,
+    ManufacturerId INTEGER NOT NULL REFERENCES Manufacturer(Id),
+    Alias TEXT NOT NULL,
+    ModelName TEXT NOT NULL,
+    UNIQUE (ManufacturerId, Alias)
+);
+
+ALTER TABLE Turbine ADD COLUMN RawManufacturer TEXT NULL;
+ALTER TABLE Turbine ADD COLUMN RawModel TEXT NULL;
+
+UPDATE Turbine SET
+    RawManufacturer = (
+        SELECT MF.Name FROM Model M INNER JOIN Manufacturer MF ON MF.Id = M.ManufacturerId WHERE M.Id = Turbine.ModelId
+    ),
+    RawModel = (SELECT M.Name FROM Model M WHERE M.Id = Turbine.ModelId);
@@ -0,0 +1,18 @@
+-- Each run of the dataloader's load command can record itself here, whether
+-- or not the load succeeded, so that operators can see when the data was last
+-- refreshed. A failed load is rolled back, so a run has no Release to refer to.
+-- Report holds the full JSON run report.
+
+CREATE TABLE LoadRun (
+    Id INTEGER NOT NULL PRIMARY KEY,
+    StartedAt TEXT NOT NULL,
+    FinishedAt TEXT NOT NULL,
+    Succeeded INTEGER NOT NULL,
+    FileName TEXT NULL,
+    Version TEXT NULL,
+    Checksum TEXT NULL,
+    RejectedRows INTEGER NOT NULL,
+    Warnings INTEGER NOT NULL,
+    ErrorMessage TEXT NULL,
+    Report TEXT NOT NULL
+);
@@ -0,0 +1,14 @@
+-- How far a checkpointed load of a file has got. The row is saved in the
+-- transaction of each batch the load commits, so it always matches the
+-- turbines in the database, and removed when the load completes. A rerun with
+-- --resume reuses the Release and skips the turbines already loaded.
+
+CREATE TABLE LoadCheckpoint (
+    Checksum TEXT NOT NULL PRIMARY KEY,
+    ReleaseId INTEGER NOT NULL REFERENCES Release(Id),
+    Turbines INTEGER NOT NULL,
+    NewTurbines INTEGER NOT NULL,
+    ChangedTurbines INTEGER NOT NULL,
+    UnchangedTurbines INTEGER NOT NULL,
+    SavedAt TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,695 @@
+use serde::Serialize;
+use std::fmt;
+use tiberius::numeric::Decimal;
+
+/// A value bound to a dimension statement. Each backend converts these to its
+/// own parameter types.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Value {
+    Int(Option<i32>),
+    Decimal(Option<Decimal>),
+    Text(Option<String>),
+}
+
+impl Value {
+    pub fn text(s: &str) -> Self {
+        Value::Text(Some(s.to_string()))
+    }
+
+    /// The value as `get_dimension_rows` gives it.
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            Value::Int(v) => v.map(|v| v.to_string()),
+            Value::Decimal(v) => v.map(|v| v.normalize().to_string()),
+            Value::Text(v) => v.as_ref().map(|v| v.trim().to_string()),
+        }
+    }
+}
+
+/// How a row given to a sync compares with the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowState {
+    /// No row has its key, so it would be inserted.
+    Missing,
+    /// The row with its key has different values, so it would be updated.
+    Different,
+    Same,
+}
+
+/// The SQL differences between the backends that matter to a dimension sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Dialect {
+    MsSql,
+    Sqlite,
+}
+
+impl Dialect {
+    fn table(self, name: &str) -> String {
+        match self {
+            Dialect::MsSql => format!("dbo.{}", name),
+            Dialect::Sqlite => name.to_string(),
+        }
+    }
+
+    /// Converts a column to text, as returned by `get_dimension_rows`.
+    fn text(self, expr: &str) -> String {
+        match self {
+            Dialect::MsSql => format!("CAST({} AS NVARCHAR(4000))", expr),
+            Dialect::Sqlite => format!("CAST({} AS TEXT)", expr),
+        }
+    }
+
+    fn param(self, idx: usize) -> String {
+        match self {
+            Dialect::MsSql => format!("@P{}", idx),
+            Dialect::Sqlite => format!("?{}", idx),
+        }
+    }
+
+    /// The temporary table the keys of the loaded rows are staged in.
+    fn loaded_keys(self) -> &'static str {
+        match self {
+            Dialect::MsSql => "#LoadedKeys",
+            Dialect::Sqlite => "temp.LoadedKeys",
+        }
+    }
+
+    /// Drops the table `create_loaded_keys_sql` creates.
+    pub(crate) fn drop_loaded_keys_sql(self) -> String {
+        format!("DROP TABLE {}", self.loaded_keys())
+    }
+
+    /// A condition that the columns equal the expressions, treating NULLs as equal.
+    fn same(self, columns: &[&str], exprs: &[String]) -> String {
+        match self {
+            Dialect::MsSql => format!(
+                "EXISTS (SELECT {} INTERSECT SELECT {})",
+                columns.join(", "),
+                exprs.join(", ")
+            ),
+            Dialect::Sqlite => columns
+                .iter()
+                .zip(exprs)
+                .map(|(c, e)| format!("{} IS {}", c, e))
+                .collect::<Vec<_>>()
+                .join(" AND "),
+        }
+    }
+
+    /// A condition that any column differs from its expression, treating NULLs as equal.
+    fn differs(self, columns: &[&str], exprs: &[String]) -> String {
+        match self {
+            Dialect::MsSql => format!(
+                "EXISTS (SELECT {} EXCEPT SELECT {})",
+                columns.join(", "),
+                exprs.join(", ")
+            ),
+            Dialect::Sqlite => {
+                let differences = columns
+                    .iter()
+                    .zip(exprs)
+                    .map(|(c, e)| format!("{} IS NOT {}", c, e))
+                    .collect::<Vec<_>>();
+                format!("({})", differences.join(" OR "))
+            }
+        }
+    }
+}
+
+/// A column of a dimension table. A lookup column holds the Id of the row of
+/// another table whose key column equals the value given.
+#[derive(Debug)]
+pub struct Column {
+    name: &'static str,
+    lookup: Option<(&'static str, &'static str)>,
+}
+
+const fn column(name: &'static str) -> Column {
+    Column { name, lookup: None }
+}
+
+const fn lookup(name: &'static str, table: &'static str, key: &'static str) -> Column {
+    Column {
+        name,
+        lookup: Some((table, key)),
+    }
+}
+
+/// Which rows of a dimension are removed at the end of a load.
+#[derive(Debug)]
+pub enum Prune {
+    /// Rows not referenced from any of these (table, column) pairs.
+    Unreferenced(&'static [(&'static str, &'static str)]),
+    /// Rows that were not loaded, as the input lists every row.
+    NotLoaded,
+}
+
+/// A table holding one of the dimensions of the turbines. Rows are matched on
+/// the key columns and are given as values for the key columns followed by
+/// the other columns.
+#[derive(Debug)]
+pub struct Dimension {
+    pub table: &'static str,
+    key: &'static [Column],
+    values: &'static [Column],
+    /// Rows loaded before the key was kept have NULL key columns, and are
+    /// matched on these columns instead.
+    legacy_key: &'static [&'static str],
+    pub prune: Prune,
+}
+
+pub const STATE: Dimension = Dimension {
+    table: "State",
+    key: &[column("Id")],
+    values: &[
+        column("Name"),
+        column("Capital"),
+        column("Population"),
+        column("AreaSquareKm"),
+        column("StateType"),
+    ],
+    legacy_key: &[],
+    prune: Prune::NotLoaded,
+};
+
+pub const COUNTY: Dimension = Dimension {
+    table: "County",
+    key: &[column("Fips")],
+    values: &[column("StateId"), column("Name")],
+    legacy_key: &["StateId", "Name"],
+    prune: Prune::Unreferenced(&[("Turbine", "CountyId")]),
+};
+
+pub const MANUFACTURER: Dimension = Dimension {
+    table: "Manufacturer",
+    key: &[column("Name")],
+    values: &[],
+    legacy_key: &[],
+    prune: Prune::Unreferenced(&[
+        ("Model", "ManufacturerId"),
+        ("ManufacturerAlias", "ManufacturerId"),
+        ("ModelAlias", "ManufacturerId"),
+    ]),
+};
+
+pub const MANUFACTURER_ALIAS: Dimension = Dimension {
+    table: "ManufacturerAlias",
+    key: &[column("Alias")],
+    values: &[lookup("ManufacturerId", "Manufacturer", "Name")],
+    legacy_key: &[],
+    prune: Prune::NotLoaded,
+};
+
+pub const MODEL_ALIAS: Dimension = Dimension {
+    table: "ModelAlias",
+    key: &[
+        lookup("ManufacturerId", "Manufacturer", "Name"),
+        column("Alias"),
+    ],
+    values: &[column("ModelName")],
+    legacy_key: &[],
+    prune: Prune::NotLoaded,
+};
+
+pub const MODEL: Dimension = Dimension {
+    table: "Model",
+    key: &[
+        lookup("ManufacturerId", "Manufacturer", "Name"),
+        column("Name"),
+    ],
+    values: &[
+        column("CapacityKW"),
+        column("HubHeight"),
+        column("RotorDiameter"),
+        column("RotorSweptArea"),
+        column("TotalHeightToTip"),
+    ],
+    legacy_key: &[],
+    prune: Prune::Unreferenced(&[("Turbine", "ModelId")]),
+};
+
+pub const IMAGE_SOURCE: Dimension = Dimension {
+    table: "ImageSource",
+    key: &[column("Name")],
+    values: &[],
+    legacy_key: &[],
+    prune: Prune::Unreferenced(&[("Turbine", "ImageSourceId")]),
+};
+
+pub const PROJECT: Dimension = Dimension {
+    table: "Project",
+    key: &[column("Name"), column("StateId"), column("Year")],
+    values: &[column("NumTurbines"), column("CapacityMW")],
+    legacy_key: &[],
+    prune: Prune::Unreferenced(&[("Turbine", "ProjectId")]),
+};
+
+/// Every dimension, in an order that syncs referenced rows first.
+pub const ALL: &[&Dimension] = &[
+    &STATE,
+    &COUNTY,
+    &MANUFACTURER,
+    &MANUFACTURER_ALIAS,
+    &MODEL_ALIAS,
+    &MODEL,
+    &IMAGE_SOURCE,
+    &PROJECT,
+];
+
+/// Finds a dimension by its table name.
+pub fn by_table(table: &str) -> Option<&'static Dimension> {
+    ALL.iter().copied().find(|d| d.table == table)
+}
+
+/// The dimensions pruned at the end of a turbine load, in an order that
+/// removes referencing rows before the rows they reference.
+pub const PRUNED_BY_TURBINES: &[&Dimension] =
+    &[&PROJECT, &MODEL, &COUNTY, &IMAGE_SOURCE, &MANUFACTURER];
+
+impl Dimension {
+    /// The names of the columns, the key columns first.
+    pub fn columns(&self) -> Vec<&'static str> {
+        self.key.iter().chain(self.values).map(|c| c.name).collect()
+    }
+
+    /// The number of key columns at the start of each row.
+    pub fn key_len(&self) -> usize {
+        self.key.len()
+    }
+
+    /// The expression giving each column its value from the parameters.
+    fn exprs(&self, dialect: Dialect) -> Vec<String> {
+        column_exprs(self.key.iter().chain(self.values), dialect, |idx| {
+            dialect.param(idx + 1)
+        })
+    }
+
+    fn key_match(&self, dialect: Dialect) -> String {
+        let columns = self.columns();
+        let exprs = self.exprs(dialect);
+        let key = dialect.same(&columns[..self.key.len()], &exprs[..self.key.len()]);
+
+        if self.legacy_key.is_empty() {
+            return key;
+        }
+
+        let legacy_exprs = self
+            .legacy_key
+            .iter()
+            .map(|name| exprs[columns.iter().position(|c| c == name).unwrap()].clone())
+            .collect::<Vec<_>>();
+        let no_key = columns[..self.key.len()]
+            .iter()
+            .map(|c| format!("{} IS NULL", c))
+            .collect::<Vec<_>>();
+
+        format!(
+            "({} OR ({} AND {}))",
+            key,
+            no_key.join(" AND "),
+            dialect.same(self.legacy_key, &legacy_exprs)
+        )
+    }
+
+    /// A condition that the row matches the key but some column differs, or
+    /// None if no column can differ from the ones it was matched on.
+    fn changed(&self, dialect: Dialect) -> Option<String> {
+        if self.values.is_empty() && self.legacy_key.is_empty() {
+            return None;
+        }
+
+        let columns = self.columns();
+        let exprs = self.exprs(dialect);
+        Some(format!(
+            "{} AND {}",
+            self.key_match(dialect),
+            dialect.differs(&columns, &exprs)
+        ))
+    }
+
+    /// Updates the matching row if any column differs, or None if no column
+    /// can differ from the ones it was matched on.
+    pub(crate) fn update_sql(&self, dialect: Dialect) -> Option<String> {
+        let changed = self.changed(dialect)?;
+        let columns = self.columns();
+        let exprs = self.exprs(dialect);
+        let hint = if dialect == Dialect::MsSql {
+            " WITH (UPDLOCK, SERIALIZABLE)"
+        } else {
+            ""
+        };
+        let assignments = columns
+            .iter()
+            .zip(&exprs)
+            .map(|(c, e)| format!("{} = {}", c, e))
+            .collect::<Vec<_>>();
+
+        Some(format!(
+            "UPDATE {}{} SET {} WHERE {}",
+            dialect.table(self.table),
+            hint,
+            assignments.join(", "),
+            changed
+        ))
+    }
+
+    /// Counts the rows that `update_sql` would update.
+    pub(crate) fn differs_sql(&self, dialect: Dialect) -> Option<String> {
+        Some(format!(
+            "SELECT COUNT(*) FROM {} WHERE {}",
+            dialect.table(self.table),
+            self.changed(dialect)?
+        ))
+    }
+
+    /// Selects every row with each column as text, giving lookup columns the
+    /// key of the row they refer to rather than its Id.
+    pub(crate) fn select_text_sql(&self, dialect: Dialect) -> String {
+        let columns = self
+            .key
+            .iter()
+            .chain(self.values)
+            .map(|c| match c.lookup {
+                Some((table, key)) => format!(
+                    "(SELECT {} FROM {} L WHERE L.Id = D.{})",
+                    dialect.text(&format!("L.{}", key)),
+                    dialect.table(table),
+                    c.name
+                ),
+                None => dialect.text(&format!("D.{}", c.name)),
+            })
+            .collect::<Vec<_>>();
+
+        format!(
+            "SELECT {} FROM {} D",
+            columns.join(", "),
+            dialect.table(self.table)
+        )
+    }
+
+    /// Counts the rows matching the key.
+    pub(crate) fn exists_sql(&self, dialect: Dialect) -> String {
+        format!(
+            "SELECT COUNT(*) FROM {} WHERE {}",
+            dialect.table(self.table),
+            self.key_match(dialect)
+        )
+    }
+
+    pub(crate) fn insert_sql(&self, dialect: Dialect) -> String {
+        format!(
+            "INSERT INTO {} ({}) SELECT {}",
+            dialect.table(self.table),
+            self.columns().join(", "),
+            self.exprs(dialect).join(", ")
+        )
+    }
+
+    /// Creates a temporary table for the keys of the loaded rows, when pruning
+    /// the rows not loaded, as there may be more than one statement can take
+    /// as parameters. It has a column Key1, Key2 etc. for each key column,
+    /// holding the values given for the key rather than looked up.
+    pub(crate) fn create_loaded_keys_sql(&self, dialect: Dialect) -> String {
+        let table = dialect.loaded_keys();
+        let columns = (1..=self.key.len())
+            .map(|idx| match dialect {
+                Dialect::MsSql => {
+                    format!("Key{} NVARCHAR(4000) COLLATE DATABASE_DEFAULT NULL", idx)
+                }
+                Dialect::Sqlite => format!("Key{}", idx),
+            })
+            .collect::<Vec<_>>();
+
+        match dialect {
+            Dialect::MsSql => format!(
+                "IF OBJECT_ID('tempdb..{0}') IS NOT NULL DROP TABLE {0}; CREATE TABLE {0} ({1});",
+                table,
+                columns.join(", ")
+            ),
+            Dialect::Sqlite => format!(
+                "DROP TABLE IF EXISTS {0}; CREATE TEMP TABLE LoadedKeys ({1});",
+                table,
+                columns.join(", ")
+            ),
+        }
+    }
+
+    /// Inserts `rows` rows into the loaded keys table, whose key columns are
+    /// the parameters, row by row.
+    pub(crate) fn insert_loaded_keys_sql(&self, dialect: Dialect, rows: usize) -> String {
+        let key_len = self.key.len();
+        let columns = (1..=key_len)
+            .map(|idx| format!("Key{}", idx))
+            .collect::<Vec<_>>();
+        let values = (0..rows)
+            .map(|row| {
+                let params = (1..=key_len)
+                    .map(|idx| dialect.param(row * key_len + idx))
+                    .collect::<Vec<_>>();
+                format!("({})", params.join(", "))
+            })
+            .collect::<Vec<_>>();
+
+        format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            dialect.loaded_keys(),
+            columns.join(", "),
+            values.join(", ")
+        )
+    }
+
+    /// Deletes the rows to be pruned. The rows not loaded are those whose key
+    /// is not in the loaded keys table.
+    pub(crate) fn prune_sql(&self, dialect: Dialect) -> String {
+        let table = dialect.table(self.table);
+        let delete = match dialect {
+            Dialect::MsSql => format!("DELETE D FROM {} D", table),
+            Dialect::Sqlite => format!("DELETE FROM {} AS D", table),
+        };
+
+        match self.prune {
+            Prune::Unreferenced(references) => {
+                let unreferenced = references
+                    .iter()
+                    .map(|(r, c)| {
+                        format!(
+                            "NOT EXISTS (SELECT 1 FROM {} R WHERE R.{} = D.Id)",
+                            dialect.table(r),
+                            c
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                format!("{} WHERE {}", delete, unreferenced.join(" AND "))
+            }
+            Prune::NotLoaded => {
+                let columns = self
+                    .key
+                    .iter()
+                    .map(|c| format!("D.{}", c.name))
+                    .collect::<Vec<_>>();
+                let columns = columns.iter().map(String::as_str).collect::<Vec<_>>();
+                let exprs =
+                    column_exprs(self.key.iter(), dialect, |idx| format!("L.Key{}", idx + 1));
+                format!(
+                    "{} WHERE NOT EXISTS (SELECT 1 FROM {} L WHERE {})",
+                    delete,
+                    dialect.loaded_keys(),
+                    dialect.same(&columns, &exprs)
+                )
+            }
+        }
+    }
+}
+
+/// The expressions giving the columns their values, the value of the column
+/// at each index being given by `value`.
+fn column_exprs<'a>(
+    columns: impl Iterator<Item = &'a Column>,
+    dialect: Dialect,
+    value: impl Fn(usize) -> String,
+) -> Vec<String> {
+    columns
+        .enumerate()
+        .map(|(idx, c)| {
+            let param = value(idx);
+            match c.lookup {
+                Some((table, key)) => format!(
+                    "(SELECT Id FROM {} WHERE {} = {})",
+                    dialect.table(table),
+                    key,
+                    param
+                ),
+                None => param,
+            }
+        })
+        .collect()
+}
+
+/// The changes made to one dimension table.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct SyncCounts {
+    pub inserted: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+impl SyncCounts {
+    pub fn add(&mut self, other: SyncCounts) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.removed += other.removed;
+    }
+}
+
+impl fmt::Display for SyncCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} inserted, {} updated, {} removed",
+            self.inserted, self.updated, self.removed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_keys_treating_nulls_as_equal() {
+        assert_eq!(
+            STATE.exists_sql(Dialect::Sqlite),
+            "SELECT COUNT(*) FROM State WHERE Id IS ?1"
+        );
+        assert_eq!(
+            STATE.exists_sql(Dialect::MsSql),
+            "SELECT COUNT(*) FROM dbo.State WHERE EXISTS (SELECT Id INTERSECT SELECT @P1)"
+        );
+        assert_eq!(
+            PROJECT.exists_sql(Dialect::Sqlite),
+            "SELECT COUNT(*) FROM Project WHERE Name IS ?1 AND StateId IS ?2 AND Year IS ?3"
+        );
+    }
+
+    #[test]
+    fn matches_rows_without_a_key_on_the_legacy_key() {
+        assert_eq!(
+            COUNTY.exists_sql(Dialect::Sqlite),
+            "SELECT COUNT(*) FROM County WHERE \
+            (Fips IS ?1 OR (Fips IS NULL AND StateId IS ?2 AND Name IS ?3))"
+        );
+        assert_eq!(
+            COUNTY.exists_sql(Dialect::MsSql),
+            "SELECT COUNT(*) FROM dbo.County WHERE \
+            (EXISTS (SELECT Fips INTERSECT SELECT @P1) OR \
+            (Fips IS NULL AND EXISTS (SELECT StateId, Name INTERSECT SELECT @P2, @P3)))"
+        );
+    }
+
+    #[test]
+    fn looks_up_referenced_rows_by_key() {
+        assert_eq!(
+            MODEL.insert_sql(Dialect::Sqlite),
+            "INSERT INTO Model (ManufacturerId, Name, CapacityKW, HubHeight, RotorDiameter, \
+            RotorSweptArea, TotalHeightToTip) \
+            SELECT (SELECT Id FROM Manufacturer WHERE Name = ?1), ?2, ?3, ?4, ?5, ?6, ?7"
+        );
+        assert_eq!(
+            MODEL.select_text_sql(Dialect::MsSql),
+            "SELECT (SELECT CAST(L.Name AS NVARCHAR(4000)) FROM dbo.Manufacturer L \
+            WHERE L.Id = D.ManufacturerId), CAST(D.Name AS NVARCHAR(4000)), \
+            CAST(D.CapacityKW AS NVARCHAR(4000)), CAST(D.HubHeight AS NVARCHAR(4000)), \
+            CAST(D.RotorDiameter AS NVARCHAR(4000)), CAST(D.RotorSweptArea AS NVARCHAR(4000)), \
+            CAST(D.TotalHeightToTip AS NVARCHAR(4000)) FROM dbo.Model D"
+        );
+    }
+
+    #[test]
+    fn updates_only_rows_that_differ() {
+        assert_eq!(
+            PROJECT.update_sql(Dialect::Sqlite).unwrap(),
+            "UPDATE Project SET Name = ?1, StateId = ?2, Year = ?3, NumTurbines = ?4, \
+            CapacityMW = ?5 WHERE Name IS ?1 AND StateId IS ?2 AND Year IS ?3 AND \
+            (Name IS NOT ?1 OR StateId IS NOT ?2 OR Year IS NOT ?3 OR NumTurbines IS NOT ?4 \
+            OR CapacityMW IS NOT ?5)"
+        );
+        assert_eq!(
+            PROJECT.update_sql(Dialect::MsSql).unwrap(),
+            "UPDATE dbo.Project WITH (UPDLOCK, SERIALIZABLE) SET Name = @P1, StateId = @P2, \
+            Year = @P3, NumTurbines = @P4, CapacityMW = @P5 WHERE \
+            EXISTS (SELECT Name, StateId, Year INTERSECT SELECT @P1, @P2, @P3) AND \
+            EXISTS (SELECT Name, StateId, Year, NumTurbines, CapacityMW \
+            EXCEPT SELECT @P1, @P2, @P3, @P4, @P5)"
+        );
+        assert_eq!(
+            PROJECT.differs_sql(Dialect::Sqlite).unwrap(),
+            format!(
+                "SELECT COUNT(*) FROM Project WHERE {}",
+                PROJECT.changed(Dialect::Sqlite).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn rows_matched_on_every_column_are_never_updated() {
+        assert_eq!(MANUFACTURER.update_sql(Dialect::Sqlite), None);
+        assert_eq!(MANUFACTURER.differs_sql(Dialect::MsSql), None);
+    }
+
+    #[test]
+    fn prunes_unreferenced_rows() {
+        assert_eq!(
+            PROJECT.prune_sql(Dialect::Sqlite),
+            "DELETE FROM Project AS D WHERE \
+            NOT EXISTS (SELECT 1 FROM Turbine R WHERE R.ProjectId = D.Id)"
+        );
+        assert_eq!(
+            PROJECT.prune_sql(Dialect::MsSql),
+            "DELETE D FROM dbo.Project D WHERE \
+            NOT EXISTS (SELECT 1 FROM dbo.Turbine R WHERE R.ProjectId = D.Id)"
+        );
+    }
+
+    #[test]
+    fn finds_dimensions_by_table() {
+        assert_eq!(by_table("Model").map(|d| d.table), Some("Model"));
+        assert!(by_table("Turbine").is_none());
+    }
+
+    #[test]
+    fn stages_the_loaded_keys() {
+        assert_eq!(
+            MODEL_ALIAS.create_loaded_keys_sql(Dialect::Sqlite),
+            "DROP TABLE IF EXISTS temp.LoadedKeys; CREATE TEMP TABLE LoadedKeys (Key1, Key2);"
+        );
+        assert_eq!(
+            MANUFACTURER_ALIAS.create_loaded_keys_sql(Dialect::MsSql),
+            "IF OBJECT_ID('tempdb..#LoadedKeys') IS NOT NULL DROP TABLE #LoadedKeys; \
+            CREATE TABLE #LoadedKeys (Key1 NVARCHAR(4000) COLLATE DATABASE_DEFAULT NULL);"
+        );
+        assert_eq!(
+            MODEL_ALIAS.insert_loaded_keys_sql(Dialect::MsSql, 2),
+            "INSERT INTO #LoadedKeys (Key1, Key2) VALUES (@P1, @P2), (@P3, @P4)"
+        );
+    }
+
+    #[test]
+    fn prunes_keys_not_loaded() {
+        assert_eq!(
+            MANUFACTURER_ALIAS.prune_sql(Dialect::Sqlite),
+            "DELETE FROM ManufacturerAlias AS D WHERE \
+            NOT EXISTS (SELECT 1 FROM temp.LoadedKeys L WHERE D.Alias IS L.Key1)"
+        );
+        assert_eq!(
+            MODEL_ALIAS.prune_sql(Dialect::Sqlite),
+            "DELETE FROM ModelAlias AS D WHERE NOT EXISTS (SELECT 1 FROM temp.LoadedKeys L WHERE \
+            D.ManufacturerId IS (SELECT Id FROM Manufacturer WHERE Name = L.Key1) AND D.Alias IS L.Key2)"
+        );
+        assert_eq!(
+            MODEL_ALIAS.prune_sql(Dialect::MsSql),
+            "DELETE D FROM dbo.ModelAlias D WHERE NOT EXISTS (SELECT 1 FROM #LoadedKeys L WHERE \
+            EXISTS (SELECT D.ManufacturerId, D.Alias INTERSECT \
+            SELECT (SELECT Id FROM dbo.Manufacturer WHERE Name = L.Key1), L.Key2))"
+        );
+    }
+}
@@ -1,29 +1,64 @@
+pub mod dimension;
+pub mod load;
+pub mod migrations;
 pub mod models;
+mod memory;
+mod mssql;
+pub mod plan;
+pub mod pool;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(all(test, feature = "sqlite"))]
+mod testing;
 
-use once_cell::sync::Lazy;
-use std::convert::{TryFrom, TryInto};
-use tiberius::Client;
-use tokio::net::TcpStream;
-use tokio_util::compat::{Compat, TokioAsyncWriteCompatExt};
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
 
-use models::*;
+pub use memory::InMemoryRepository;
+pub use mssql::MsSqlRepository;
+pub use pool::Pool;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteRepository;
 
-/// Represents a connection to the MS SQL US Wind Power Stats database.
-pub struct Repository {
-    client: Client<Compat<TcpStream>>,
-}
+use dimension::{Dimension, RowState, SyncCounts, Value};
+use load::{HistoryEntry, LoadCheckpoint, NewLoadRun, NewRelease, ReleaseCounts, TurbineRow};
+use migrations::Migration;
+use models::*;
 
 pub mod error {
+    #[derive(Debug)]
     pub enum Error {
         LowLevel(String),
         NotFound,
         UnknownStateType(String),
         UnknownConfidenceLevel(String),
+        UnknownChangeType(String),
+        /// A failure that may not recur, such as a dropped connection or a
+        /// locked database, so the operation can be tried again.
+        Transient(String),
+    }
+
+    impl std::fmt::Display for Error {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Error::LowLevel(msg) => write!(f, "{}", msg),
+                Error::NotFound => write!(f, "Not found"),
+                Error::UnknownStateType(msg) => write!(f, "Unknown state type {}", msg),
+                Error::UnknownConfidenceLevel(msg) => write!(f, "Unknown confidence level {}", msg),
+                Error::UnknownChangeType(msg) => write!(f, "Unknown change type {}", msg),
+                Error::Transient(msg) => write!(f, "{}", msg),
+            }
+        }
     }
 
+    impl std::error::Error for Error {}
+
     impl From<tiberius::error::Error> for Error {
         fn from(err: tiberius::error::Error) -> Self {
-            Error::LowLevel(format!("{}", err))
+            match err {
+                tiberius::error::Error::Io { .. } => Error::Transient(format!("{}", err)),
+                _ => Error::LowLevel(format!("{}", err)),
+            }
         }
     }
 
@@ -33,167 +68,229 @@ pub mod error {
             err.into()
         }
     }
-}
 
-static CONN_STR: Lazy<String> = Lazy::new(|| {
-    std::env::var("MSSQL_CONNECTION_STRING").unwrap_or_else(|_| {
-        "server=tcp:localhost,1433;User Id=SA;Password=EawRsi2PCfurVZi7dym9;Initial Catalog=UsWindPowerStats;TrustServerCertificate=true".to_owned()
-    })
-});
+    #[cfg(feature = "sqlite")]
+    impl From<rusqlite::Error> for Error {
+        fn from(err: rusqlite::Error) -> Self {
+            use rusqlite::ErrorCode::{DatabaseBusy, DatabaseLocked};
 
-impl Repository {
-    /// Opens a new connection.
-    pub async fn open(connection_string: Option<&str>) -> Result<Self, crate::error::Error> {
-        let connection_string = connection_string.unwrap_or_else(|| CONN_STR.as_ref());
+            match &err {
+                rusqlite::Error::SqliteFailure(e, _)
+                    if e.code == DatabaseBusy || e.code == DatabaseLocked =>
+                {
+                    Error::Transient(format!("{}", err))
+                }
+                _ => Error::LowLevel(format!("{}", err)),
+            }
+        }
+    }
+}
 
-        let config = tiberius::Config::from_ado_string(&connection_string)?;
-        let tcp = TcpStream::connect(config.get_addr()).await?;
-        tcp.set_nodelay(true)?;
-        let client = Client::connect(config, tcp.compat_write()).await?;
+/// Opens a repository, choosing the backend from the connection string.
+/// Strings of the form `sqlite://path/to/file.db` open a SQLite database,
+/// anything else is treated as an ADO-style MS SQL connection string.
+pub async fn open(connection_string: &str) -> Result<Box<dyn Repository>, crate::error::Error> {
+    match sqlite_path(connection_string) {
+        Some(path) => open_sqlite(path),
+        None => Ok(Box::new(MsSqlRepository::open(connection_string).await?)),
+    }
+}
 
-        Ok(Repository { client })
+/// Opens `size` connections to the same database.
+pub async fn open_pool(connection_string: &str, size: usize) -> Result<Pool, crate::error::Error> {
+    let mut repos = Vec::with_capacity(size);
+    for _ in 0..size.max(1) {
+        repos.push(open(connection_string).await?);
     }
 
+    Ok(Pool::new(repos))
+}
+
+/// If the connection string refers to a SQLite database, returns the path
+/// of the database file.
+pub fn sqlite_path(connection_string: &str) -> Option<&str> {
+    connection_string
+        .strip_prefix("sqlite://")
+        .or_else(|| connection_string.strip_prefix("sqlite:"))
+}
+
+#[cfg(feature = "sqlite")]
+fn open_sqlite(path: &str) -> Result<Box<dyn Repository>, crate::error::Error> {
+    Ok(Box::new(SqliteRepository::open(path)?))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite(path: &str) -> Result<Box<dyn Repository>, crate::error::Error> {
+    Err(crate::error::Error::LowLevel(format!(
+        "Cannot open {}: this build does not include the 'sqlite' feature",
+        path
+    )))
+}
+
+/// The operations supported by a US Wind Power Stats data store.
+/// `MsSqlRepository` talks to the real database, `InMemoryRepository`
+/// can be used when there is no database available.
+///
+/// The REST API only reads. The dataloader also writes, running each load
+/// between `begin` and `commit` so that readers see either the previous data
+/// set or the new one, never a mixture. A checkpointed load is the exception:
+/// it commits each batch, with a `LoadCheckpoint` to resume from.
+#[async_trait]
+pub trait Repository: Send {
     /// Gets all ImageSource rows.
-    pub async fn get_all_image_sources(&mut self) -> Result<Vec<ImageSource>, crate::error::Error> {
-        let stream = self
-            .client
-            .simple_query("SELECT Id, Name FROM dbo.ImageSource")
-            .await?;
-
-        stream
-            .into_first_result()
-            .await?
-            .iter()
-            .map(|row| ImageSource::try_from(row))
-            .collect()
-    }
+    async fn get_all_image_sources(&mut self) -> Result<Vec<ImageSource>, crate::error::Error>;
 
-    /// Gets the ImageSource with the specific Id. Returns None if no match found.
-    pub async fn get_image_source(&mut self, id: u8) -> Result<ImageSource, crate::error::Error> {
-        let stream = self
-            .client
-            .query(
-                "SELECT Id, Name FROM dbo.ImageSource WHERE Id = @P1",
-                &[&id],
-            )
-            .await?;
-        let rows = stream.into_first_result().await?;
-        match rows.iter().next() {
-            Some(row) => Ok(row.try_into()?),
-            None => Err(error::Error::NotFound),
-        }
-    }
+    /// Gets the ImageSource with the specific Id. Returns NotFound if no match found.
+    async fn get_image_source(&mut self, id: u8) -> Result<ImageSource, crate::error::Error>;
 
     /// Update a row in the ImageSource table. Returns the number of rows affected (0 or 1).
-    pub async fn update_image_source(
-        &mut self,
-        id: u8,
-        name: &str,
-    ) -> Result<u64, crate::error::Error> {
-        let stmt = "UPDATE dbo.ImageSource SET Name = @P1 WHERE Id = @P2;";
-        let result = self.client.execute(stmt, &[&name, &id]).await?;
-        Ok(result.total())
-    }
+    async fn update_image_source(&mut self, id: u8, name: &str)
+        -> Result<u64, crate::error::Error>;
 
     /// Gets all State rows.
-    pub async fn get_all_states(&mut self) -> Result<Vec<State>, crate::error::Error> {
-        let stream = self
-            .client
-            .simple_query(
-                "SELECT Id, Name, Capital, Population, AreaSquareKm, StateType FROM dbo.State",
-            )
-            .await?;
-
-        stream
-            .into_first_result()
-            .await?
-            .iter()
-            .map(|row| State::try_from(row))
-            .collect()
-    }
+    async fn get_all_states(&mut self) -> Result<Vec<State>, crate::error::Error>;
 
     /// Gets all County rows.
-    pub async fn get_all_counties(&mut self) -> Result<Vec<County>, crate::error::Error> {
-        let stream = self
-            .client
-            .simple_query("SELECT Id, StateId, Name FROM dbo.County")
-            .await?;
-
-        stream
-            .into_first_result()
-            .await?
-            .iter()
-            .map(|row| County::try_from(row))
-            .collect()
-    }
+    async fn get_all_counties(&mut self) -> Result<Vec<County>, crate::error::Error>;
+
+    /// Gets the County with the specific 5-digit FIPS code. Returns NotFound if no match found.
+    async fn get_county_by_fips(&mut self, fips: i32) -> Result<County, crate::error::Error>;
 
     /// Gets all Project rows.
-    pub async fn get_all_projects(&mut self) -> Result<Vec<Project>, crate::error::Error> {
-        let stream = self
-            .client
-            .simple_query("SELECT Id, Name, NumTurbines, CapacityMW FROM dbo.Project")
-            .await?;
-
-        stream
-            .into_first_result()
-            .await?
-            .iter()
-            .map(|row| Project::try_from(row))
-            .collect()
-    }
+    async fn get_all_projects(&mut self) -> Result<Vec<Project>, crate::error::Error>;
 
     /// Gets all Manufacturer rows.
-    pub async fn get_all_manufacturers(
-        &mut self,
-    ) -> Result<Vec<Manufacturer>, crate::error::Error> {
-        let stream = self
-            .client
-            .simple_query("SELECT Id, Name FROM dbo.Manufacturer")
-            .await?;
-
-        stream
-            .into_first_result()
-            .await?
-            .iter()
-            .map(|row| Manufacturer::try_from(row))
-            .collect()
-    }
+    async fn get_all_manufacturers(&mut self) -> Result<Vec<Manufacturer>, crate::error::Error>;
+
+    /// Gets all ManufacturerAlias rows.
+    async fn get_all_manufacturer_aliases(&mut self)
+        -> Result<Vec<ManufacturerAlias>, crate::error::Error>;
 
     /// Gets all Model rows.
-    pub async fn get_all_models(&mut self) -> Result<Vec<Model>, crate::error::Error> {
-        let stream = self
-            .client
-            .simple_query(
-                "SELECT Id, ManufacturerId, Name, CapacityKW,
-                HubHeight, RotorDiameter, RotorSweptArea, TotalHeightToTip FROM dbo.Model",
-            )
-            .await?;
-
-        stream
-            .into_first_result()
-            .await?
-            .iter()
-            .map(|row| Model::try_from(row))
-            .collect()
-    }
+    async fn get_all_models(&mut self) -> Result<Vec<Model>, crate::error::Error>;
+
+    /// Gets all ModelAlias rows.
+    async fn get_all_model_aliases(&mut self) -> Result<Vec<ModelAlias>, crate::error::Error>;
 
     /// Gets all Turbine rows.
-    pub async fn get_all_turbines(&mut self) -> Result<Vec<Turbine>, crate::error::Error> {
-        let stream = self
-            .client
-            .simple_query(
-                "SELECT Id, CountyId, ProjectId, ModelId, ImageSourceId,
-                Retrofit, RetrofitYear, AttributesConfidenceLevel, LocationConfidenceLevel,
-                ImageDate, Latitude, Longitude FROM dbo.Turbine",
-            )
-            .await?;
-
-        stream
-            .into_first_result()
-            .await?
-            .iter()
-            .map(|row| Turbine::try_from(row))
-            .collect()
-    }
+    async fn get_all_turbines(&mut self) -> Result<Vec<Turbine>, crate::error::Error>;
+
+    /// Gets the turbines at the plant with the specific EIA plant id.
+    async fn get_turbines_by_eia_id(&mut self, eia_id: i32)
+        -> Result<Vec<Turbine>, crate::error::Error>;
+
+    /// Gets the turbines filed under the specific FAA obstruction evaluation
+    /// number (ASN), e.g. "2013-WTE-2956-OE".
+    async fn get_turbines_by_faa_asn(&mut self, faa_asn: &str)
+        -> Result<Vec<Turbine>, crate::error::Error>;
+
+    /// Gets all Release rows, one per load of a USWTDB file.
+    async fn get_all_releases(&mut self) -> Result<Vec<Release>, crate::error::Error>;
+
+    /// Gets all LoadRun rows, one per recorded run of the dataloader's load command.
+    async fn get_all_load_runs(&mut self) -> Result<Vec<LoadRun>, crate::error::Error>;
+
+    /// Gets the history of the turbine with the USWTDB case_id, oldest first.
+    /// A decommissioned turbine keeps its history. Returns NotFound if there
+    /// is no history for the case_id.
+    async fn get_turbine_history(&mut self, case_id: i32)
+        -> Result<Vec<TurbineChange>, crate::error::Error>;
+
+    /// All the schema migrations for this backend, in version order.
+    fn migrations(&self) -> &'static [Migration];
+
+    /// Gets the versions of the migrations that have been applied.
+    async fn get_applied_migrations(&mut self) -> Result<Vec<i32>, crate::error::Error>;
+
+    /// Applies a migration and records it in the SchemaVersion table.
+    async fn apply_migration(&mut self, migration: &Migration)
+        -> Result<(), crate::error::Error>;
+
+    /// Counts the rows in a table, giving 0 if there is no such table.
+    async fn count_rows(&mut self, table: &str) -> Result<i64, crate::error::Error>;
+
+    /// Begins the transaction a load runs in.
+    async fn begin(&mut self) -> Result<(), crate::error::Error>;
+
+    /// Commits the transaction begun by `begin`.
+    async fn commit(&mut self) -> Result<(), crate::error::Error>;
+
+    /// Rolls back the transaction begun by `begin`.
+    async fn rollback(&mut self) -> Result<(), crate::error::Error>;
+
+    /// Inserts the rows of a dimension table that are missing and updates
+    /// those that differ. The rows a lookup column refers to must already exist.
+    async fn sync_dimension(&mut self, dimension: &Dimension, rows: &[Vec<Value>])
+        -> Result<SyncCounts, crate::error::Error>;
+
+    /// Deletes the rows of a dimension table chosen by its `prune`, given the
+    /// rows loaded. Returns the number of rows deleted.
+    async fn prune_dimension(&mut self, dimension: &Dimension, loaded: &[Vec<Value>])
+        -> Result<usize, crate::error::Error>;
+
+    /// Compares rows with a dimension table as `sync_dimension` would,
+    /// without changing it.
+    async fn compare_dimension(&mut self, dimension: &Dimension, rows: &[Vec<Value>])
+        -> Result<Vec<RowState>, crate::error::Error>;
+
+    /// Gets every row of a dimension table, in the order of its columns, as
+    /// text. Lookup columns give the key of the row they refer to.
+    async fn get_dimension_rows(&mut self, dimension: &Dimension)
+        -> Result<Vec<Vec<Option<String>>>, crate::error::Error>;
+
+    /// Gets the Id of every State.
+    async fn get_state_ids(&mut self) -> Result<HashSet<String>, crate::error::Error>;
+
+    /// Gets the Name of every Manufacturer.
+    async fn get_manufacturer_names(&mut self) -> Result<HashSet<String>, crate::error::Error>;
+
+    /// Gets the (manufacturer, model) names of every Model.
+    async fn get_model_names(&mut self)
+        -> Result<HashSet<(String, String)>, crate::error::Error>;
+
+    /// Gets the USWTDB case_id of every Turbine.
+    async fn get_case_ids(&mut self) -> Result<HashSet<i32>, crate::error::Error>;
+
+    /// Gets the turbines with the specific case_ids, keyed by case_id.
+    /// Case_ids with no turbine are left out.
+    async fn get_turbine_rows(&mut self, case_ids: &[i32])
+        -> Result<HashMap<i32, TurbineRow>, crate::error::Error>;
+
+    /// Inserts the turbines whose case_id is new and updates the rest. The
+    /// county, project, model and image source of each must already exist.
+    async fn upsert_turbines(&mut self, turbines: &[TurbineRow])
+        -> Result<(), crate::error::Error>;
+
+    /// Deletes the turbines with the specific case_ids.
+    async fn delete_turbines(&mut self, case_ids: &[i32]) -> Result<(), crate::error::Error>;
+
+    /// Adds a Release row for a load, with zero counts. Returns its Id.
+    async fn create_release(&mut self, release: &NewRelease<'_>)
+        -> Result<i32, crate::error::Error>;
+
+    /// Adds rows to the TurbineHistory table.
+    async fn record_history(&mut self, release_id: i32, history: &[HistoryEntry])
+        -> Result<(), crate::error::Error>;
+
+    /// Sets the counts of a Release once its load is complete.
+    async fn finish_release(&mut self, release_id: i32, counts: &ReleaseCounts)
+        -> Result<(), crate::error::Error>;
+
+    /// Gets the checkpoint of the load of the file with the specific checksum,
+    /// if an earlier load of it stopped part way.
+    async fn get_load_checkpoint(&mut self, checksum: &str)
+        -> Result<Option<LoadCheckpoint>, crate::error::Error>;
+
+    /// Adds or replaces the checkpoint of the load of a file.
+    async fn save_load_checkpoint(&mut self, checkpoint: &LoadCheckpoint)
+        -> Result<(), crate::error::Error>;
+
+    /// Deletes the checkpoint of the load of a file, once it is complete.
+    async fn delete_load_checkpoint(&mut self, checksum: &str)
+        -> Result<(), crate::error::Error>;
+
+    /// Adds a LoadRun row. Runs are recorded outside the load's transaction,
+    /// so that failed loads are kept. Returns its Id.
+    async fn record_load_run(&mut self, run: &NewLoadRun<'_>)
+        -> Result<i32, crate::error::Error>;
 }
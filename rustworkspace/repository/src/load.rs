@@ -0,0 +1,103 @@
+use crate::models::ChangeType;
+
+/// A turbine as written by the dataloader, and as read back to find what a
+/// new file changes. Dimensions are held by name, the county by FIPS code,
+/// coordinates in millionths of a degree and heights in centimetres, the
+/// precision of the database, so that rows can be compared exactly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TurbineRow {
+    pub case_id: i32,
+    pub county_fips: i32,
+    pub project: String,
+    pub project_state: String,
+    pub project_year: Option<i32>,
+    pub manufacturer: String,
+    pub model: String,
+    pub raw_manufacturer: Option<String>,
+    pub raw_model: Option<String>,
+    pub image_source: String,
+    pub retrofit: bool,
+    pub retrofit_year: Option<i32>,
+    pub attributes_confidence_level: u8,
+    pub location_confidence_level: u8,
+    /// The date as 'YYYY-MM-DD'.
+    pub image_date: Option<String>,
+    pub latitude: i64,
+    pub longitude: i64,
+    pub faa_ors: Option<String>,
+    pub faa_asn: Option<String>,
+    pub usgs_pr_id: Option<i32>,
+    pub eia_id: Option<i32>,
+    pub hub_height: Option<i64>,
+    pub total_height_to_tip: Option<i64>,
+}
+
+/// One row for the TurbineHistory table.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub case_id: i32,
+    pub change_type: ChangeType,
+    pub attribute: Option<&'static str>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(case_id: i32, change_type: ChangeType) -> Self {
+        HistoryEntry {
+            case_id,
+            change_type,
+            attribute: None,
+            old_value: None,
+            new_value: None,
+        }
+    }
+}
+
+/// The USWTDB file a load is recorded against in the Release table.
+#[derive(Debug, Clone, Copy)]
+pub struct NewRelease<'a> {
+    pub file_name: &'a str,
+    pub version: &'a str,
+    /// The date as 'YYYY-MM-DD'.
+    pub release_date: Option<&'a str>,
+    pub checksum: &'a str,
+}
+
+/// The totals recorded on a Release row once its load is complete.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReleaseCounts {
+    pub turbines: usize,
+    pub new: usize,
+    pub changed: usize,
+    pub decommissioned: usize,
+}
+
+/// A run of the load command, as recorded in the LoadRun table.
+#[derive(Debug, Clone, Copy)]
+pub struct NewLoadRun<'a> {
+    /// The times in UTC as 'YYYY-MM-DD HH:MM:SS'.
+    pub started_at: &'a str,
+    pub finished_at: &'a str,
+    pub succeeded: bool,
+    pub file_name: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub checksum: Option<&'a str>,
+    pub rejected_rows: usize,
+    pub warnings: usize,
+    pub error_message: Option<&'a str>,
+    pub report: &'a str,
+}
+
+/// How far a checkpointed load of a USWTDB file has got, identified by the
+/// file's checksum. Saved with each batch the load commits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadCheckpoint {
+    pub checksum: String,
+    pub release_id: i32,
+    /// The number of valid rows of the file that have been loaded.
+    pub turbines: usize,
+    pub new: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+}
@@ -0,0 +1,305 @@
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+
+use crate::dimension::{Dimension, RowState, SyncCounts, Value};
+use crate::error::Error;
+use crate::load::{
+    HistoryEntry, LoadCheckpoint, NewLoadRun, NewRelease, ReleaseCounts, TurbineRow,
+};
+use crate::migrations::Migration;
+use crate::models::*;
+use crate::Repository;
+
+/// A repository that holds all its data in memory. Seed it by filling in the
+/// fields, e.g. `InMemoryRepository { states: vec![...], ..Default::default() }`.
+/// Used for testing and for running the REST API without a database; the
+/// dataloader cannot load into it.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryRepository {
+    pub image_sources: Vec<ImageSource>,
+    pub states: Vec<State>,
+    pub counties: Vec<County>,
+    pub projects: Vec<Project>,
+    pub manufacturers: Vec<Manufacturer>,
+    pub manufacturer_aliases: Vec<ManufacturerAlias>,
+    pub models: Vec<Model>,
+    pub model_aliases: Vec<ModelAlias>,
+    pub turbines: Vec<Turbine>,
+    pub releases: Vec<Release>,
+    pub load_runs: Vec<LoadRun>,
+    pub turbine_history: Vec<TurbineChange>,
+}
+
+impl InMemoryRepository {
+    /// Creates a new, empty repository.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// The result of the operations that write loaded data.
+fn not_loadable<T>() -> Result<T, Error> {
+    Err(Error::LowLevel(
+        "The in-memory repository does not support loading data".to_string(),
+    ))
+}
+
+#[async_trait]
+impl Repository for InMemoryRepository {
+    async fn get_all_image_sources(&mut self) -> Result<Vec<ImageSource>, Error> {
+        Ok(self.image_sources.clone())
+    }
+
+    async fn get_image_source(&mut self, id: u8) -> Result<ImageSource, Error> {
+        self.image_sources
+            .iter()
+            .find(|i| i.id == id)
+            .cloned()
+            .ok_or(Error::NotFound)
+    }
+
+    async fn update_image_source(&mut self, id: u8, name: &str) -> Result<u64, Error> {
+        match self.image_sources.iter_mut().find(|i| i.id == id) {
+            Some(image_source) => {
+                image_source.name = name.to_string();
+                Ok(1)
+            }
+            None => Ok(0),
+        }
+    }
+
+    async fn get_all_states(&mut self) -> Result<Vec<State>, Error> {
+        Ok(self.states.clone())
+    }
+
+    async fn get_all_counties(&mut self) -> Result<Vec<County>, Error> {
+        Ok(self.counties.clone())
+    }
+
+    async fn get_county_by_fips(&mut self, fips: i32) -> Result<County, Error> {
+        self.counties
+            .iter()
+            .find(|c| c.fips == Some(fips))
+            .cloned()
+            .ok_or(Error::NotFound)
+    }
+
+    async fn get_all_projects(&mut self) -> Result<Vec<Project>, Error> {
+        Ok(self.projects.clone())
+    }
+
+    async fn get_all_manufacturers(&mut self) -> Result<Vec<Manufacturer>, Error> {
+        Ok(self.manufacturers.clone())
+    }
+
+    async fn get_all_manufacturer_aliases(&mut self) -> Result<Vec<ManufacturerAlias>, Error> {
+        Ok(self.manufacturer_aliases.clone())
+    }
+
+    async fn get_all_models(&mut self) -> Result<Vec<Model>, Error> {
+        Ok(self.models.clone())
+    }
+
+    async fn get_all_model_aliases(&mut self) -> Result<Vec<ModelAlias>, Error> {
+        Ok(self.model_aliases.clone())
+    }
+
+    async fn get_all_turbines(&mut self) -> Result<Vec<Turbine>, Error> {
+        Ok(self.turbines.clone())
+    }
+
+    async fn get_turbines_by_eia_id(&mut self, eia_id: i32) -> Result<Vec<Turbine>, Error> {
+        Ok(self
+            .turbines
+            .iter()
+            .filter(|t| t.eia_id == Some(eia_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_turbines_by_faa_asn(&mut self, faa_asn: &str) -> Result<Vec<Turbine>, Error> {
+        Ok(self
+            .turbines
+            .iter()
+            .filter(|t| t.faa_asn.as_deref() == Some(faa_asn))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_all_releases(&mut self) -> Result<Vec<Release>, Error> {
+        Ok(self.releases.clone())
+    }
+
+    async fn get_all_load_runs(&mut self) -> Result<Vec<LoadRun>, Error> {
+        Ok(self.load_runs.clone())
+    }
+
+    async fn get_turbine_history(&mut self, case_id: i32) -> Result<Vec<TurbineChange>, Error> {
+        let history = self
+            .turbine_history
+            .iter()
+            .filter(|h| h.case_id == case_id)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if history.is_empty() {
+            Err(Error::NotFound)
+        } else {
+            Ok(history)
+        }
+    }
+
+    fn migrations(&self) -> &'static [Migration] {
+        &[]
+    }
+
+    async fn get_applied_migrations(&mut self) -> Result<Vec<i32>, Error> {
+        Ok(Vec::new())
+    }
+
+    async fn apply_migration(&mut self, _migration: &Migration) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn count_rows(&mut self, table: &str) -> Result<i64, Error> {
+        let rows = match table {
+            "ImageSource" => self.image_sources.len(),
+            "State" => self.states.len(),
+            "County" => self.counties.len(),
+            "Project" => self.projects.len(),
+            "Manufacturer" => self.manufacturers.len(),
+            "ManufacturerAlias" => self.manufacturer_aliases.len(),
+            "Model" => self.models.len(),
+            "ModelAlias" => self.model_aliases.len(),
+            "Turbine" => self.turbines.len(),
+            "Release" => self.releases.len(),
+            "LoadRun" => self.load_runs.len(),
+            "TurbineHistory" => self.turbine_history.len(),
+            _ => 0,
+        };
+        Ok(rows as i64)
+    }
+
+    async fn begin(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn commit(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn rollback(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn sync_dimension(
+        &mut self,
+        _dimension: &Dimension,
+        _rows: &[Vec<Value>],
+    ) -> Result<SyncCounts, Error> {
+        not_loadable()
+    }
+
+    async fn prune_dimension(
+        &mut self,
+        _dimension: &Dimension,
+        _loaded: &[Vec<Value>],
+    ) -> Result<usize, Error> {
+        not_loadable()
+    }
+
+    async fn compare_dimension(
+        &mut self,
+        _dimension: &Dimension,
+        _rows: &[Vec<Value>],
+    ) -> Result<Vec<RowState>, Error> {
+        not_loadable()
+    }
+
+    async fn get_dimension_rows(
+        &mut self,
+        _dimension: &Dimension,
+    ) -> Result<Vec<Vec<Option<String>>>, Error> {
+        not_loadable()
+    }
+
+    async fn get_state_ids(&mut self) -> Result<HashSet<String>, Error> {
+        Ok(self.states.iter().map(|s| s.id.clone()).collect())
+    }
+
+    async fn get_manufacturer_names(&mut self) -> Result<HashSet<String>, Error> {
+        Ok(self.manufacturers.iter().map(|m| m.name.clone()).collect())
+    }
+
+    async fn get_model_names(&mut self) -> Result<HashSet<(String, String)>, Error> {
+        Ok(self
+            .models
+            .iter()
+            .filter_map(|m| {
+                let manufacturer = self
+                    .manufacturers
+                    .iter()
+                    .find(|mf| mf.id == m.manufacturer_id)?;
+                Some((manufacturer.name.clone(), m.name.clone()))
+            })
+            .collect())
+    }
+
+    async fn get_case_ids(&mut self) -> Result<HashSet<i32>, Error> {
+        Ok(self.turbines.iter().map(|t| t.case_id).collect())
+    }
+
+    async fn get_turbine_rows(
+        &mut self,
+        _case_ids: &[i32],
+    ) -> Result<HashMap<i32, TurbineRow>, Error> {
+        not_loadable()
+    }
+
+    async fn upsert_turbines(&mut self, _turbines: &[TurbineRow]) -> Result<(), Error> {
+        not_loadable()
+    }
+
+    async fn delete_turbines(&mut self, _case_ids: &[i32]) -> Result<(), Error> {
+        not_loadable()
+    }
+
+    async fn create_release(&mut self, _release: &NewRelease<'_>) -> Result<i32, Error> {
+        not_loadable()
+    }
+
+    async fn record_history(
+        &mut self,
+        _release_id: i32,
+        _history: &[HistoryEntry],
+    ) -> Result<(), Error> {
+        not_loadable()
+    }
+
+    async fn finish_release(
+        &mut self,
+        _release_id: i32,
+        _counts: &ReleaseCounts,
+    ) -> Result<(), Error> {
+        not_loadable()
+    }
+
+    async fn record_load_run(&mut self, _run: &NewLoadRun<'_>) -> Result<i32, Error> {
+        not_loadable()
+    }
+
+    async fn get_load_checkpoint(
+        &mut self,
+        _checksum: &str,
+    ) -> Result<Option<LoadCheckpoint>, Error> {
+        Ok(None)
+    }
+
+    async fn save_load_checkpoint(&mut self, _checkpoint: &LoadCheckpoint) -> Result<(), Error> {
+        not_loadable()
+    }
+
+    async fn delete_load_checkpoint(&mut self, _checksum: &str) -> Result<(), Error> {
+        not_loadable()
+    }
+}
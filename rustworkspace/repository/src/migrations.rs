@@ -0,0 +1,367 @@
+use crate::error::Error;
+use crate::Repository;
+
+/// A single versioned change to the database schema. The SQL is embedded in
+/// the binary from the `migrations` folder of this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+    /// The table whose existing rows the migration deletes, if it deletes
+    /// any. Such a migration is only applied to a table with rows in it when
+    /// data loss is allowed.
+    pub deletes_from: Option<&'static str>,
+}
+
+impl Migration {
+    /// Splits the script into batches on lines consisting of just `GO`.
+    /// MS SQL requires statements such as `CREATE PROCEDURE` to be the only
+    /// statement in their batch.
+    pub fn batches(&self) -> Vec<String> {
+        let mut batches = Vec::new();
+        let mut current = String::new();
+
+        for line in self.sql.lines() {
+            if line.trim().eq_ignore_ascii_case("GO") {
+                batches.push(std::mem::take(&mut current));
+            } else {
+                current.push_str(line);
+                current.push('\n');
+            }
+        }
+        batches.push(current);
+
+        batches.retain(|b| !b.trim().is_empty());
+        batches
+    }
+}
+
+/// The migrations for MS SQL, in the order they must be applied.
+pub const MSSQL_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        sql: include_str!("../migrations/mssql/0001_initial_schema.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 2,
+        name: "turbine_case_id",
+        sql: include_str!("../migrations/mssql/0002_turbine_case_id.sql"),
+        deletes_from: Some("Turbine"),
+    },
+    Migration {
+        version: 3,
+        name: "release_history",
+        sql: include_str!("../migrations/mssql/0003_release_history.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 4,
+        name: "missing_values",
+        sql: include_str!("../migrations/mssql/0004_missing_values.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 5,
+        name: "usgs_identifiers",
+        sql: include_str!("../migrations/mssql/0005_usgs_identifiers.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 6,
+        name: "county_fips",
+        sql: include_str!("../migrations/mssql/0006_county_fips.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 7,
+        name: "project_key",
+        sql: include_str!("../migrations/mssql/0007_project_key.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 8,
+        name: "turbine_heights",
+        sql: include_str!("../migrations/mssql/0008_turbine_heights.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 9,
+        name: "manufacturer_aliases",
+        sql: include_str!("../migrations/mssql/0009_manufacturer_aliases.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 10,
+        name: "load_runs",
+        sql: include_str!("../migrations/mssql/0010_load_runs.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 11,
+        name: "load_checkpoints",
+        sql: include_str!("../migrations/mssql/0011_load_checkpoints.sql"),
+        deletes_from: None,
+    },
+];
+
+/// The migrations for SQLite, in the order they must be applied.
+pub const SQLITE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        sql: include_str!("../migrations/sqlite/0001_initial_schema.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 2,
+        name: "turbine_case_id",
+        sql: include_str!("../migrations/sqlite/0002_turbine_case_id.sql"),
+        deletes_from: Some("Turbine"),
+    },
+    Migration {
+        version: 3,
+        name: "release_history",
+        sql: include_str!("../migrations/sqlite/0003_release_history.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 4,
+        name: "missing_values",
+        sql: include_str!("../migrations/sqlite/0004_missing_values.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 5,
+        name: "usgs_identifiers",
+        sql: include_str!("../migrations/sqlite/0005_usgs_identifiers.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 6,
+        name: "county_fips",
+        sql: include_str!("../migrations/sqlite/0006_county_fips.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 7,
+        name: "project_key",
+        sql: include_str!("../migrations/sqlite/0007_project_key.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 8,
+        name: "turbine_heights",
+        sql: include_str!("../migrations/sqlite/0008_turbine_heights.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 9,
+        name: "manufacturer_aliases",
+        sql: include_str!("../migrations/sqlite/0009_manufacturer_aliases.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 10,
+        name: "load_runs",
+        sql: include_str!("../migrations/sqlite/0010_load_runs.sql"),
+        deletes_from: None,
+    },
+    Migration {
+        version: 11,
+        name: "load_checkpoints",
+        sql: include_str!("../migrations/sqlite/0011_load_checkpoints.sql"),
+        deletes_from: None,
+    },
+];
+
+/// Returns the migrations that are not in `applied`, in version order.
+pub fn pending(all: &'static [Migration], applied: &[i32]) -> Vec<&'static Migration> {
+    all.iter()
+        .filter(|m| !applied.contains(&m.version))
+        .collect()
+}
+
+/// Brings the schema up to date. Returns the migrations that were pending;
+/// when `dry_run` is true they are returned but not applied. Unless
+/// `allow_data_loss` is true, fails without applying anything if a pending
+/// migration would delete existing rows.
+pub async fn migrate(
+    repo: &mut dyn Repository,
+    dry_run: bool,
+    allow_data_loss: bool,
+) -> Result<Vec<&'static Migration>, Error> {
+    let applied = repo.get_applied_migrations().await?;
+    let pending = pending(repo.migrations(), &applied);
+
+    if !dry_run {
+        if !allow_data_loss {
+            check_data_loss(repo, &pending).await?;
+        }
+        for migration in &pending {
+            repo.apply_migration(migration).await?;
+        }
+    }
+
+    Ok(pending)
+}
+
+/// Fails if one of the migrations would delete the rows of a table that has some.
+async fn check_data_loss(
+    repo: &mut dyn Repository,
+    pending: &[&'static Migration],
+) -> Result<(), Error> {
+    for migration in pending {
+        if let Some(table) = migration.deletes_from {
+            let rows = repo.count_rows(table).await?;
+            if rows > 0 {
+                return Err(Error::LowLevel(format!(
+                    "Migration {} {} would delete the {} rows in {}; run `dataloader migrate --allow-data-loss` to apply it, then load the turbines file again",
+                    migration.version, migration.name, rows, table
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "sqlite")]
+    use crate::sqlite::{open_connection, SqliteRepository};
+    #[cfg(feature = "sqlite")]
+    use crate::testing::TempDb;
+
+    #[test]
+    fn splits_batches_on_go() {
+        let migration = Migration {
+            version: 1,
+            name: "test",
+            sql: "CREATE TABLE A (Id INT);\ngo\n\nCREATE PROCEDURE P AS SELECT 1;\n  GO  \nGO\n",
+            deletes_from: None,
+        };
+
+        assert_eq!(
+            migration.batches(),
+            vec![
+                "CREATE TABLE A (Id INT);\n",
+                "\nCREATE PROCEDURE P AS SELECT 1;\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn pending_skips_applied_versions() {
+        let versions = pending(SQLITE_MIGRATIONS, &[1, 3])
+            .iter()
+            .map(|m| m.version)
+            .collect::<Vec<_>>();
+        assert_eq!(versions[..2], [2, 4]);
+        assert_eq!(versions.len(), SQLITE_MIGRATIONS.len() - 2);
+    }
+
+    #[test]
+    fn backends_have_the_same_versions() {
+        let names = |all: &[Migration]| all.iter().map(|m| (m.version, m.name)).collect::<Vec<_>>();
+        assert_eq!(names(MSSQL_MIGRATIONS), names(SQLITE_MIGRATIONS));
+        assert!(MSSQL_MIGRATIONS
+            .iter()
+            .zip(1..)
+            .all(|(m, version)| m.version == version));
+    }
+
+    /// A database file with just the initial schema, with a turbine loaded
+    /// before turbines had case_ids if `turbine` is true.
+    #[cfg(feature = "sqlite")]
+    async fn initial_database(name: &str, turbine: bool) -> (TempDb, SqliteRepository) {
+        let db = TempDb::new(name);
+        let mut repo = SqliteRepository::open(db.path()).unwrap();
+        repo.apply_migration(&SQLITE_MIGRATIONS[0]).await.unwrap();
+        if turbine {
+            open_connection(db.path())
+                .unwrap()
+                .execute_batch(
+                    "PRAGMA foreign_keys = OFF;
+                    INSERT INTO Turbine (Id, CountyId, ProjectId, ModelId, ImageSourceId, Retrofit,
+                        AttributesConfidenceLevel, LocationConfidenceLevel, Latitude, Longitude)
+                    VALUES (1, 1, 1, 1, 1, 0, 3, 3, 42.7, -93.2);",
+                )
+                .unwrap();
+        }
+
+        (db, repo)
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn refuses_to_delete_turbines_unless_allowed() {
+        let (_db, mut repo) = initial_database("migrate-guard", true).await;
+
+        let err = migrate(&mut repo, false, false).await.unwrap_err();
+        assert!(err.to_string().contains("--allow-data-loss"), "{}", err);
+        assert_eq!(repo.get_applied_migrations().await.unwrap(), vec![1]);
+        assert_eq!(repo.count_rows("Turbine").await.unwrap(), 1);
+
+        let applied = migrate(&mut repo, false, true).await.unwrap();
+        assert_eq!(applied.len(), SQLITE_MIGRATIONS.len() - 1);
+        assert_eq!(repo.count_rows("Turbine").await.unwrap(), 0);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn migrates_a_database_without_turbines() {
+        let (_db, mut repo) = initial_database("migrate-empty", false).await;
+
+        let applied = migrate(&mut repo, false, false).await.unwrap();
+        assert_eq!(applied.len(), SQLITE_MIGRATIONS.len() - 1);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn keeps_project_capacities_exactly() {
+        let (db, mut repo) = initial_database("migrate-capacity", false).await;
+        for migration in &SQLITE_MIGRATIONS[1..6] {
+            repo.apply_migration(migration).await.unwrap();
+        }
+        open_connection(db.path())
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO Project (Id, Name, NumTurbines, CapacityMW) VALUES
+                    (1, 'A', 1, 1.65), (2, 'B', 2, 2.0), (3, 'C', 3, NULL);",
+            )
+            .unwrap();
+
+        repo.apply_migration(&SQLITE_MIGRATIONS[6]).await.unwrap();
+
+        let mut projects = repo.get_all_projects().await.unwrap();
+        projects.sort_by_key(|p| p.id);
+        let capacities = projects
+            .iter()
+            .map(|p| p.capacity_mw.map(|c| c.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            capacities,
+            vec![Some("1.650".to_string()), Some("2.000".to_string()), None]
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn projects_without_a_year_are_unique_as_on_ms_sql() {
+        let (db, mut repo) = initial_database("migrate-project-key", false).await;
+        migrate(&mut repo, false, false).await.unwrap();
+
+        let conn = open_connection(db.path()).unwrap();
+        conn.execute_batch("INSERT INTO State (Id, Name, StateType) VALUES ('IA', 'Iowa', 'S');")
+            .unwrap();
+        let insert = "INSERT INTO Project (Name, StateId, Year) VALUES ('Crystal Lake', 'IA', NULL);";
+        conn.execute_batch(insert).unwrap();
+        let err = conn.execute_batch(insert).unwrap_err();
+        assert!(err.to_string().contains("UNIQUE"), "{}", err);
+        assert_eq!(repo.get_all_projects().await.unwrap().len(), 1);
+    }
+}
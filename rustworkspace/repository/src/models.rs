@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
-use tiberius::{numeric::Decimal, time::chrono::NaiveDate, Row};
+use tiberius::{
+    numeric::Decimal,
+    time::chrono::{NaiveDate, NaiveDateTime},
+    Row,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ImageSource {
@@ -25,6 +29,19 @@ pub enum StateType {
     FederalCapital,
 }
 
+impl TryFrom<Option<&str>> for StateType {
+    type Error = crate::error::Error;
+
+    fn try_from(value: Option<&str>) -> Result<Self, Self::Error> {
+        match value {
+            Some("S") => Ok(StateType::State),
+            Some("T") => Ok(StateType::Territory),
+            Some("F") => Ok(StateType::FederalCapital),
+            x => Err(Self::Error::UnknownStateType(format!("{:?}", x))),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct State {
     pub id: String,
@@ -44,12 +61,7 @@ impl TryFrom<&Row> for State {
         let capital = row.try_get::<&str, _>(2)?.map(|s| s.to_string());
         let population = row.try_get::<i32, _>(3)?;
         let area_square_km = row.try_get::<i32, _>(4)?;
-        let state_type = match row.try_get::<&str, _>(5)? {
-            Some("S") => StateType::State,
-            Some("T") => StateType::Territory,
-            Some("F") => StateType::FederalCapital,
-            x @ _ => return Err(Self::Error::UnknownStateType(format!("{:?}", x))),
-        };
+        let state_type = StateType::try_from(row.try_get::<&str, _>(5)?)?;
 
         Ok(State {
             id,
@@ -67,6 +79,7 @@ pub struct County {
     pub id: i32,
     pub state_id: String,
     pub name: String,
+    pub fips: Option<i32>,
 }
 
 impl TryFrom<&Row> for County {
@@ -76,7 +89,8 @@ impl TryFrom<&Row> for County {
         let id = row.try_get::<i32, _>(0)?.unwrap();
         let state_id = row.try_get::<&str, _>(1)?.unwrap().to_string();
         let name = row.try_get::<&str, _>(2)?.unwrap().to_string();
-        Ok(County { id, state_id, name })
+        let fips = row.try_get::<i32, _>(3)?;
+        Ok(County { id, state_id, name, fips })
     }
 }
 
@@ -95,7 +109,7 @@ impl TryFrom<Option<u8>> for ConfidenceLevel {
             Some(1) => Ok(ConfidenceLevel::Low),
             Some(2) => Ok(ConfidenceLevel::Medium),
             Some(3) => Ok(ConfidenceLevel::High),
-            x @ _ => Err(Self::Error::UnknownConfidenceLevel(format!("{:?}", x))),
+            x => Err(Self::Error::UnknownConfidenceLevel(format!("{:?}", x))),
         }
     }
 }
@@ -116,12 +130,56 @@ impl TryFrom<&Row> for Manufacturer {
     }
 }
 
+/// Another name for a manufacturer, used in some USWTDB rows.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ManufacturerAlias {
+    pub id: i32,
+    pub manufacturer_id: i32,
+    pub alias: String,
+}
+
+impl TryFrom<&Row> for ManufacturerAlias {
+    type Error = crate::error::Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        let id = row.try_get::<i32, _>(0)?.unwrap();
+        let manufacturer_id = row.try_get::<i32, _>(1)?.unwrap();
+        let alias = row.try_get::<&str, _>(2)?.unwrap().to_string();
+        Ok(ManufacturerAlias { id, manufacturer_id, alias })
+    }
+}
+
+/// Another name for a model of a manufacturer, used in some USWTDB rows. The
+/// model is named rather than referred to by Id, as its row is only kept
+/// while turbines use it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ModelAlias {
+    pub id: i32,
+    pub manufacturer_id: i32,
+    pub alias: String,
+    pub model_name: String,
+}
+
+impl TryFrom<&Row> for ModelAlias {
+    type Error = crate::error::Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        let id = row.try_get::<i32, _>(0)?.unwrap();
+        let manufacturer_id = row.try_get::<i32, _>(1)?.unwrap();
+        let alias = row.try_get::<&str, _>(2)?.unwrap().to_string();
+        let model_name = row.try_get::<&str, _>(3)?.unwrap().to_string();
+        Ok(ModelAlias { id, manufacturer_id, alias, model_name })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Project {
     pub id: i32,
     pub name: String,
     pub num_turbines: Option<i16>,
     pub capacity_mw: Option<Decimal>,
+    pub year: Option<i16>,
+    pub state_id: Option<String>,
 }
 
 impl TryFrom<&Row> for Project {
@@ -132,11 +190,15 @@ impl TryFrom<&Row> for Project {
         let name = row.try_get::<&str, _>(1)?.unwrap().to_string();
         let num_turbines = row.try_get::<i16, _>(2)?;
         let capacity_mw = row.try_get::<Decimal, _>(3)?;
+        let year = row.try_get::<i16, _>(4)?;
+        let state_id = row.try_get::<&str, _>(5)?.map(|s| s.to_string());
         Ok(Project {
             id,
             name,
             num_turbines,
             capacity_mw,
+            year,
+            state_id,
         })
     }
 }
@@ -182,6 +244,7 @@ impl TryFrom<&Row> for Model {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Turbine {
     pub id: i32,
+    pub case_id: i32,
     pub county_id: i32,
     pub project_id: i32,
     pub model_id: i32,
@@ -193,6 +256,15 @@ pub struct Turbine {
     pub image_date: Option<NaiveDate>,
     pub latitude: Decimal,
     pub longitude: Decimal,
+    pub faa_ors: Option<String>,
+    pub faa_asn: Option<String>,
+    pub usgs_pr_id: Option<i32>,
+    pub eia_id: Option<i32>,
+    pub hub_height: Option<Decimal>,
+    pub total_height_to_tip: Option<Decimal>,
+    /// The manufacturer and model names as given in the USWTDB, before aliases were applied.
+    pub raw_manufacturer: Option<String>,
+    pub raw_model: Option<String>,
 }
 
 impl TryFrom<&Row> for Turbine {
@@ -211,9 +283,19 @@ impl TryFrom<&Row> for Turbine {
         let image_date = row.try_get::<NaiveDate, _>(9)?;
         let latitude = row.try_get::<Decimal, _>(10)?.unwrap();
         let longitude = row.try_get::<Decimal, _>(11)?.unwrap();
+        let case_id = row.try_get::<i32, _>(12)?.unwrap();
+        let faa_ors = row.try_get::<&str, _>(13)?.map(|s| s.to_string());
+        let faa_asn = row.try_get::<&str, _>(14)?.map(|s| s.to_string());
+        let usgs_pr_id = row.try_get::<i32, _>(15)?;
+        let eia_id = row.try_get::<i32, _>(16)?;
+        let hub_height = row.try_get::<Decimal, _>(17)?;
+        let total_height_to_tip = row.try_get::<Decimal, _>(18)?;
+        let raw_manufacturer = row.try_get::<&str, _>(19)?.map(|s| s.to_string());
+        let raw_model = row.try_get::<&str, _>(20)?.map(|s| s.to_string());
 
         Ok(Turbine {
             id,
+            case_id,
             county_id,
             project_id,
             model_id,
@@ -225,6 +307,174 @@ impl TryFrom<&Row> for Turbine {
             image_date,
             latitude,
             longitude,
+            faa_ors,
+            faa_asn,
+            usgs_pr_id,
+            eia_id,
+            hub_height,
+            total_height_to_tip,
+            raw_manufacturer,
+            raw_model,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Release {
+    pub id: i32,
+    pub version: String,
+    pub release_date: Option<NaiveDate>,
+    pub file_name: String,
+    pub checksum: String,
+    pub turbine_count: i32,
+    pub new_turbines: i32,
+    pub changed_turbines: i32,
+    pub decommissioned_turbines: i32,
+    pub loaded_at: NaiveDateTime,
+}
+
+impl TryFrom<&Row> for Release {
+    type Error = crate::error::Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        let id = row.try_get::<i32, _>(0)?.unwrap();
+        let version = row.try_get::<&str, _>(1)?.unwrap().to_string();
+        let release_date = row.try_get::<NaiveDate, _>(2)?;
+        let file_name = row.try_get::<&str, _>(3)?.unwrap().to_string();
+        let checksum = row.try_get::<&str, _>(4)?.unwrap().to_string();
+        let turbine_count = row.try_get::<i32, _>(5)?.unwrap();
+        let new_turbines = row.try_get::<i32, _>(6)?.unwrap();
+        let changed_turbines = row.try_get::<i32, _>(7)?.unwrap();
+        let decommissioned_turbines = row.try_get::<i32, _>(8)?.unwrap();
+        let loaded_at = row.try_get::<NaiveDateTime, _>(9)?.unwrap();
+
+        Ok(Release {
+            id,
+            version,
+            release_date,
+            file_name,
+            checksum,
+            turbine_count,
+            new_turbines,
+            changed_turbines,
+            decommissioned_turbines,
+            loaded_at,
+        })
+    }
+}
+
+/// A run of the dataloader's load command. `report` is the JSON run report.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LoadRun {
+    pub id: i32,
+    pub started_at: NaiveDateTime,
+    pub finished_at: NaiveDateTime,
+    pub succeeded: bool,
+    pub file_name: Option<String>,
+    pub version: Option<String>,
+    pub checksum: Option<String>,
+    pub rejected_rows: i32,
+    pub warnings: i32,
+    pub error_message: Option<String>,
+    pub report: String,
+}
+
+impl TryFrom<&Row> for LoadRun {
+    type Error = crate::error::Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        let id = row.try_get::<i32, _>(0)?.unwrap();
+        let started_at = row.try_get::<NaiveDateTime, _>(1)?.unwrap();
+        let finished_at = row.try_get::<NaiveDateTime, _>(2)?.unwrap();
+        let succeeded = row.try_get::<bool, _>(3)?.unwrap();
+        let file_name = row.try_get::<&str, _>(4)?.map(|s| s.to_string());
+        let version = row.try_get::<&str, _>(5)?.map(|s| s.to_string());
+        let checksum = row.try_get::<&str, _>(6)?.map(|s| s.to_string());
+        let rejected_rows = row.try_get::<i32, _>(7)?.unwrap();
+        let warnings = row.try_get::<i32, _>(8)?.unwrap();
+        let error_message = row.try_get::<&str, _>(9)?.map(|s| s.to_string());
+        let report = row.try_get::<&str, _>(10)?.unwrap().to_string();
+
+        Ok(LoadRun {
+            id,
+            started_at,
+            finished_at,
+            succeeded,
+            file_name,
+            version,
+            checksum,
+            rejected_rows,
+            warnings,
+            error_message,
+            report,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChangeType {
+    New,
+    Changed,
+    Decommissioned,
+}
+
+impl ChangeType {
+    /// Return the single letter code stored in the ChangeType column.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ChangeType::New => "N",
+            ChangeType::Changed => "C",
+            ChangeType::Decommissioned => "D",
+        }
+    }
+}
+
+impl TryFrom<Option<&str>> for ChangeType {
+    type Error = crate::error::Error;
+
+    fn try_from(value: Option<&str>) -> Result<Self, Self::Error> {
+        match value {
+            Some("N") => Ok(ChangeType::New),
+            Some("C") => Ok(ChangeType::Changed),
+            Some("D") => Ok(ChangeType::Decommissioned),
+            x => Err(Self::Error::UnknownChangeType(format!("{:?}", x))),
+        }
+    }
+}
+
+/// One row of a turbine's history. New and decommissioned turbines have a
+/// single row with no attribute; a changed turbine has a row per attribute.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TurbineChange {
+    pub id: i32,
+    pub release_id: i32,
+    pub case_id: i32,
+    pub change_type: ChangeType,
+    pub attribute: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+impl TryFrom<&Row> for TurbineChange {
+    type Error = crate::error::Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        let id = row.try_get::<i32, _>(0)?.unwrap();
+        let release_id = row.try_get::<i32, _>(1)?.unwrap();
+        let case_id = row.try_get::<i32, _>(2)?.unwrap();
+        let change_type = ChangeType::try_from(row.try_get::<&str, _>(3)?)?;
+        let attribute = row.try_get::<&str, _>(4)?.map(|s| s.to_string());
+        let old_value = row.try_get::<&str, _>(5)?.map(|s| s.to_string());
+        let new_value = row.try_get::<&str, _>(6)?.map(|s| s.to_string());
+
+        Ok(TurbineChange {
+            id,
+            release_id,
+            case_id,
+            change_type,
+            attribute,
+            old_value,
+            new_value,
         })
     }
 }
@@ -0,0 +1,1029 @@
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
+use tiberius::{numeric::Decimal, Client, FromSql, Query, Row};
+use tokio::net::TcpStream;
+use tokio_util::compat::{Compat, TokioAsyncWriteCompatExt};
+
+use crate::dimension::{Dialect, Dimension, Prune, RowState, SyncCounts, Value};
+use crate::error::Error;
+use crate::load::{
+    HistoryEntry, LoadCheckpoint, NewLoadRun, NewRelease, ReleaseCounts, TurbineRow,
+};
+use crate::migrations::{Migration, MSSQL_MIGRATIONS};
+use crate::models::*;
+use crate::Repository;
+
+/// Selects the columns of Turbine in the order expected by `Turbine::try_from`.
+const TURBINE_SELECT: &str = "SELECT Id, CountyId, ProjectId, ModelId, ImageSourceId,
+    Retrofit, RetrofitYear, AttributesConfidenceLevel, LocationConfidenceLevel,
+    ImageDate, Latitude, Longitude, CaseId, FaaOrs, FaaAsn, UsgsPrId, EiaId,
+    HubHeight, TotalHeightToTip, RawManufacturer, RawModel FROM dbo.Turbine";
+
+/// Represents a connection to the MS SQL US Wind Power Stats database.
+pub struct MsSqlRepository {
+    client: Client<Compat<TcpStream>>,
+}
+
+impl MsSqlRepository {
+    /// Opens a new connection.
+    pub async fn open(connection_string: &str) -> Result<Self, Error> {
+        let config = tiberius::Config::from_ado_string(connection_string)?;
+        let tcp = TcpStream::connect(config.get_addr()).await?;
+        tcp.set_nodelay(true)?;
+        let client = Client::connect(config, tcp.compat_write()).await?;
+
+        Ok(MsSqlRepository { client })
+    }
+
+    /// Runs a query that returns a single count.
+    async fn count(&mut self, sql: &str, values: &[Value]) -> Result<i32, Error> {
+        let row = bind(sql, values)
+            .query(&mut self.client)
+            .await?
+            .into_row()
+            .await?;
+
+        Ok(row.and_then(|r| r.get::<i32, _>(0)).unwrap_or_default())
+    }
+
+    /// Runs each batch of the migration then records it. The caller is
+    /// responsible for the surrounding transaction.
+    async fn run_migration(&mut self, migration: &Migration) -> Result<(), Error> {
+        for batch in migration.batches() {
+            self.client.simple_query(batch).await?.into_results().await?;
+        }
+
+        self.client
+            .execute(
+                "INSERT INTO dbo.SchemaVersion (Version, Name) VALUES (@P1, @P2)",
+                &[&migration.version, &migration.name],
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Repository for MsSqlRepository {
+    async fn get_all_image_sources(&mut self) -> Result<Vec<ImageSource>, Error> {
+        let stream = self
+            .client
+            .simple_query("SELECT Id, Name FROM dbo.ImageSource")
+            .await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(ImageSource::try_from)
+            .collect()
+    }
+
+    async fn get_image_source(&mut self, id: u8) -> Result<ImageSource, Error> {
+        let stream = self
+            .client
+            .query(
+                "SELECT Id, Name FROM dbo.ImageSource WHERE Id = @P1",
+                &[&id],
+            )
+            .await?;
+        let rows = stream.into_first_result().await?;
+        match rows.first() {
+            Some(row) => Ok(row.try_into()?),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn update_image_source(&mut self, id: u8, name: &str) -> Result<u64, Error> {
+        let stmt = "UPDATE dbo.ImageSource SET Name = @P1 WHERE Id = @P2;";
+        let result = self.client.execute(stmt, &[&name, &id]).await?;
+        Ok(result.total())
+    }
+
+    async fn get_all_states(&mut self) -> Result<Vec<State>, Error> {
+        let stream = self
+            .client
+            .simple_query(
+                "SELECT Id, Name, Capital, Population, AreaSquareKm, StateType FROM dbo.State",
+            )
+            .await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(State::try_from)
+            .collect()
+    }
+
+    async fn get_all_counties(&mut self) -> Result<Vec<County>, Error> {
+        let stream = self
+            .client
+            .simple_query("SELECT Id, StateId, Name, Fips FROM dbo.County")
+            .await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(County::try_from)
+            .collect()
+    }
+
+    async fn get_county_by_fips(&mut self, fips: i32) -> Result<County, Error> {
+        let stream = self
+            .client
+            .query(
+                "SELECT Id, StateId, Name, Fips FROM dbo.County WHERE Fips = @P1",
+                &[&fips],
+            )
+            .await?;
+        let rows = stream.into_first_result().await?;
+        match rows.first() {
+            Some(row) => Ok(row.try_into()?),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn get_all_projects(&mut self) -> Result<Vec<Project>, Error> {
+        let stream = self
+            .client
+            .simple_query("SELECT Id, Name, NumTurbines, CapacityMW, Year, StateId FROM dbo.Project")
+            .await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(Project::try_from)
+            .collect()
+    }
+
+    async fn get_all_manufacturers(&mut self) -> Result<Vec<Manufacturer>, Error> {
+        let stream = self
+            .client
+            .simple_query("SELECT Id, Name FROM dbo.Manufacturer")
+            .await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(Manufacturer::try_from)
+            .collect()
+    }
+
+    async fn get_all_manufacturer_aliases(&mut self) -> Result<Vec<ManufacturerAlias>, Error> {
+        let stream = self
+            .client
+            .simple_query("SELECT Id, ManufacturerId, Alias FROM dbo.ManufacturerAlias")
+            .await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(ManufacturerAlias::try_from)
+            .collect()
+    }
+
+    async fn get_all_models(&mut self) -> Result<Vec<Model>, Error> {
+        let stream = self
+            .client
+            .simple_query(
+                "SELECT Id, ManufacturerId, Name, CapacityKW,
+                HubHeight, RotorDiameter, RotorSweptArea, TotalHeightToTip FROM dbo.Model",
+            )
+            .await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(Model::try_from)
+            .collect()
+    }
+
+    async fn get_all_model_aliases(&mut self) -> Result<Vec<ModelAlias>, Error> {
+        let stream = self
+            .client
+            .simple_query("SELECT Id, ManufacturerId, Alias, ModelName FROM dbo.ModelAlias")
+            .await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(ModelAlias::try_from)
+            .collect()
+    }
+
+    async fn get_all_turbines(&mut self) -> Result<Vec<Turbine>, Error> {
+        let stream = self.client.simple_query(TURBINE_SELECT).await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(Turbine::try_from)
+            .collect()
+    }
+
+    async fn get_turbines_by_eia_id(&mut self, eia_id: i32) -> Result<Vec<Turbine>, Error> {
+        let stmt = format!("{} WHERE EiaId = @P1", TURBINE_SELECT);
+        let stream = self.client.query(stmt, &[&eia_id]).await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(Turbine::try_from)
+            .collect()
+    }
+
+    async fn get_turbines_by_faa_asn(&mut self, faa_asn: &str) -> Result<Vec<Turbine>, Error> {
+        let stmt = format!("{} WHERE FaaAsn = @P1", TURBINE_SELECT);
+        let stream = self.client.query(stmt, &[&faa_asn]).await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(Turbine::try_from)
+            .collect()
+    }
+
+    async fn get_all_releases(&mut self) -> Result<Vec<Release>, Error> {
+        let stream = self
+            .client
+            .simple_query(
+                "SELECT Id, Version, ReleaseDate, FileName, Checksum, TurbineCount,
+                NewTurbines, ChangedTurbines, DecommissionedTurbines, LoadedAt FROM dbo.Release",
+            )
+            .await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(Release::try_from)
+            .collect()
+    }
+
+    async fn get_all_load_runs(&mut self) -> Result<Vec<LoadRun>, Error> {
+        let stream = self
+            .client
+            .simple_query(
+                "SELECT Id, StartedAt, FinishedAt, Succeeded, FileName, Version, Checksum,
+                RejectedRows, Warnings, ErrorMessage, Report FROM dbo.LoadRun",
+            )
+            .await?;
+
+        stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(LoadRun::try_from)
+            .collect()
+    }
+
+    async fn get_turbine_history(&mut self, case_id: i32) -> Result<Vec<TurbineChange>, Error> {
+        let stream = self
+            .client
+            .query(
+                "SELECT Id, ReleaseId, CaseId, ChangeType, Attribute, OldValue, NewValue
+                FROM dbo.TurbineHistory WHERE CaseId = @P1 ORDER BY Id",
+                &[&case_id],
+            )
+            .await?;
+
+        let history = stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(TurbineChange::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if history.is_empty() {
+            Err(Error::NotFound)
+        } else {
+            Ok(history)
+        }
+    }
+
+    fn migrations(&self) -> &'static [Migration] {
+        MSSQL_MIGRATIONS
+    }
+
+    async fn get_applied_migrations(&mut self) -> Result<Vec<i32>, Error> {
+        // Does not create the tracking table, so that a dry run stays read-only.
+        let stream = self
+            .client
+            .simple_query(
+                "IF OBJECT_ID(N'dbo.SchemaVersion', N'U') IS NOT NULL
+                    SELECT Version FROM dbo.SchemaVersion ORDER BY Version
+                ELSE
+                    SELECT CAST(NULL AS INT) AS Version WHERE 1 = 0",
+            )
+            .await?;
+
+        Ok(stream
+            .into_first_result()
+            .await?
+            .iter()
+            .filter_map(|row| row.get::<i32, _>(0))
+            .collect())
+    }
+
+    async fn apply_migration(&mut self, migration: &Migration) -> Result<(), Error> {
+        self.client
+            .simple_query(
+                "IF OBJECT_ID(N'dbo.SchemaVersion', N'U') IS NULL
+                CREATE TABLE dbo.SchemaVersion (
+                    Version INT NOT NULL CONSTRAINT PK_SchemaVersion PRIMARY KEY,
+                    Name NVARCHAR(100) NOT NULL,
+                    AppliedAt DATETIME2 NOT NULL CONSTRAINT DF_SchemaVersion_AppliedAt DEFAULT SYSUTCDATETIME()
+                );",
+            )
+            .await?
+            .into_results()
+            .await?;
+
+        self.client.simple_query("BEGIN TRANSACTION").await?.into_results().await?;
+
+        let result = self.run_migration(migration).await;
+        let end = match result {
+            Ok(_) => "COMMIT TRANSACTION",
+            Err(_) => "IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION",
+        };
+        self.client.simple_query(end).await?.into_results().await?;
+
+        result
+    }
+
+    async fn count_rows(&mut self, table: &str) -> Result<i64, Error> {
+        let sql = format!(
+            "IF OBJECT_ID(N'dbo.{0}', N'U') IS NOT NULL
+                SELECT COUNT_BIG(*) FROM dbo.{0}
+            ELSE
+                SELECT CAST(0 AS BIGINT)",
+            table
+        );
+        let row = self.client.simple_query(sql).await?.into_row().await?;
+        Ok(row.and_then(|r| r.get::<i64, _>(0)).unwrap_or_default())
+    }
+
+    async fn begin(&mut self) -> Result<(), Error> {
+        // XACT_ABORT makes any error roll back the whole transaction, rather
+        // than just the statement that failed.
+        self.client
+            .simple_query("SET XACT_ABORT ON; BEGIN TRANSACTION;")
+            .await?
+            .into_results()
+            .await?;
+        Ok(())
+    }
+
+    async fn commit(&mut self) -> Result<(), Error> {
+        self.client
+            .simple_query("COMMIT TRANSACTION")
+            .await?
+            .into_results()
+            .await?;
+        Ok(())
+    }
+
+    async fn rollback(&mut self) -> Result<(), Error> {
+        self.client
+            .simple_query("IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION")
+            .await?
+            .into_results()
+            .await?;
+        Ok(())
+    }
+
+    async fn sync_dimension(
+        &mut self,
+        dimension: &Dimension,
+        rows: &[Vec<Value>],
+    ) -> Result<SyncCounts, Error> {
+        let update = dimension.update_sql(Dialect::MsSql);
+        let exists = dimension.exists_sql(Dialect::MsSql);
+        let insert = dimension.insert_sql(Dialect::MsSql);
+
+        let mut counts = SyncCounts::default();
+        for row in rows {
+            if let Some(update) = &update {
+                if bind(update, row).execute(&mut self.client).await?.total() > 0 {
+                    counts.updated += 1;
+                    continue;
+                }
+            }
+
+            if self.count(&exists, row).await? == 0 {
+                bind(&insert, row).execute(&mut self.client).await?;
+                counts.inserted += 1;
+            }
+        }
+
+        Ok(counts)
+    }
+
+    async fn prune_dimension(
+        &mut self,
+        dimension: &Dimension,
+        loaded: &[Vec<Value>],
+    ) -> Result<usize, Error> {
+        let stmt = dimension.prune_sql(Dialect::MsSql);
+        let staged = matches!(dimension.prune, Prune::NotLoaded);
+        if staged {
+            let create = dimension.create_loaded_keys_sql(Dialect::MsSql);
+            self.client.simple_query(create).await?.into_results().await?;
+
+            let key_len = dimension.key_len();
+            let batch_size = (MAX_PARAMETERS / key_len).min(MAX_INSERT_ROWS);
+            for batch in loaded.chunks(batch_size) {
+                let insert = dimension.insert_loaded_keys_sql(Dialect::MsSql, batch.len());
+                let keys = batch
+                    .iter()
+                    .flat_map(|row| row[..key_len].iter().cloned())
+                    .collect::<Vec<_>>();
+                bind(&insert, &keys).execute(&mut self.client).await?;
+            }
+        }
+
+        let removed = Query::new(stmt.as_str())
+            .execute(&mut self.client)
+            .await?
+            .total() as usize;
+        if staged {
+            let drop = Dialect::MsSql.drop_loaded_keys_sql();
+            self.client.simple_query(drop).await?.into_results().await?;
+        }
+
+        Ok(removed)
+    }
+
+    async fn compare_dimension(
+        &mut self,
+        dimension: &Dimension,
+        rows: &[Vec<Value>],
+    ) -> Result<Vec<RowState>, Error> {
+        let exists = dimension.exists_sql(Dialect::MsSql);
+        let differs = dimension.differs_sql(Dialect::MsSql);
+
+        let mut states = Vec::with_capacity(rows.len());
+        for row in rows {
+            if self.count(&exists, row).await? == 0 {
+                states.push(RowState::Missing);
+                continue;
+            }
+
+            let different = match &differs {
+                Some(differs) => self.count(differs, row).await? > 0,
+                None => false,
+            };
+            states.push(if different {
+                RowState::Different
+            } else {
+                RowState::Same
+            });
+        }
+
+        Ok(states)
+    }
+
+    async fn get_dimension_rows(
+        &mut self,
+        dimension: &Dimension,
+    ) -> Result<Vec<Vec<Option<String>>>, Error> {
+        let sql = dimension.select_text_sql(Dialect::MsSql);
+        let columns = dimension.columns().len();
+        let stream = self.client.simple_query(sql).await?;
+
+        Ok(stream
+            .into_first_result()
+            .await?
+            .iter()
+            .map(|row| {
+                (0..columns)
+                    .map(|idx| row.get::<&str, _>(idx).map(|v| v.trim().to_string()))
+                    .collect()
+            })
+            .collect())
+    }
+
+    async fn get_state_ids(&mut self) -> Result<HashSet<String>, Error> {
+        let stream = self.client.simple_query("SELECT Id FROM dbo.State").await?;
+
+        Ok(stream
+            .into_first_result()
+            .await?
+            .iter()
+            .filter_map(|row| row.get::<&str, _>(0))
+            .map(|id| id.trim().to_string())
+            .collect())
+    }
+
+    async fn get_manufacturer_names(&mut self) -> Result<HashSet<String>, Error> {
+        let stream = self
+            .client
+            .simple_query("SELECT Name FROM dbo.Manufacturer")
+            .await?;
+
+        Ok(stream
+            .into_first_result()
+            .await?
+            .iter()
+            .filter_map(|row| row.get::<&str, _>(0))
+            .map(|name| name.to_string())
+            .collect())
+    }
+
+    async fn get_model_names(&mut self) -> Result<HashSet<(String, String)>, Error> {
+        let stream = self
+            .client
+            .simple_query(
+                "SELECT MF.Name, M.Name FROM dbo.Model M
+                INNER JOIN dbo.Manufacturer MF ON MF.Id = M.ManufacturerId",
+            )
+            .await?;
+
+        Ok(stream
+            .into_first_result()
+            .await?
+            .iter()
+            .filter_map(|row| {
+                Some((
+                    row.get::<&str, _>(0)?.to_string(),
+                    row.get::<&str, _>(1)?.to_string(),
+                ))
+            })
+            .collect())
+    }
+
+    async fn get_case_ids(&mut self) -> Result<HashSet<i32>, Error> {
+        let stream = self
+            .client
+            .simple_query("SELECT CaseId FROM dbo.Turbine")
+            .await?;
+
+        Ok(stream
+            .into_first_result()
+            .await?
+            .iter()
+            .filter_map(|row| row.get::<i32, _>(0))
+            .collect())
+    }
+
+    async fn get_turbine_rows(
+        &mut self,
+        case_ids: &[i32],
+    ) -> Result<HashMap<i32, TurbineRow>, Error> {
+        let mut turbines = HashMap::with_capacity(case_ids.len());
+
+        for batch in case_ids.chunks(CASE_ID_BATCH_SIZE) {
+            let mut query = Query::new(format!(
+                "SELECT T.CaseId, C.Fips, P.Name, P.StateId, CAST(P.Year AS INT), MF.Name, M.Name,
+                    T.RawManufacturer, T.RawModel, I.Name, T.Retrofit, CAST(T.RetrofitYear AS INT),
+                    T.AttributesConfidenceLevel, T.LocationConfidenceLevel, CONVERT(CHAR(10), T.ImageDate, 126),
+                    CAST(ROUND(T.Latitude * 1000000, 0) AS BIGINT), CAST(ROUND(T.Longitude * 1000000, 0) AS BIGINT),
+                    T.FaaOrs, T.FaaAsn, T.UsgsPrId, T.EiaId,
+                    CAST(ROUND(T.HubHeight * 100, 0) AS BIGINT), CAST(ROUND(T.TotalHeightToTip * 100, 0) AS BIGINT)
+                FROM dbo.Turbine T
+                INNER JOIN dbo.County C ON C.Id = T.CountyId
+                INNER JOIN dbo.Project P ON P.Id = T.ProjectId
+                INNER JOIN dbo.Model M ON M.Id = T.ModelId
+                INNER JOIN dbo.Manufacturer MF ON MF.Id = M.ManufacturerId
+                INNER JOIN dbo.ImageSource I ON I.Id = T.ImageSourceId
+                WHERE T.CaseId IN ({})",
+                params(batch.len(), 1)
+            ));
+            for case_id in batch {
+                query.bind(*case_id);
+            }
+
+            for row in query
+                .query(&mut self.client)
+                .await?
+                .into_first_result()
+                .await?
+            {
+                let text = |idx: usize| -> Result<Option<String>, Error> {
+                    Ok(row.try_get::<&str, _>(idx)?.map(|s| s.to_string()))
+                };
+
+                let turbine = TurbineRow {
+                    case_id: required(&row, 0)?,
+                    county_fips: row.try_get(1)?.unwrap_or_default(),
+                    project: text(2)?.unwrap_or_default(),
+                    project_state: text(3)?.unwrap_or_default(),
+                    project_year: row.try_get(4)?,
+                    manufacturer: text(5)?.unwrap_or_default(),
+                    model: text(6)?.unwrap_or_default(),
+                    raw_manufacturer: text(7)?,
+                    raw_model: text(8)?,
+                    image_source: text(9)?.unwrap_or_default(),
+                    retrofit: row.try_get(10)?.unwrap_or_default(),
+                    retrofit_year: row.try_get(11)?,
+                    attributes_confidence_level: row.try_get(12)?.unwrap_or_default(),
+                    location_confidence_level: row.try_get(13)?.unwrap_or_default(),
+                    image_date: text(14)?,
+                    latitude: row.try_get(15)?.unwrap_or_default(),
+                    longitude: row.try_get(16)?.unwrap_or_default(),
+                    faa_ors: text(17)?,
+                    faa_asn: text(18)?,
+                    usgs_pr_id: row.try_get(19)?,
+                    eia_id: row.try_get(20)?,
+                    hub_height: row.try_get(21)?,
+                    total_height_to_tip: row.try_get(22)?,
+                };
+
+                turbines.insert(turbine.case_id, turbine);
+            }
+        }
+
+        Ok(turbines)
+    }
+
+    async fn upsert_turbines(&mut self, turbines: &[TurbineRow]) -> Result<(), Error> {
+        if turbines.is_empty() {
+            return Ok(());
+        }
+
+        // The staging table must be created in a plain batch; one created by a
+        // parameterised query would be dropped as soon as that query finished.
+        let stmt = "
+        IF OBJECT_ID('tempdb..#TurbineStaging') IS NOT NULL DROP TABLE #TurbineStaging;
+
+        CREATE TABLE #TurbineStaging (
+            CaseId INT NOT NULL PRIMARY KEY,
+            CountyFips INT NOT NULL,
+            ProjectName NVARCHAR(200) COLLATE DATABASE_DEFAULT NOT NULL,
+            ProjectStateId CHAR(2) COLLATE DATABASE_DEFAULT NOT NULL,
+            ProjectYear SMALLINT NULL,
+            ManufacturerName NVARCHAR(100) COLLATE DATABASE_DEFAULT NOT NULL,
+            ModelName NVARCHAR(100) COLLATE DATABASE_DEFAULT NOT NULL,
+            ImageSourceName NVARCHAR(50) COLLATE DATABASE_DEFAULT NOT NULL,
+            Retrofit BIT NOT NULL,
+            RetrofitYear SMALLINT NULL,
+            AttributesConfidenceLevel TINYINT NOT NULL,
+            LocationConfidenceLevel TINYINT NOT NULL,
+            ImageDate DATE NULL,
+            Latitude DECIMAL(9, 6) NOT NULL,
+            Longitude DECIMAL(9, 6) NOT NULL,
+            FaaOrs NVARCHAR(20) COLLATE DATABASE_DEFAULT NULL,
+            FaaAsn NVARCHAR(30) COLLATE DATABASE_DEFAULT NULL,
+            UsgsPrId INT NULL,
+            EiaId INT NULL,
+            HubHeight DECIMAL(6, 2) NULL,
+            TotalHeightToTip DECIMAL(6, 2) NULL,
+            RawManufacturer NVARCHAR(100) COLLATE DATABASE_DEFAULT NULL,
+            RawModel NVARCHAR(100) COLLATE DATABASE_DEFAULT NULL,
+            CountyId INT NULL,
+            ProjectId INT NULL,
+            ModelId INT NULL,
+            ImageSourceId TINYINT NULL
+        );
+        ";
+        self.client.simple_query(stmt).await?.into_results().await?;
+
+        for batch in turbines.chunks(TURBINE_BATCH_SIZE) {
+            let rows = (0..batch.len())
+                .map(|r| format!("({})", params(STAGING_COLUMNS, r * STAGING_COLUMNS + 1)))
+                .collect::<Vec<_>>();
+            let mut query = Query::new(format!(
+                "INSERT INTO #TurbineStaging (CaseId, CountyFips, ProjectName, ProjectStateId, ProjectYear,
+                    ManufacturerName, ModelName, ImageSourceName, Retrofit, RetrofitYear,
+                    AttributesConfidenceLevel, LocationConfidenceLevel, ImageDate, Latitude, Longitude,
+                    FaaOrs, FaaAsn, UsgsPrId, EiaId, HubHeight, TotalHeightToTip, RawManufacturer, RawModel)
+                VALUES {}",
+                rows.join(", ")
+            ));
+
+            for t in batch {
+                query.bind(t.case_id);
+                query.bind(t.county_fips);
+                query.bind(t.project.as_str());
+                query.bind(t.project_state.as_str());
+                query.bind(t.project_year);
+                query.bind(t.manufacturer.as_str());
+                query.bind(t.model.as_str());
+                query.bind(t.image_source.as_str());
+                query.bind(t.retrofit);
+                query.bind(t.retrofit_year);
+                query.bind(t.attributes_confidence_level);
+                query.bind(t.location_confidence_level);
+                query.bind(t.image_date.as_deref());
+                query.bind(Decimal::new(t.latitude, 6));
+                query.bind(Decimal::new(t.longitude, 6));
+                query.bind(t.faa_ors.as_deref());
+                query.bind(t.faa_asn.as_deref());
+                query.bind(t.usgs_pr_id);
+                query.bind(t.eia_id);
+                query.bind(t.hub_height.map(|h| Decimal::new(h, 2)));
+                query.bind(t.total_height_to_tip.map(|h| Decimal::new(h, 2)));
+                query.bind(t.raw_manufacturer.as_deref());
+                query.bind(t.raw_model.as_deref());
+            }
+
+            query.execute(&mut self.client).await?;
+        }
+
+        // Resolve the surrogate keys for every staged turbine in one statement, then
+        // update the turbines that already exist and insert the rest. The key columns
+        // are NOT NULL in dbo.Turbine, so an unknown name fails the load rather than
+        // the turbine silently disappearing.
+        let stmt = "
+        UPDATE S SET CountyId = C.Id, ProjectId = P.Id, ModelId = M.Id, ImageSourceId = I.Id
+        FROM #TurbineStaging S
+        LEFT JOIN dbo.County C ON C.Fips = S.CountyFips
+        LEFT JOIN dbo.Project P ON P.Name = S.ProjectName AND P.StateId = S.ProjectStateId
+            AND (P.Year = S.ProjectYear OR (P.Year IS NULL AND S.ProjectYear IS NULL))
+        LEFT JOIN dbo.Manufacturer MF ON MF.Name = S.ManufacturerName
+        LEFT JOIN dbo.Model M ON M.ManufacturerId = MF.Id AND M.Name = S.ModelName
+        LEFT JOIN dbo.ImageSource I ON I.Name = S.ImageSourceName;
+
+        UPDATE T SET CountyId = S.CountyId, ProjectId = S.ProjectId, ModelId = S.ModelId,
+            ImageSourceId = S.ImageSourceId, Retrofit = S.Retrofit, RetrofitYear = S.RetrofitYear,
+            AttributesConfidenceLevel = S.AttributesConfidenceLevel, LocationConfidenceLevel = S.LocationConfidenceLevel,
+            ImageDate = S.ImageDate, Latitude = S.Latitude, Longitude = S.Longitude,
+            FaaOrs = S.FaaOrs, FaaAsn = S.FaaAsn, UsgsPrId = S.UsgsPrId, EiaId = S.EiaId,
+            HubHeight = S.HubHeight, TotalHeightToTip = S.TotalHeightToTip,
+            RawManufacturer = S.RawManufacturer, RawModel = S.RawModel
+        FROM dbo.Turbine T
+        INNER JOIN #TurbineStaging S ON S.CaseId = T.CaseId;
+
+        INSERT INTO dbo.Turbine (CaseId, CountyId, ProjectId, ModelId, ImageSourceId, Retrofit, RetrofitYear,
+            AttributesConfidenceLevel, LocationConfidenceLevel, ImageDate, Latitude, Longitude,
+            FaaOrs, FaaAsn, UsgsPrId, EiaId, HubHeight, TotalHeightToTip, RawManufacturer, RawModel)
+        SELECT S.CaseId, S.CountyId, S.ProjectId, S.ModelId, S.ImageSourceId, S.Retrofit, S.RetrofitYear,
+            S.AttributesConfidenceLevel, S.LocationConfidenceLevel, S.ImageDate, S.Latitude, S.Longitude,
+            S.FaaOrs, S.FaaAsn, S.UsgsPrId, S.EiaId, S.HubHeight, S.TotalHeightToTip, S.RawManufacturer, S.RawModel
+        FROM #TurbineStaging S
+        WHERE NOT EXISTS (SELECT 1 FROM dbo.Turbine T WHERE T.CaseId = S.CaseId);
+
+        DROP TABLE #TurbineStaging;
+        ";
+        self.client.simple_query(stmt).await?.into_results().await?;
+
+        Ok(())
+    }
+
+    async fn delete_turbines(&mut self, case_ids: &[i32]) -> Result<(), Error> {
+        for batch in case_ids.chunks(CASE_ID_BATCH_SIZE) {
+            let mut query = Query::new(format!(
+                "DELETE dbo.Turbine WHERE CaseId IN ({})",
+                params(batch.len(), 1)
+            ));
+            for case_id in batch {
+                query.bind(*case_id);
+            }
+            query.execute(&mut self.client).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn create_release(&mut self, release: &NewRelease<'_>) -> Result<i32, Error> {
+        let mut query = Query::new(
+            "INSERT INTO dbo.Release (Version, ReleaseDate, FileName, Checksum, TurbineCount,
+                NewTurbines, ChangedTurbines, DecommissionedTurbines)
+            OUTPUT INSERTED.Id
+            VALUES (@P1, @P2, @P3, @P4, 0, 0, 0, 0)",
+        );
+        query.bind(release.version);
+        query.bind(release.release_date);
+        query.bind(release.file_name);
+        query.bind(release.checksum);
+
+        match query.query(&mut self.client).await?.into_row().await? {
+            Some(row) => required(&row, 0),
+            None => Err(Error::LowLevel(
+                "No Id returned for the new release".to_string(),
+            )),
+        }
+    }
+
+    async fn record_history(
+        &mut self,
+        release_id: i32,
+        history: &[HistoryEntry],
+    ) -> Result<(), Error> {
+        for batch in history.chunks(HISTORY_BATCH_SIZE) {
+            let rows = (0..batch.len())
+                .map(|r| format!("({})", params(HISTORY_COLUMNS, r * HISTORY_COLUMNS + 1)))
+                .collect::<Vec<_>>();
+            let mut query = Query::new(format!(
+                "INSERT INTO dbo.TurbineHistory (ReleaseId, CaseId, ChangeType, Attribute, OldValue, NewValue)
+                VALUES {}",
+                rows.join(", ")
+            ));
+
+            for h in batch {
+                query.bind(release_id);
+                query.bind(h.case_id);
+                query.bind(h.change_type.code());
+                query.bind(h.attribute);
+                query.bind(h.old_value.clone());
+                query.bind(h.new_value.clone());
+            }
+
+            query.execute(&mut self.client).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn finish_release(
+        &mut self,
+        release_id: i32,
+        counts: &ReleaseCounts,
+    ) -> Result<(), Error> {
+        let stmt = "UPDATE dbo.Release SET TurbineCount = @P1, NewTurbines = @P2,
+            ChangedTurbines = @P3, DecommissionedTurbines = @P4 WHERE Id = @P5";
+
+        self.client
+            .execute(
+                stmt,
+                &[
+                    &(counts.turbines as i32),
+                    &(counts.new as i32),
+                    &(counts.changed as i32),
+                    &(counts.decommissioned as i32),
+                    &release_id,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn record_load_run(&mut self, run: &NewLoadRun<'_>) -> Result<i32, Error> {
+        let mut query = Query::new(
+            "INSERT INTO dbo.LoadRun (StartedAt, FinishedAt, Succeeded, FileName, Version, Checksum,
+                RejectedRows, Warnings, ErrorMessage, Report)
+            OUTPUT INSERTED.Id
+            VALUES (@P1, @P2, @P3, @P4, @P5, @P6, @P7, @P8, @P9, @P10)",
+        );
+        query.bind(run.started_at);
+        query.bind(run.finished_at);
+        query.bind(run.succeeded);
+        query.bind(run.file_name);
+        query.bind(run.version);
+        query.bind(run.checksum);
+        query.bind(run.rejected_rows as i32);
+        query.bind(run.warnings as i32);
+        query.bind(run.error_message);
+        query.bind(run.report);
+
+        match query.query(&mut self.client).await?.into_row().await? {
+            Some(row) => required(&row, 0),
+            None => Err(Error::LowLevel(
+                "No Id returned for the new load run".to_string(),
+            )),
+        }
+    }
+
+    async fn get_load_checkpoint(
+        &mut self,
+        checksum: &str,
+    ) -> Result<Option<LoadCheckpoint>, Error> {
+        let stream = self
+            .client
+            .query(
+                "SELECT ReleaseId, Turbines, NewTurbines, ChangedTurbines, UnchangedTurbines
+                FROM dbo.LoadCheckpoint WHERE Checksum = @P1",
+                &[&checksum],
+            )
+            .await?;
+
+        let row = match stream.into_row().await? {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        Ok(Some(LoadCheckpoint {
+            checksum: checksum.to_string(),
+            release_id: required(&row, 0)?,
+            turbines: get_count(&row, 1)?,
+            new: get_count(&row, 2)?,
+            changed: get_count(&row, 3)?,
+            unchanged: get_count(&row, 4)?,
+        }))
+    }
+
+    async fn save_load_checkpoint(&mut self, checkpoint: &LoadCheckpoint) -> Result<(), Error> {
+        let mut query = Query::new(
+            "UPDATE dbo.LoadCheckpoint SET ReleaseId = @P2, Turbines = @P3, NewTurbines = @P4,
+                ChangedTurbines = @P5, UnchangedTurbines = @P6, SavedAt = SYSUTCDATETIME()
+            WHERE Checksum = @P1;
+            IF @@ROWCOUNT = 0
+                INSERT INTO dbo.LoadCheckpoint (Checksum, ReleaseId, Turbines, NewTurbines,
+                    ChangedTurbines, UnchangedTurbines)
+                VALUES (@P1, @P2, @P3, @P4, @P5, @P6);",
+        );
+        query.bind(checkpoint.checksum.as_str());
+        query.bind(checkpoint.release_id);
+        query.bind(checkpoint.turbines as i32);
+        query.bind(checkpoint.new as i32);
+        query.bind(checkpoint.changed as i32);
+        query.bind(checkpoint.unchanged as i32);
+        query.execute(&mut self.client).await?;
+
+        Ok(())
+    }
+
+    async fn delete_load_checkpoint(&mut self, checksum: &str) -> Result<(), Error> {
+        self.client
+            .execute(
+                "DELETE FROM dbo.LoadCheckpoint WHERE Checksum = @P1",
+                &[&checksum],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// SQL Server's limit on the parameters of one request.
+const MAX_PARAMETERS: usize = 2100;
+
+/// SQL Server's limit on the rows of one INSERT ... VALUES.
+const MAX_INSERT_ROWS: usize = 1000;
+
+/// The number of columns bound per turbine in the staging INSERT.
+const STAGING_COLUMNS: usize = 23;
+
+/// The number of turbines sent per INSERT into the staging table.
+const TURBINE_BATCH_SIZE: usize = MAX_PARAMETERS / STAGING_COLUMNS;
+
+/// The number of case_ids sent per SELECT or DELETE, again bounded by the parameter limit.
+const CASE_ID_BATCH_SIZE: usize = 2000;
+
+/// The number of columns bound per row of TurbineHistory.
+const HISTORY_COLUMNS: usize = 6;
+
+/// The number of rows sent per INSERT into TurbineHistory.
+const HISTORY_BATCH_SIZE: usize = 300;
+
+// Each batch must stay within the parameter limit.
+const _: () = {
+    assert!(TURBINE_BATCH_SIZE * STAGING_COLUMNS <= MAX_PARAMETERS);
+    assert!(HISTORY_BATCH_SIZE * HISTORY_COLUMNS <= MAX_PARAMETERS);
+    assert!(CASE_ID_BATCH_SIZE <= MAX_PARAMETERS);
+};
+
+/// A comma separated list of `count` parameters, numbered from `first`.
+fn params(count: usize, first: usize) -> String {
+    (first..first + count)
+        .map(|i| format!("@P{}", i))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Creates a query binding the values in order.
+fn bind<'a>(sql: &'a str, values: &'a [Value]) -> Query<'a> {
+    let mut query = Query::new(sql);
+    for value in values {
+        match value {
+            Value::Int(v) => query.bind(*v),
+            Value::Decimal(v) => query.bind(*v),
+            Value::Text(v) => query.bind(v.as_deref()),
+        }
+    }
+    query
+}
+
+/// Gets a column that should never be NULL.
+fn required<'a, R: FromSql<'a>>(row: &'a Row, idx: usize) -> Result<R, Error> {
+    row.try_get(idx)?
+        .ok_or_else(|| Error::LowLevel(format!("Column {} is unexpectedly NULL", idx)))
+}
+
+/// Gets a column holding a count of rows.
+fn get_count(row: &Row, idx: usize) -> Result<usize, Error> {
+    let count = required::<i32>(row, idx)?;
+    usize::try_from(count)
+        .map_err(|_| Error::LowLevel(format!("Column {} holds a negative count {}", idx, count)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params_are_numbered_from_first() {
+        assert_eq!(params(3, 1), "@P1, @P2, @P3");
+        assert_eq!(params(2, 24), "@P24, @P25");
+        assert_eq!(params(0, 1), "");
+    }
+
+    #[test]
+    fn staging_rows_take_consecutive_parameters() {
+        let rows = (0..2)
+            .map(|r| format!("({})", params(STAGING_COLUMNS, r * STAGING_COLUMNS + 1)))
+            .collect::<Vec<_>>();
+        assert!(rows[0].starts_with("(@P1, ") && rows[0].ends_with(", @P23)"));
+        assert!(rows[1].starts_with("(@P24, ") && rows[1].ends_with(", @P46)"));
+    }
+}
@@ -0,0 +1,526 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use crate::dimension::{self, Dimension, Prune, RowState, SyncCounts, Value};
+use crate::error::Error;
+use crate::load::{
+    HistoryEntry, LoadCheckpoint, NewLoadRun, NewRelease, ReleaseCounts, TurbineRow,
+};
+use crate::migrations::Migration;
+use crate::models::*;
+use crate::Repository;
+
+/// The number of turbines read at a time to find what they refer to.
+const CASE_ID_BATCH_SIZE: usize = 2000;
+
+/// The rows a load would insert, update and remove in one table, identified
+/// by their keys; turbines by case_id.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TableChanges {
+    pub inserted: BTreeSet<String>,
+    pub updated: BTreeSet<String>,
+    pub removed: BTreeSet<String>,
+}
+
+impl TableChanges {
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl fmt::Display for TableChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} inserted, {} updated, {} removed",
+            self.inserted.len(),
+            self.updated.len(),
+            self.removed.len()
+        )
+    }
+}
+
+/// The changes a load would make, keyed by table name.
+pub type Plan = BTreeMap<&'static str, TableChanges>;
+
+/// A repository that reads from another but only plans the writes made to
+/// it. Running a load against it finds what the load would change without
+/// changing anything: rows are compared with the database as they would be
+/// written, and the rows a prune would remove are worked out from the
+/// turbines the load leaves behind.
+pub struct DryRun<'a> {
+    db: &'a mut dyn Repository,
+    plan: Plan,
+    /// The rows the plan inserts or updates, by table then key.
+    rows: HashMap<&'static str, HashMap<Vec<Value>, Vec<Value>>>,
+    /// The keys of the rows the plan removes, by table, as text.
+    removed: HashMap<&'static str, HashSet<Vec<Option<String>>>>,
+    existing: Option<HashSet<i32>>,
+    upserted: HashMap<i32, TurbineRow>,
+    deleted: HashSet<i32>,
+    /// The turbines left once the load is complete, found at the first prune.
+    remaining: Option<Vec<TurbineRow>>,
+}
+
+impl<'a> DryRun<'a> {
+    pub fn new(db: &'a mut dyn Repository) -> Self {
+        DryRun {
+            db,
+            plan: Plan::new(),
+            rows: HashMap::new(),
+            removed: HashMap::new(),
+            existing: None,
+            upserted: HashMap::new(),
+            deleted: HashSet::new(),
+            remaining: None,
+        }
+    }
+
+    /// The changes planned so far.
+    pub fn into_plan(self) -> Plan {
+        self.plan
+    }
+
+    /// Reads the case_ids of the turbines in the database, the first time.
+    async fn read_existing(&mut self) -> Result<(), Error> {
+        if self.existing.is_none() {
+            self.existing = Some(self.db.get_case_ids().await?);
+        }
+        Ok(())
+    }
+
+    /// The turbines in the database that the load leaves alone, followed by
+    /// those it writes.
+    async fn remaining(&mut self) -> Result<&[TurbineRow], Error> {
+        if self.remaining.is_none() {
+            self.read_existing().await?;
+            let untouched = self
+                .existing
+                .as_ref()
+                .unwrap()
+                .iter()
+                .copied()
+                .filter(|case_id| {
+                    !self.upserted.contains_key(case_id) && !self.deleted.contains(case_id)
+                })
+                .collect::<Vec<_>>();
+
+            let mut remaining = Vec::with_capacity(untouched.len() + self.upserted.len());
+            for case_ids in untouched.chunks(CASE_ID_BATCH_SIZE) {
+                remaining.extend(self.db.get_turbine_rows(case_ids).await?.into_values());
+            }
+            remaining.extend(self.upserted.values().cloned());
+            self.remaining = Some(remaining);
+        }
+        Ok(self.remaining.as_deref().unwrap())
+    }
+
+    /// The rows of a dimension table once the planned changes are made, as text.
+    async fn rows_after(&mut self, dimension: &Dimension) -> Result<Vec<Vec<Option<String>>>, Error> {
+        let key_len = dimension.key_len();
+        let planned = self
+            .rows
+            .get(dimension.table)
+            .map(|rows| rows.values().map(|row| text(row)).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let planned_keys = planned
+            .iter()
+            .map(|row| row[..key_len].to_vec())
+            .collect::<HashSet<_>>();
+
+        let mut rows = self
+            .db
+            .get_dimension_rows(dimension)
+            .await?
+            .into_iter()
+            .filter(|row| !planned_keys.contains(&row[..key_len]))
+            .collect::<Vec<_>>();
+        rows.extend(planned);
+
+        let removed = self.removed.get(dimension.table);
+        rows.retain(|row| match removed {
+            Some(removed) => !removed.contains(&row[..key_len]),
+            None => true,
+        });
+        Ok(rows)
+    }
+
+    /// The keys of the rows of `dimension` that the rows of `table` refer to
+    /// through `column`, once the load is complete.
+    async fn referenced(
+        &mut self,
+        dimension: &Dimension,
+        table: &str,
+        column: &str,
+    ) -> Result<HashSet<Vec<Option<String>>>, Error> {
+        if table == "Turbine" {
+            return Ok(self
+                .remaining()
+                .await?
+                .iter()
+                .filter_map(|t| turbine_reference(t, column))
+                .collect());
+        }
+
+        let referencing = dimension::by_table(table).ok_or_else(|| {
+            Error::LowLevel(format!(
+                "Cannot plan the pruning of {}: {} is not a dimension",
+                dimension.table, table
+            ))
+        })?;
+        let idx = referencing
+            .columns()
+            .iter()
+            .position(|c| *c == column)
+            .ok_or_else(|| Error::LowLevel(format!("{} has no column {}", table, column)))?;
+
+        Ok(self
+            .rows_after(referencing)
+            .await?
+            .into_iter()
+            .map(|row| vec![row[idx].clone()])
+            .collect())
+    }
+
+    fn changes(&mut self, table: &'static str) -> &mut TableChanges {
+        self.plan.entry(table).or_default()
+    }
+}
+
+/// The columns of a row as `get_dimension_rows` gives them.
+fn text(row: &[Value]) -> Vec<Option<String>> {
+    row.iter().map(Value::to_text).collect()
+}
+
+/// A key as it is shown in a plan.
+fn describe(key: &[Option<String>]) -> String {
+    key.iter()
+        .map(|v| v.as_deref().unwrap_or("NULL"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The key of the dimension row a turbine refers to through a column of the
+/// Turbine table, as text.
+fn turbine_reference(t: &TurbineRow, column: &str) -> Option<Vec<Option<String>>> {
+    let key = match column {
+        "CountyId" => vec![Some(t.county_fips.to_string())],
+        "ProjectId" => vec![
+            Some(t.project.trim().to_string()),
+            Some(t.project_state.trim().to_string()),
+            t.project_year.map(|y| y.to_string()),
+        ],
+        "ModelId" => vec![
+            Some(t.manufacturer.trim().to_string()),
+            Some(t.model.trim().to_string()),
+        ],
+        "ImageSourceId" => vec![Some(t.image_source.trim().to_string())],
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn read_only<T>() -> Result<T, Error> {
+    Err(Error::LowLevel(
+        "A dry run does not write to the database".to_string(),
+    ))
+}
+
+#[async_trait]
+impl Repository for DryRun<'_> {
+    async fn get_all_image_sources(&mut self) -> Result<Vec<ImageSource>, Error> {
+        self.db.get_all_image_sources().await
+    }
+
+    async fn get_image_source(&mut self, id: u8) -> Result<ImageSource, Error> {
+        self.db.get_image_source(id).await
+    }
+
+    async fn update_image_source(&mut self, _id: u8, _name: &str) -> Result<u64, Error> {
+        read_only()
+    }
+
+    async fn get_all_states(&mut self) -> Result<Vec<State>, Error> {
+        self.db.get_all_states().await
+    }
+
+    async fn get_all_counties(&mut self) -> Result<Vec<County>, Error> {
+        self.db.get_all_counties().await
+    }
+
+    async fn get_county_by_fips(&mut self, fips: i32) -> Result<County, Error> {
+        self.db.get_county_by_fips(fips).await
+    }
+
+    async fn get_all_projects(&mut self) -> Result<Vec<Project>, Error> {
+        self.db.get_all_projects().await
+    }
+
+    async fn get_all_manufacturers(&mut self) -> Result<Vec<Manufacturer>, Error> {
+        self.db.get_all_manufacturers().await
+    }
+
+    async fn get_all_manufacturer_aliases(&mut self) -> Result<Vec<ManufacturerAlias>, Error> {
+        self.db.get_all_manufacturer_aliases().await
+    }
+
+    async fn get_all_models(&mut self) -> Result<Vec<Model>, Error> {
+        self.db.get_all_models().await
+    }
+
+    async fn get_all_model_aliases(&mut self) -> Result<Vec<ModelAlias>, Error> {
+        self.db.get_all_model_aliases().await
+    }
+
+    async fn get_all_turbines(&mut self) -> Result<Vec<Turbine>, Error> {
+        self.db.get_all_turbines().await
+    }
+
+    async fn get_turbines_by_eia_id(&mut self, eia_id: i32) -> Result<Vec<Turbine>, Error> {
+        self.db.get_turbines_by_eia_id(eia_id).await
+    }
+
+    async fn get_turbines_by_faa_asn(&mut self, faa_asn: &str) -> Result<Vec<Turbine>, Error> {
+        self.db.get_turbines_by_faa_asn(faa_asn).await
+    }
+
+    async fn get_all_releases(&mut self) -> Result<Vec<Release>, Error> {
+        self.db.get_all_releases().await
+    }
+
+    async fn get_all_load_runs(&mut self) -> Result<Vec<LoadRun>, Error> {
+        self.db.get_all_load_runs().await
+    }
+
+    async fn get_turbine_history(&mut self, case_id: i32) -> Result<Vec<TurbineChange>, Error> {
+        self.db.get_turbine_history(case_id).await
+    }
+
+    fn migrations(&self) -> &'static [Migration] {
+        self.db.migrations()
+    }
+
+    async fn get_applied_migrations(&mut self) -> Result<Vec<i32>, Error> {
+        self.db.get_applied_migrations().await
+    }
+
+    async fn apply_migration(&mut self, _migration: &Migration) -> Result<(), Error> {
+        read_only()
+    }
+
+    async fn count_rows(&mut self, table: &str) -> Result<i64, Error> {
+        self.db.count_rows(table).await
+    }
+
+    async fn begin(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn commit(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn rollback(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Counts as the real sync would, including updates to rows inserted or
+    /// updated earlier in the load, but lists each row in the plan once.
+    async fn sync_dimension(
+        &mut self,
+        dimension: &Dimension,
+        rows: &[Vec<Value>],
+    ) -> Result<SyncCounts, Error> {
+        let key_len = dimension.key_len();
+        let mut counts = SyncCounts::default();
+
+        let planned = self.rows.entry(dimension.table).or_default();
+        let mut unplanned = Vec::new();
+        for row in rows {
+            match planned.get_mut(&row[..key_len]) {
+                Some(old) if old != row => {
+                    *old = row.clone();
+                    counts.updated += 1;
+                }
+                Some(_) => {}
+                None => unplanned.push(row.clone()),
+            }
+        }
+
+        let states = self.db.compare_dimension(dimension, &unplanned).await?;
+        for (row, state) in unplanned.into_iter().zip(states) {
+            let key = describe(&text(&row[..key_len]));
+            match state {
+                RowState::Missing => {
+                    counts.inserted += 1;
+                    self.changes(dimension.table).inserted.insert(key);
+                }
+                RowState::Different => {
+                    counts.updated += 1;
+                    self.changes(dimension.table).updated.insert(key);
+                }
+                RowState::Same => continue,
+            }
+            self.rows
+                .entry(dimension.table)
+                .or_default()
+                .insert(row[..key_len].to_vec(), row);
+        }
+
+        Ok(counts)
+    }
+
+    async fn prune_dimension(
+        &mut self,
+        dimension: &Dimension,
+        loaded: &[Vec<Value>],
+    ) -> Result<usize, Error> {
+        let key_len = dimension.key_len();
+        let removed = match dimension.prune {
+            Prune::NotLoaded => {
+                let loaded = loaded
+                    .iter()
+                    .map(|row| text(&row[..key_len]))
+                    .collect::<HashSet<_>>();
+                self.rows_after(dimension)
+                    .await?
+                    .into_iter()
+                    .map(|row| row[..key_len].to_vec())
+                    .filter(|key| !loaded.contains(key))
+                    .collect::<Vec<_>>()
+            }
+            Prune::Unreferenced(references) => {
+                let mut referenced = HashSet::new();
+                for (table, column) in references {
+                    referenced.extend(self.referenced(dimension, table, column).await?);
+                }
+                self.rows_after(dimension)
+                    .await?
+                    .into_iter()
+                    .map(|row| row[..key_len].to_vec())
+                    .filter(|key| !referenced.contains(key))
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        let count = removed.len();
+        for key in removed {
+            self.changes(dimension.table).removed.insert(describe(&key));
+            self.removed.entry(dimension.table).or_default().insert(key);
+        }
+        Ok(count)
+    }
+
+    async fn compare_dimension(
+        &mut self,
+        dimension: &Dimension,
+        rows: &[Vec<Value>],
+    ) -> Result<Vec<RowState>, Error> {
+        self.db.compare_dimension(dimension, rows).await
+    }
+
+    async fn get_dimension_rows(
+        &mut self,
+        dimension: &Dimension,
+    ) -> Result<Vec<Vec<Option<String>>>, Error> {
+        self.rows_after(dimension).await
+    }
+
+    /// Includes the states the plan inserts, so that their turbines are
+    /// validated as they would be by a real load.
+    async fn get_state_ids(&mut self) -> Result<HashSet<String>, Error> {
+        Ok(self
+            .rows_after(&dimension::STATE)
+            .await?
+            .into_iter()
+            .filter_map(|row| row[0].clone())
+            .collect())
+    }
+
+    async fn get_manufacturer_names(&mut self) -> Result<HashSet<String>, Error> {
+        self.db.get_manufacturer_names().await
+    }
+
+    async fn get_model_names(&mut self) -> Result<HashSet<(String, String)>, Error> {
+        self.db.get_model_names().await
+    }
+
+    async fn get_case_ids(&mut self) -> Result<HashSet<i32>, Error> {
+        self.read_existing().await?;
+        Ok(self.existing.clone().unwrap())
+    }
+
+    async fn get_turbine_rows(
+        &mut self,
+        case_ids: &[i32],
+    ) -> Result<HashMap<i32, TurbineRow>, Error> {
+        self.db.get_turbine_rows(case_ids).await
+    }
+
+    async fn upsert_turbines(&mut self, turbines: &[TurbineRow]) -> Result<(), Error> {
+        self.read_existing().await?;
+        let existing = self.existing.as_ref().unwrap();
+        let changes = self.plan.entry("Turbine").or_default();
+        for t in turbines {
+            if existing.contains(&t.case_id) {
+                changes.updated.insert(t.case_id.to_string());
+            } else {
+                changes.inserted.insert(t.case_id.to_string());
+            }
+            self.upserted.insert(t.case_id, t.clone());
+        }
+        Ok(())
+    }
+
+    async fn delete_turbines(&mut self, case_ids: &[i32]) -> Result<(), Error> {
+        for case_id in case_ids {
+            self.changes("Turbine").removed.insert(case_id.to_string());
+            self.deleted.insert(*case_id);
+        }
+        Ok(())
+    }
+
+    async fn create_release(&mut self, release: &NewRelease<'_>) -> Result<i32, Error> {
+        self.changes("Release")
+            .inserted
+            .insert(format!("{} ({})", release.version, release.file_name));
+        Ok(0)
+    }
+
+    async fn record_history(&mut self, _release_id: i32, history: &[HistoryEntry]) -> Result<(), Error> {
+        let changes = self.changes("TurbineHistory");
+        for entry in history {
+            changes.inserted.insert(format!(
+                "{} {}",
+                entry.case_id,
+                entry.attribute.unwrap_or(entry.change_type.code())
+            ));
+        }
+        Ok(())
+    }
+
+    async fn finish_release(&mut self, _release_id: i32, _counts: &ReleaseCounts) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn record_load_run(&mut self, _run: &NewLoadRun<'_>) -> Result<i32, Error> {
+        read_only()
+    }
+
+    async fn get_load_checkpoint(
+        &mut self,
+        checksum: &str,
+    ) -> Result<Option<LoadCheckpoint>, Error> {
+        self.db.get_load_checkpoint(checksum).await
+    }
+
+    async fn save_load_checkpoint(&mut self, _checkpoint: &LoadCheckpoint) -> Result<(), Error> {
+        read_only()
+    }
+
+    async fn delete_load_checkpoint(&mut self, _checksum: &str) -> Result<(), Error> {
+        read_only()
+    }
+}
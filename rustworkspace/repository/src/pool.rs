@@ -0,0 +1,83 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::Repository;
+
+/// A fixed set of open repositories shared between tasks, so that requests
+/// wait for a free connection rather than all queueing behind one.
+pub struct Pool {
+    idle: Mutex<Vec<Box<dyn Repository>>>,
+    available: Semaphore,
+    size: usize,
+}
+
+impl Pool {
+    /// Creates a pool from repositories that are already open.
+    pub fn new(repos: Vec<Box<dyn Repository>>) -> Self {
+        Pool {
+            available: Semaphore::new(repos.len()),
+            size: repos.len(),
+            idle: Mutex::new(repos),
+        }
+    }
+
+    /// The number of repositories in the pool.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Waits for a repository to be free. It returns to the pool when the
+    /// guard is dropped.
+    pub async fn lock(&self) -> PooledRepository<'_> {
+        // The semaphore is never closed, and holds one permit per idle repository.
+        let permit = self
+            .available
+            .acquire()
+            .await
+            .expect("the pool semaphore is never closed");
+        let repo = self
+            .idle
+            .lock()
+            .unwrap()
+            .pop()
+            .expect("a permit guarantees an idle repository");
+
+        PooledRepository {
+            pool: self,
+            repo: Some(repo),
+            _permit: permit,
+        }
+    }
+}
+
+/// A repository taken from a `Pool`.
+pub struct PooledRepository<'a> {
+    pool: &'a Pool,
+    repo: Option<Box<dyn Repository>>,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Deref for PooledRepository<'_> {
+    type Target = Box<dyn Repository>;
+
+    fn deref(&self) -> &Self::Target {
+        self.repo.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledRepository<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.repo.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledRepository<'_> {
+    fn drop(&mut self) {
+        // The repository goes back before the permit is released, so a waiter
+        // woken by the permit always finds it.
+        if let Some(repo) = self.repo.take() {
+            self.pool.idle.lock().unwrap().push(repo);
+        }
+    }
+}
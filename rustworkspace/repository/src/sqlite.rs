@@ -0,0 +1,1214 @@
+use async_trait::async_trait;
+use rusqlite::types::ToSqlOutput;
+use rusqlite::{params, Connection, OptionalExtension, Row, ToSql, NO_PARAMS};
+use std::collections::{HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
+use std::hash::Hash;
+use std::str::FromStr;
+use tiberius::{
+    numeric::Decimal,
+    time::chrono::{NaiveDate, NaiveDateTime},
+};
+
+use crate::dimension::{Dialect, Dimension, Prune, RowState, SyncCounts, Value};
+use crate::error::Error;
+use crate::load::{
+    HistoryEntry, LoadCheckpoint, NewLoadRun, NewRelease, ReleaseCounts, TurbineRow,
+};
+use crate::migrations::{Migration, SQLITE_MIGRATIONS};
+use crate::models::*;
+use crate::Repository;
+
+/// Selects the columns of Turbine in the order expected by `Turbine::try_from`.
+const TURBINE_SELECT: &str = "SELECT Id, CountyId, ProjectId, ModelId, ImageSourceId,
+    Retrofit, RetrofitYear, AttributesConfidenceLevel, LocationConfidenceLevel,
+    ImageDate, Latitude, Longitude, CaseId, FaaOrs, FaaAsn, UsgsPrId, EiaId,
+    HubHeight, TotalHeightToTip, RawManufacturer, RawModel FROM Turbine";
+
+/// Opens (creating if necessary) a database file and switches on foreign key
+/// enforcement, which SQLite leaves off by default.
+/// The special path ":memory:" opens a transient in-memory database.
+pub fn open_connection(path: &str) -> Result<Connection, Error> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    Ok(conn)
+}
+
+fn applied_migrations(conn: &Connection) -> Result<Vec<i32>, Error> {
+    let exists: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'SchemaVersion'",
+        NO_PARAMS,
+        |row| row.get(0),
+    )?;
+    if exists == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut stmt = conn.prepare("SELECT Version FROM SchemaVersion ORDER BY Version")?;
+    let versions = stmt.query_map(NO_PARAMS, |row| row.get(0))?;
+    Ok(versions.collect::<Result<_, _>>()?)
+}
+
+fn apply_migration(conn: &mut Connection, migration: &Migration) -> Result<(), Error> {
+    let tx = conn.transaction()?;
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS SchemaVersion (
+            Version INTEGER NOT NULL PRIMARY KEY,
+            Name TEXT NOT NULL,
+            AppliedAt TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );",
+    )?;
+    tx.execute_batch(migration.sql)?;
+    tx.execute(
+        "INSERT INTO SchemaVersion (Version, Name) VALUES (?1, ?2)",
+        params![migration.version, migration.name],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Represents a connection to a SQLite US Wind Power Stats database.
+pub struct SqliteRepository {
+    conn: Connection,
+}
+
+impl SqliteRepository {
+    /// Opens the database in the given file. The schema is not touched;
+    /// the dataloader brings it up to date when it loads data.
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let conn = open_connection(path)?;
+        Ok(SqliteRepository { conn })
+    }
+
+    /// Runs a query which takes no parameters and converts every row.
+    fn query_all<T>(&self, sql: &str) -> Result<Vec<T>, Error>
+    where
+        T: for<'a, 'b> TryFrom<&'a Row<'b>, Error = Error>,
+    {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_and_then(NO_PARAMS, |row| T::try_from(row))?;
+        rows.collect()
+    }
+
+    /// Runs a query which takes no parameters and collects the first column.
+    fn query_set<T: rusqlite::types::FromSql + Eq + Hash>(
+        &self,
+        sql: &str,
+    ) -> Result<HashSet<T>, Error> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map(NO_PARAMS, |row| row.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Runs a query which takes no parameters and maps the leading columns
+    /// (the key) to the Id in the last column.
+    fn query_ids<K: Eq + Hash>(
+        &self,
+        sql: &str,
+        key: impl Fn(&Row) -> rusqlite::Result<K>,
+    ) -> Result<HashMap<K, i64>, Error> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let id = stmt.column_count() - 1;
+        let rows = stmt.query_map(NO_PARAMS, |row| Ok((key(row)?, row.get(id)?)))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+/// Decimals are bound as text, which a TEXT column such as Project.CapacityMW
+/// keeps exactly and a REAL column converts to a REAL.
+impl ToSql for Value {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            Value::Int(v) => v.to_sql(),
+            Value::Decimal(v) => Ok(ToSqlOutput::Owned(
+                v.map(|d| d.normalize().to_string()).into(),
+            )),
+            Value::Text(v) => v.to_sql(),
+        }
+    }
+}
+
+/// Converts a value held in hundredths or millionths to a REAL.
+fn scaled(value: i64, scale: i32) -> f64 {
+    value as f64 / 10f64.powi(scale)
+}
+
+/// Converts a REAL to a decimal with the same scale as the equivalent
+/// MS SQL column, so both backends return identical values.
+fn to_decimal(value: Option<f64>, scale: usize) -> Option<Decimal> {
+    value.and_then(|v| Decimal::from_str(&format!("{:.*}", scale, v)).ok())
+}
+
+/// Parses a decimal stored as TEXT, giving it the scale of the equivalent
+/// MS SQL column.
+fn text_to_decimal(value: Option<String>, scale: usize) -> Option<Decimal> {
+    value
+        .and_then(|v| Decimal::from_str(&v).ok())
+        .and_then(|d| Decimal::from_str(&format!("{:.*}", scale, d)).ok())
+}
+
+/// Parses a timestamp as written by CURRENT_TIMESTAMP, in UTC as
+/// 'YYYY-MM-DD HH:MM:SS'.
+fn parse_timestamp(text: String) -> Result<NaiveDateTime, Error> {
+    NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S")
+        .map_err(|e| Error::LowLevel(format!("Bad timestamp {}: {}", text, e)))
+}
+
+#[async_trait]
+impl Repository for SqliteRepository {
+    async fn get_all_image_sources(&mut self) -> Result<Vec<ImageSource>, Error> {
+        self.query_all("SELECT Id, Name FROM ImageSource")
+    }
+
+    async fn get_image_source(&mut self, id: u8) -> Result<ImageSource, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT Id, Name FROM ImageSource WHERE Id = ?1")?;
+        let mut rows = stmt.query_and_then(params![id], |row| ImageSource::try_from(row))?;
+        match rows.next() {
+            Some(image_source) => image_source,
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn update_image_source(&mut self, id: u8, name: &str) -> Result<u64, Error> {
+        let stmt = "UPDATE ImageSource SET Name = ?1 WHERE Id = ?2;";
+        let n = self.conn.execute(stmt, params![name, id])?;
+        Ok(n as u64)
+    }
+
+    async fn get_all_states(&mut self) -> Result<Vec<State>, Error> {
+        self.query_all("SELECT Id, Name, Capital, Population, AreaSquareKm, StateType FROM State")
+    }
+
+    async fn get_all_counties(&mut self) -> Result<Vec<County>, Error> {
+        self.query_all("SELECT Id, StateId, Name, Fips FROM County")
+    }
+
+    async fn get_county_by_fips(&mut self, fips: i32) -> Result<County, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT Id, StateId, Name, Fips FROM County WHERE Fips = ?1")?;
+        let mut rows = stmt.query_and_then(params![fips], |row| County::try_from(row))?;
+        match rows.next() {
+            Some(county) => county,
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn get_all_projects(&mut self) -> Result<Vec<Project>, Error> {
+        self.query_all("SELECT Id, Name, NumTurbines, CapacityMW, Year, StateId FROM Project")
+    }
+
+    async fn get_all_manufacturers(&mut self) -> Result<Vec<Manufacturer>, Error> {
+        self.query_all("SELECT Id, Name FROM Manufacturer")
+    }
+
+    async fn get_all_manufacturer_aliases(&mut self) -> Result<Vec<ManufacturerAlias>, Error> {
+        self.query_all("SELECT Id, ManufacturerId, Alias FROM ManufacturerAlias")
+    }
+
+    async fn get_all_models(&mut self) -> Result<Vec<Model>, Error> {
+        self.query_all(
+            "SELECT Id, ManufacturerId, Name, CapacityKW,
+            HubHeight, RotorDiameter, RotorSweptArea, TotalHeightToTip FROM Model",
+        )
+    }
+
+    async fn get_all_model_aliases(&mut self) -> Result<Vec<ModelAlias>, Error> {
+        self.query_all("SELECT Id, ManufacturerId, Alias, ModelName FROM ModelAlias")
+    }
+
+    async fn get_all_turbines(&mut self) -> Result<Vec<Turbine>, Error> {
+        self.query_all(TURBINE_SELECT)
+    }
+
+    async fn get_turbines_by_eia_id(&mut self, eia_id: i32) -> Result<Vec<Turbine>, Error> {
+        let mut stmt = self.conn.prepare(&format!("{} WHERE EiaId = ?1", TURBINE_SELECT))?;
+        let rows = stmt.query_and_then(params![eia_id], |row| Turbine::try_from(row))?;
+        rows.collect()
+    }
+
+    async fn get_turbines_by_faa_asn(&mut self, faa_asn: &str) -> Result<Vec<Turbine>, Error> {
+        let mut stmt = self.conn.prepare(&format!("{} WHERE FaaAsn = ?1", TURBINE_SELECT))?;
+        let rows = stmt.query_and_then(params![faa_asn], |row| Turbine::try_from(row))?;
+        rows.collect()
+    }
+
+    async fn get_all_releases(&mut self) -> Result<Vec<Release>, Error> {
+        self.query_all(
+            "SELECT Id, Version, ReleaseDate, FileName, Checksum, TurbineCount,
+            NewTurbines, ChangedTurbines, DecommissionedTurbines, LoadedAt FROM Release",
+        )
+    }
+
+    async fn get_all_load_runs(&mut self) -> Result<Vec<LoadRun>, Error> {
+        self.query_all(
+            "SELECT Id, StartedAt, FinishedAt, Succeeded, FileName, Version, Checksum,
+            RejectedRows, Warnings, ErrorMessage, Report FROM LoadRun",
+        )
+    }
+
+    async fn get_turbine_history(&mut self, case_id: i32) -> Result<Vec<TurbineChange>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT Id, ReleaseId, CaseId, ChangeType, Attribute, OldValue, NewValue
+            FROM TurbineHistory WHERE CaseId = ?1 ORDER BY Id",
+        )?;
+        let rows = stmt.query_and_then(params![case_id], |row| TurbineChange::try_from(row))?;
+        let history = rows.collect::<Result<Vec<_>, _>>()?;
+
+        if history.is_empty() {
+            Err(Error::NotFound)
+        } else {
+            Ok(history)
+        }
+    }
+
+    fn migrations(&self) -> &'static [Migration] {
+        SQLITE_MIGRATIONS
+    }
+
+    async fn get_applied_migrations(&mut self) -> Result<Vec<i32>, Error> {
+        applied_migrations(&self.conn)
+    }
+
+    async fn apply_migration(&mut self, migration: &Migration) -> Result<(), Error> {
+        apply_migration(&mut self.conn, migration)
+    }
+
+    async fn count_rows(&mut self, table: &str) -> Result<i64, Error> {
+        let exists: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            params![table],
+            |row| row.get(0),
+        )?;
+        if exists == 0 {
+            return Ok(0);
+        }
+
+        let sql = format!("SELECT COUNT(*) FROM {}", table);
+        Ok(self.conn.query_row(&sql, NO_PARAMS, |row| row.get(0))?)
+    }
+
+    // The operations below that make several changes do so in a savepoint,
+    // which nests inside the transaction started by begin().
+
+    async fn begin(&mut self) -> Result<(), Error> {
+        self.conn.execute_batch("BEGIN IMMEDIATE TRANSACTION")?;
+        Ok(())
+    }
+
+    async fn commit(&mut self) -> Result<(), Error> {
+        self.conn.execute_batch("COMMIT TRANSACTION")?;
+        Ok(())
+    }
+
+    async fn rollback(&mut self) -> Result<(), Error> {
+        self.conn.execute_batch("ROLLBACK TRANSACTION")?;
+        Ok(())
+    }
+
+    async fn sync_dimension(
+        &mut self,
+        dimension: &Dimension,
+        rows: &[Vec<Value>],
+    ) -> Result<SyncCounts, Error> {
+        let mut counts = SyncCounts::default();
+        let tx = self.conn.savepoint()?;
+        {
+            let mut update = dimension
+                .update_sql(Dialect::Sqlite)
+                .map(|sql| tx.prepare(&sql))
+                .transpose()?;
+            let mut exists = tx.prepare(&dimension.exists_sql(Dialect::Sqlite))?;
+            let mut insert = tx.prepare(&dimension.insert_sql(Dialect::Sqlite))?;
+
+            // SQLite rejects values for parameters a statement does not have,
+            // so each is given only as many as it uses.
+            for row in rows {
+                if let Some(update) = update.as_mut() {
+                    let used = update.parameter_count();
+                    if update.execute(&row[..used])? > 0 {
+                        counts.updated += 1;
+                        continue;
+                    }
+                }
+
+                let used = exists.parameter_count();
+                let row_count: i64 = exists.query_row(&row[..used], |r| r.get(0))?;
+                if row_count == 0 {
+                    let used = insert.parameter_count();
+                    insert.execute(&row[..used])?;
+                    counts.inserted += 1;
+                }
+            }
+        }
+        tx.commit()?;
+
+        Ok(counts)
+    }
+
+    async fn prune_dimension(
+        &mut self,
+        dimension: &Dimension,
+        loaded: &[Vec<Value>],
+    ) -> Result<usize, Error> {
+        let stmt = dimension.prune_sql(Dialect::Sqlite);
+        let staged = matches!(dimension.prune, Prune::NotLoaded);
+        if staged {
+            self.conn
+                .execute_batch(&dimension.create_loaded_keys_sql(Dialect::Sqlite))?;
+            let mut insert = self
+                .conn
+                .prepare(&dimension.insert_loaded_keys_sql(Dialect::Sqlite, 1))?;
+            for row in loaded {
+                insert.execute(&row[..dimension.key_len()])?;
+            }
+        }
+
+        let removed = self.conn.execute(&stmt, NO_PARAMS)?;
+        if staged {
+            self.conn
+                .execute_batch(&Dialect::Sqlite.drop_loaded_keys_sql())?;
+        }
+
+        Ok(removed)
+    }
+
+    async fn compare_dimension(
+        &mut self,
+        dimension: &Dimension,
+        rows: &[Vec<Value>],
+    ) -> Result<Vec<RowState>, Error> {
+        let mut exists = self.conn.prepare(&dimension.exists_sql(Dialect::Sqlite))?;
+        let mut differs = dimension
+            .differs_sql(Dialect::Sqlite)
+            .map(|sql| self.conn.prepare(&sql))
+            .transpose()?;
+
+        let mut states = Vec::with_capacity(rows.len());
+        for row in rows {
+            let used = exists.parameter_count();
+            let row_count: i64 = exists.query_row(&row[..used], |r| r.get(0))?;
+            if row_count == 0 {
+                states.push(RowState::Missing);
+                continue;
+            }
+
+            let different = match differs.as_mut() {
+                Some(differs) => {
+                    let used = differs.parameter_count();
+                    differs.query_row(&row[..used], |r| r.get::<_, i64>(0))? > 0
+                }
+                None => false,
+            };
+            states.push(if different {
+                RowState::Different
+            } else {
+                RowState::Same
+            });
+        }
+
+        Ok(states)
+    }
+
+    async fn get_dimension_rows(
+        &mut self,
+        dimension: &Dimension,
+    ) -> Result<Vec<Vec<Option<String>>>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare(&dimension.select_text_sql(Dialect::Sqlite))?;
+        let columns = stmt.column_count();
+        let rows = stmt.query_map(NO_PARAMS, |row| {
+            (0..columns)
+                .map(|idx| Ok(row.get::<_, Option<String>>(idx)?.map(|v| v.trim().to_string())))
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    async fn get_state_ids(&mut self) -> Result<HashSet<String>, Error> {
+        let ids: HashSet<String> = self.query_set("SELECT Id FROM State")?;
+        Ok(ids.iter().map(|id| id.trim().to_string()).collect())
+    }
+
+    async fn get_manufacturer_names(&mut self) -> Result<HashSet<String>, Error> {
+        self.query_set("SELECT Name FROM Manufacturer")
+    }
+
+    async fn get_model_names(&mut self) -> Result<HashSet<(String, String)>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT MF.Name, M.Name FROM Model M
+            INNER JOIN Manufacturer MF ON MF.Id = M.ManufacturerId",
+        )?;
+        let rows = stmt.query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    async fn get_case_ids(&mut self) -> Result<HashSet<i32>, Error> {
+        self.query_set("SELECT CaseId FROM Turbine")
+    }
+
+    async fn get_turbine_rows(
+        &mut self,
+        case_ids: &[i32],
+    ) -> Result<HashMap<i32, TurbineRow>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT T.CaseId, C.Fips, P.Name, P.StateId, P.Year, MF.Name, M.Name,
+                T.RawManufacturer, T.RawModel, I.Name, T.Retrofit, T.RetrofitYear,
+                T.AttributesConfidenceLevel, T.LocationConfidenceLevel, T.ImageDate,
+                CAST(ROUND(T.Latitude * 1000000) AS INTEGER), CAST(ROUND(T.Longitude * 1000000) AS INTEGER),
+                T.FaaOrs, T.FaaAsn, T.UsgsPrId, T.EiaId,
+                CAST(ROUND(T.HubHeight * 100) AS INTEGER), CAST(ROUND(T.TotalHeightToTip * 100) AS INTEGER)
+            FROM Turbine T
+            INNER JOIN County C ON C.Id = T.CountyId
+            INNER JOIN Project P ON P.Id = T.ProjectId
+            INNER JOIN Model M ON M.Id = T.ModelId
+            INNER JOIN Manufacturer MF ON MF.Id = M.ManufacturerId
+            INNER JOIN ImageSource I ON I.Id = T.ImageSourceId
+            WHERE T.CaseId = ?1",
+        )?;
+
+        let mut turbines = HashMap::with_capacity(case_ids.len());
+        for case_id in case_ids {
+            let turbine = stmt
+                .query_row(params![case_id], |row| {
+                    Ok(TurbineRow {
+                        case_id: row.get(0)?,
+                        county_fips: row.get::<_, Option<i32>>(1)?.unwrap_or_default(),
+                        project: row.get(2)?,
+                        project_state: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                        project_year: row.get(4)?,
+                        manufacturer: row.get(5)?,
+                        model: row.get(6)?,
+                        raw_manufacturer: row.get(7)?,
+                        raw_model: row.get(8)?,
+                        image_source: row.get(9)?,
+                        retrofit: row.get(10)?,
+                        retrofit_year: row.get(11)?,
+                        attributes_confidence_level: row.get(12)?,
+                        location_confidence_level: row.get(13)?,
+                        image_date: row.get(14)?,
+                        latitude: row.get(15)?,
+                        longitude: row.get(16)?,
+                        faa_ors: row.get(17)?,
+                        faa_asn: row.get(18)?,
+                        usgs_pr_id: row.get(19)?,
+                        eia_id: row.get(20)?,
+                        hub_height: row.get(21)?,
+                        total_height_to_tip: row.get(22)?,
+                    })
+                })
+                .optional()?;
+
+            if let Some(turbine) = turbine {
+                turbines.insert(*case_id, turbine);
+            }
+        }
+
+        Ok(turbines)
+    }
+
+    async fn upsert_turbines(&mut self, turbines: &[TurbineRow]) -> Result<(), Error> {
+        let county_ids = self.query_ids(
+            "SELECT Fips, Id FROM County WHERE Fips IS NOT NULL",
+            |row| row.get::<_, i32>(0),
+        )?;
+        let project_ids = self.query_ids(
+            "SELECT Name, StateId, Year, Id FROM Project WHERE StateId IS NOT NULL",
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<i32>>(2)?,
+                ))
+            },
+        )?;
+        let model_ids = self.query_ids(
+            "SELECT MF.Name, M.Name, M.Id FROM Model M JOIN Manufacturer MF ON MF.Id = M.ManufacturerId",
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )?;
+        let image_source_ids = self.query_ids("SELECT Name, Id FROM ImageSource", |row| {
+            row.get::<_, String>(0)
+        })?;
+
+        let tx = self.conn.savepoint()?;
+        {
+            // An upsert, so that new and changed turbines are written the same way.
+            let mut stmt = tx.prepare(
+                "INSERT INTO Turbine (CaseId, CountyId, ProjectId, ModelId, ImageSourceId,
+                    Retrofit, RetrofitYear, AttributesConfidenceLevel, LocationConfidenceLevel,
+                    ImageDate, Latitude, Longitude, FaaOrs, FaaAsn, UsgsPrId, EiaId, HubHeight, TotalHeightToTip,
+                    RawManufacturer, RawModel)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)
+                ON CONFLICT (CaseId) DO UPDATE SET CountyId = excluded.CountyId, ProjectId = excluded.ProjectId,
+                    ModelId = excluded.ModelId, ImageSourceId = excluded.ImageSourceId, Retrofit = excluded.Retrofit,
+                    RetrofitYear = excluded.RetrofitYear, AttributesConfidenceLevel = excluded.AttributesConfidenceLevel,
+                    LocationConfidenceLevel = excluded.LocationConfidenceLevel, ImageDate = excluded.ImageDate,
+                    Latitude = excluded.Latitude, Longitude = excluded.Longitude, FaaOrs = excluded.FaaOrs,
+                    FaaAsn = excluded.FaaAsn, UsgsPrId = excluded.UsgsPrId, EiaId = excluded.EiaId,
+                    HubHeight = excluded.HubHeight, TotalHeightToTip = excluded.TotalHeightToTip,
+                    RawManufacturer = excluded.RawManufacturer, RawModel = excluded.RawModel",
+            )?;
+
+            for t in turbines {
+                let unknown = |what: String| {
+                    Error::LowLevel(format!("Turbine {}: unknown {}", t.case_id, what))
+                };
+                let county_id = county_ids
+                    .get(&t.county_fips)
+                    .ok_or_else(|| unknown(format!("county FIPS {:05}", t.county_fips)))?;
+                let project_id = project_ids
+                    .get(&(t.project.clone(), t.project_state.clone(), t.project_year))
+                    .ok_or_else(|| {
+                        unknown(format!(
+                            "project '{}' in state '{}'",
+                            t.project, t.project_state
+                        ))
+                    })?;
+                let model_id = model_ids
+                    .get(&(t.manufacturer.clone(), t.model.clone()))
+                    .ok_or_else(|| {
+                        unknown(format!(
+                            "model '{}' from manufacturer '{}'",
+                            t.model, t.manufacturer
+                        ))
+                    })?;
+                let image_source_id = image_source_ids
+                    .get(&t.image_source)
+                    .ok_or_else(|| unknown(format!("image source '{}'", t.image_source)))?;
+
+                stmt.execute(params![
+                    t.case_id,
+                    county_id,
+                    project_id,
+                    model_id,
+                    image_source_id,
+                    t.retrofit,
+                    t.retrofit_year,
+                    t.attributes_confidence_level,
+                    t.location_confidence_level,
+                    t.image_date,
+                    scaled(t.latitude, 6),
+                    scaled(t.longitude, 6),
+                    t.faa_ors,
+                    t.faa_asn,
+                    t.usgs_pr_id,
+                    t.eia_id,
+                    t.hub_height.map(|h| scaled(h, 2)),
+                    t.total_height_to_tip.map(|h| scaled(h, 2)),
+                    t.raw_manufacturer,
+                    t.raw_model,
+                ])?;
+            }
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    async fn delete_turbines(&mut self, case_ids: &[i32]) -> Result<(), Error> {
+        let tx = self.conn.savepoint()?;
+        {
+            let mut stmt = tx.prepare("DELETE FROM Turbine WHERE CaseId = ?1")?;
+            for case_id in case_ids {
+                stmt.execute(params![case_id])?;
+            }
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    async fn create_release(&mut self, release: &NewRelease<'_>) -> Result<i32, Error> {
+        self.conn.execute(
+            "INSERT INTO Release (Version, ReleaseDate, FileName, Checksum, TurbineCount,
+                NewTurbines, ChangedTurbines, DecommissionedTurbines)
+            VALUES (?1, ?2, ?3, ?4, 0, 0, 0, 0)",
+            params![
+                release.version,
+                release.release_date,
+                release.file_name,
+                release.checksum
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid() as i32)
+    }
+
+    async fn record_history(
+        &mut self,
+        release_id: i32,
+        history: &[HistoryEntry],
+    ) -> Result<(), Error> {
+        let tx = self.conn.savepoint()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO TurbineHistory (ReleaseId, CaseId, ChangeType, Attribute, OldValue, NewValue)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+
+            for h in history {
+                stmt.execute(params![
+                    release_id,
+                    h.case_id,
+                    h.change_type.code(),
+                    h.attribute,
+                    h.old_value,
+                    h.new_value
+                ])?;
+            }
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    async fn finish_release(
+        &mut self,
+        release_id: i32,
+        counts: &ReleaseCounts,
+    ) -> Result<(), Error> {
+        self.conn.execute(
+            "UPDATE Release SET TurbineCount = ?1, NewTurbines = ?2, ChangedTurbines = ?3,
+                DecommissionedTurbines = ?4 WHERE Id = ?5",
+            params![
+                counts.turbines as i64,
+                counts.new as i64,
+                counts.changed as i64,
+                counts.decommissioned as i64,
+                release_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn record_load_run(&mut self, run: &NewLoadRun<'_>) -> Result<i32, Error> {
+        self.conn.execute(
+            "INSERT INTO LoadRun (StartedAt, FinishedAt, Succeeded, FileName, Version, Checksum,
+                RejectedRows, Warnings, ErrorMessage, Report)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                run.started_at,
+                run.finished_at,
+                run.succeeded,
+                run.file_name,
+                run.version,
+                run.checksum,
+                run.rejected_rows as i64,
+                run.warnings as i64,
+                run.error_message,
+                run.report
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid() as i32)
+    }
+
+    async fn get_load_checkpoint(
+        &mut self,
+        checksum: &str,
+    ) -> Result<Option<LoadCheckpoint>, Error> {
+        let checkpoint = self
+            .conn
+            .query_row(
+                "SELECT ReleaseId, Turbines, NewTurbines, ChangedTurbines, UnchangedTurbines
+                FROM LoadCheckpoint WHERE Checksum = ?1",
+                params![checksum],
+                |row| {
+                    Ok(LoadCheckpoint {
+                        checksum: checksum.to_string(),
+                        release_id: row.get(0)?,
+                        // A negative count fails to convert rather than wrapping.
+                        turbines: row.get::<_, u32>(1)? as usize,
+                        new: row.get::<_, u32>(2)? as usize,
+                        changed: row.get::<_, u32>(3)? as usize,
+                        unchanged: row.get::<_, u32>(4)? as usize,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(checkpoint)
+    }
+
+    async fn save_load_checkpoint(&mut self, checkpoint: &LoadCheckpoint) -> Result<(), Error> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO LoadCheckpoint (Checksum, ReleaseId, Turbines, NewTurbines,
+                ChangedTurbines, UnchangedTurbines)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                checkpoint.checksum,
+                checkpoint.release_id,
+                checkpoint.turbines as i64,
+                checkpoint.new as i64,
+                checkpoint.changed as i64,
+                checkpoint.unchanged as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn delete_load_checkpoint(&mut self, checksum: &str) -> Result<(), Error> {
+        self.conn.execute(
+            "DELETE FROM LoadCheckpoint WHERE Checksum = ?1",
+            params![checksum],
+        )?;
+        Ok(())
+    }
+}
+
+impl TryFrom<&Row<'_>> for ImageSource {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        Ok(ImageSource {
+            id: row.get(0)?,
+            name: row.get(1)?,
+        })
+    }
+}
+
+impl TryFrom<&Row<'_>> for State {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        let state_type: Option<String> = row.get(5)?;
+
+        Ok(State {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            capital: row.get(2)?,
+            population: row.get(3)?,
+            area_square_km: row.get(4)?,
+            state_type: state_type.as_deref().try_into()?,
+        })
+    }
+}
+
+impl TryFrom<&Row<'_>> for County {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        Ok(County {
+            id: row.get(0)?,
+            state_id: row.get(1)?,
+            name: row.get(2)?,
+            fips: row.get(3)?,
+        })
+    }
+}
+
+impl TryFrom<&Row<'_>> for Manufacturer {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        Ok(Manufacturer {
+            id: row.get(0)?,
+            name: row.get(1)?,
+        })
+    }
+}
+
+impl TryFrom<&Row<'_>> for ManufacturerAlias {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        Ok(ManufacturerAlias {
+            id: row.get(0)?,
+            manufacturer_id: row.get(1)?,
+            alias: row.get(2)?,
+        })
+    }
+}
+
+impl TryFrom<&Row<'_>> for ModelAlias {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        Ok(ModelAlias {
+            id: row.get(0)?,
+            manufacturer_id: row.get(1)?,
+            alias: row.get(2)?,
+            model_name: row.get(3)?,
+        })
+    }
+}
+
+impl TryFrom<&Row<'_>> for Project {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        Ok(Project {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            num_turbines: row.get(2)?,
+            capacity_mw: text_to_decimal(row.get(3)?, 3),
+            year: row.get(4)?,
+            state_id: row.get(5)?,
+        })
+    }
+}
+
+impl TryFrom<&Row<'_>> for Model {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        Ok(Model {
+            id: row.get(0)?,
+            manufacturer_id: row.get(1)?,
+            name: row.get(2)?,
+            capacity_kw: row.get(3)?,
+            hub_height: to_decimal(row.get(4)?, 2),
+            rotor_diameter: to_decimal(row.get(5)?, 2),
+            rotor_swept_area: to_decimal(row.get(6)?, 2),
+            total_height_to_tip: to_decimal(row.get(7)?, 2),
+        })
+    }
+}
+
+impl TryFrom<&Row<'_>> for Turbine {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        let image_date: Option<String> = row.get(9)?;
+        let image_date = match image_date {
+            Some(d) => Some(
+                NaiveDate::parse_from_str(&d, "%Y-%m-%d")
+                    .map_err(|e| Error::LowLevel(format!("Bad ImageDate {}: {}", d, e)))?,
+            ),
+            None => None,
+        };
+
+        Ok(Turbine {
+            id: row.get(0)?,
+            case_id: row.get(12)?,
+            county_id: row.get(1)?,
+            project_id: row.get(2)?,
+            model_id: row.get(3)?,
+            image_source_id: row.get(4)?,
+            retrofit: row.get(5)?,
+            retrofit_year: row.get(6)?,
+            attributes_confidence_level: ConfidenceLevel::try_from(row.get::<_, Option<u8>>(7)?)?,
+            location_confidence_level: ConfidenceLevel::try_from(row.get::<_, Option<u8>>(8)?)?,
+            image_date,
+            latitude: to_decimal(row.get(10)?, 6).unwrap_or_default(),
+            longitude: to_decimal(row.get(11)?, 6).unwrap_or_default(),
+            faa_ors: row.get(13)?,
+            faa_asn: row.get(14)?,
+            usgs_pr_id: row.get(15)?,
+            eia_id: row.get(16)?,
+            hub_height: to_decimal(row.get(17)?, 2),
+            total_height_to_tip: to_decimal(row.get(18)?, 2),
+            raw_manufacturer: row.get(19)?,
+            raw_model: row.get(20)?,
+        })
+    }
+}
+
+impl TryFrom<&Row<'_>> for Release {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        let release_date: Option<String> = row.get(2)?;
+        let release_date = match release_date {
+            Some(d) => Some(
+                NaiveDate::parse_from_str(&d, "%Y-%m-%d")
+                    .map_err(|e| Error::LowLevel(format!("Bad ReleaseDate {}: {}", d, e)))?,
+            ),
+            None => None,
+        };
+
+        let loaded_at = parse_timestamp(row.get(9)?)?;
+
+        Ok(Release {
+            id: row.get(0)?,
+            version: row.get(1)?,
+            release_date,
+            file_name: row.get(3)?,
+            checksum: row.get(4)?,
+            turbine_count: row.get(5)?,
+            new_turbines: row.get(6)?,
+            changed_turbines: row.get(7)?,
+            decommissioned_turbines: row.get(8)?,
+            loaded_at,
+        })
+    }
+}
+
+impl TryFrom<&Row<'_>> for LoadRun {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        Ok(LoadRun {
+            id: row.get(0)?,
+            started_at: parse_timestamp(row.get(1)?)?,
+            finished_at: parse_timestamp(row.get(2)?)?,
+            succeeded: row.get(3)?,
+            file_name: row.get(4)?,
+            version: row.get(5)?,
+            checksum: row.get(6)?,
+            rejected_rows: row.get(7)?,
+            warnings: row.get(8)?,
+            error_message: row.get(9)?,
+            report: row.get(10)?,
+        })
+    }
+}
+
+impl TryFrom<&Row<'_>> for TurbineChange {
+    type Error = Error;
+
+    fn try_from(row: &Row) -> Result<Self, Self::Error> {
+        let change_type: Option<String> = row.get(3)?;
+
+        Ok(TurbineChange {
+            id: row.get(0)?,
+            release_id: row.get(1)?,
+            case_id: row.get(2)?,
+            change_type: change_type.as_deref().try_into()?,
+            attribute: row.get(4)?,
+            old_value: row.get(5)?,
+            new_value: row.get(6)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dimension::{
+        COUNTY, IMAGE_SOURCE, MANUFACTURER, MODEL, MODEL_ALIAS, PROJECT, STATE,
+    };
+    use crate::testing::TempDb;
+
+    /// A database file with the whole schema.
+    async fn migrated(name: &str) -> (TempDb, SqliteRepository) {
+        let db = TempDb::new(&format!("sqlite-{}", name));
+        let mut repo = SqliteRepository::open(db.path()).unwrap();
+        crate::migrations::migrate(&mut repo, false, false)
+            .await
+            .unwrap();
+        (db, repo)
+    }
+
+    fn state(id: &str, name: &str) -> Vec<Value> {
+        vec![
+            Value::text(id),
+            Value::text(name),
+            Value::Text(None),
+            Value::Int(None),
+            Value::Int(None),
+            Value::text("S"),
+        ]
+    }
+
+    fn county(fips: Option<i32>, name: &str) -> Vec<Value> {
+        vec![Value::Int(fips), Value::text("IA"), Value::text(name)]
+    }
+
+    /// The dimension rows of an Iowa turbine, as the dataloader writes them.
+    async fn iowa_dimensions(repo: &mut SqliteRepository) {
+        repo.sync_dimension(&STATE, &[state("IA", "Iowa")])
+            .await
+            .unwrap();
+        repo.sync_dimension(&COUNTY, &[county(Some(19069), "Franklin County")])
+            .await
+            .unwrap();
+        repo.sync_dimension(&MANUFACTURER, &[vec![Value::text("Vestas")]])
+            .await
+            .unwrap();
+        let model = vec![
+            Value::text("Vestas"),
+            Value::text("V90"),
+            Value::Int(Some(1500)),
+            Value::Decimal(None),
+            Value::Decimal(Some(Decimal::new(90, 0))),
+            Value::Decimal(None),
+            Value::Decimal(None),
+        ];
+        repo.sync_dimension(&MODEL, &[model]).await.unwrap();
+        repo.sync_dimension(&IMAGE_SOURCE, &[vec![Value::text("NAIP")]])
+            .await
+            .unwrap();
+        let project = vec![
+            Value::text("Crystal Lake"),
+            Value::text("IA"),
+            Value::Int(Some(2008)),
+            Value::Int(Some(1)),
+            Value::Decimal(Some(Decimal::new(15, 1))),
+        ];
+        repo.sync_dimension(&PROJECT, &[project]).await.unwrap();
+    }
+
+    fn iowa_turbine(case_id: i32) -> TurbineRow {
+        TurbineRow {
+            case_id,
+            county_fips: 19069,
+            project: "Crystal Lake".to_string(),
+            project_state: "IA".to_string(),
+            project_year: Some(2008),
+            manufacturer: "Vestas".to_string(),
+            model: "V90".to_string(),
+            raw_manufacturer: Some("VESTAS".to_string()),
+            raw_model: Some("V90".to_string()),
+            image_source: "NAIP".to_string(),
+            retrofit: false,
+            retrofit_year: None,
+            attributes_confidence_level: 3,
+            location_confidence_level: 3,
+            image_date: Some("2019-06-01".to_string()),
+            latitude: 42_700_000,
+            longitude: -93_200_000,
+            faa_ors: None,
+            faa_asn: Some("2013-WTE-2956-OE".to_string()),
+            usgs_pr_id: None,
+            eia_id: Some(56291),
+            hub_height: Some(8000),
+            total_height_to_tip: Some(12500),
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_counts_inserted_and_updated_rows() {
+        let (_db, mut repo) = migrated("sync").await;
+        let rows = vec![state("IA", "Iowa"), state("CA", "California")];
+
+        let counts = repo.sync_dimension(&STATE, &rows).await.unwrap();
+        assert_eq!((counts.inserted, counts.updated), (2, 0));
+        let counts = repo.sync_dimension(&STATE, &rows).await.unwrap();
+        assert_eq!((counts.inserted, counts.updated), (0, 0));
+
+        let changed = vec![state("IA", "State of Iowa")];
+        assert_eq!(
+            repo.compare_dimension(
+                &STATE,
+                &[changed[0].clone(), rows[1].clone(), state("TX", "Texas")]
+            )
+            .await
+            .unwrap(),
+            vec![RowState::Different, RowState::Same, RowState::Missing]
+        );
+        let counts = repo.sync_dimension(&STATE, &changed).await.unwrap();
+        assert_eq!((counts.inserted, counts.updated), (0, 1));
+
+        let mut names = repo
+            .get_dimension_rows(&STATE)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| row[1].clone().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["California", "State of Iowa"]);
+    }
+
+    #[tokio::test]
+    async fn sync_gives_legacy_rows_their_key() {
+        let (_db, mut repo) = migrated("legacy").await;
+        repo.sync_dimension(&STATE, &[state("IA", "Iowa")])
+            .await
+            .unwrap();
+        repo.sync_dimension(&COUNTY, &[county(None, "Franklin County")])
+            .await
+            .unwrap();
+
+        let counts = repo
+            .sync_dimension(&COUNTY, &[county(Some(19069), "Franklin County")])
+            .await
+            .unwrap();
+        assert_eq!((counts.inserted, counts.updated), (0, 1));
+        let counties = repo.get_all_counties().await.unwrap();
+        assert_eq!(counties.len(), 1);
+        assert_eq!(counties[0].fips, Some(19069));
+    }
+
+    #[tokio::test]
+    async fn prunes_rows_not_loaded() {
+        let (_db, mut repo) = migrated("prune-loaded").await;
+        let rows = vec![state("IA", "Iowa"), state("CA", "California")];
+        repo.sync_dimension(&STATE, &rows).await.unwrap();
+
+        assert_eq!(repo.prune_dimension(&STATE, &rows[..1]).await.unwrap(), 1);
+        assert_eq!(
+            repo.get_state_ids()
+                .await
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["IA"]
+        );
+        // Nothing loaded leaves nothing.
+        assert_eq!(repo.prune_dimension(&STATE, &[]).await.unwrap(), 1);
+        assert!(repo.get_state_ids().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn prunes_with_more_loaded_keys_than_a_statement_takes() {
+        let (_db, mut repo) = migrated("prune-many").await;
+        repo.sync_dimension(&MANUFACTURER, &[vec![Value::text("Vestas")]])
+            .await
+            .unwrap();
+        let aliases = (0..2500)
+            .map(|i| {
+                vec![
+                    Value::text("Vestas"),
+                    Value::Text(Some(format!("V{}", i))),
+                    Value::text("V90"),
+                ]
+            })
+            .collect::<Vec<_>>();
+        repo.sync_dimension(&MODEL_ALIAS, &aliases).await.unwrap();
+
+        // Two key columns a row, far beyond one statement's parameters.
+        assert_eq!(
+            repo.prune_dimension(&MODEL_ALIAS, &aliases[..2200])
+                .await
+                .unwrap(),
+            300
+        );
+        assert_eq!(repo.get_all_model_aliases().await.unwrap().len(), 2200);
+    }
+
+    #[tokio::test]
+    async fn writes_reads_and_deletes_turbines() {
+        let (_db, mut repo) = migrated("turbines").await;
+        iowa_dimensions(&mut repo).await;
+
+        repo.upsert_turbines(&[iowa_turbine(1), iowa_turbine(2)])
+            .await
+            .unwrap();
+        let mut changed = iowa_turbine(2);
+        changed.hub_height = Some(9500);
+        repo.upsert_turbines(&[changed]).await.unwrap();
+
+        let rows = repo.get_turbine_rows(&[1, 2]).await.unwrap();
+        assert_eq!(rows[&1].hub_height, Some(8000));
+        assert_eq!(rows[&2].hub_height, Some(9500));
+        assert_eq!(rows[&1].latitude, 42_700_000);
+        assert_eq!(rows[&1].raw_manufacturer.as_deref(), Some("VESTAS"));
+        assert_eq!(repo.get_case_ids().await.unwrap().len(), 2);
+
+        repo.delete_turbines(&[1, 2]).await.unwrap();
+        assert!(repo.get_case_ids().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn prunes_rows_no_turbine_refers_to() {
+        let (_db, mut repo) = migrated("prune-unreferenced").await;
+        iowa_dimensions(&mut repo).await;
+        repo.upsert_turbines(&[iowa_turbine(1)]).await.unwrap();
+
+        assert_eq!(repo.prune_dimension(&PROJECT, &[]).await.unwrap(), 0);
+
+        repo.delete_turbines(&[1]).await.unwrap();
+        for dimension in crate::dimension::PRUNED_BY_TURBINES {
+            assert_eq!(
+                repo.prune_dimension(dimension, &[]).await.unwrap(),
+                1,
+                "{}",
+                dimension.table
+            );
+        }
+        assert!(repo.get_all_manufacturers().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,23 @@
+//! Helpers for the unit tests.
+
+/// A database file for one test, removed when dropped.
+pub struct TempDb(String);
+
+impl TempDb {
+    /// A path for a new database, named for the test and this process.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("uswps-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        TempDb(path.display().to_string())
+    }
+
+    pub fn path(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
@@ -1,5 +1,7 @@
-use repository::Repository;
-use rocket::{Build, Request, Response, State, fairing::{Fairing, Info, Kind}, futures::lock::Mutex, get, http::Header, put, response::Responder, routes, serde::json::Json};
+use repository::Pool;
+use rocket::{Build, Request, Response, State, fairing::{Fairing, Info, Kind}, get, http::Header, put, response::Responder, routes, serde::json::Json};
+use settings::{ConfigArgs, ServerSettings, Settings};
+use structopt::StructOpt;
 
 mod results;
 use results::*;
@@ -14,28 +16,65 @@ pub enum Error {
     NotFound(()),
     #[response(status = 500)]
     ServerError(String),
+    #[response(status = 500)]
+    Config(String),
 }
 
 impl From<repository::error::Error> for Error {
     fn from(err: repository::error::Error) -> Self {
         match err {
             repository::error::Error::LowLevel(msg) => Error::LowLevel(msg),
+            repository::error::Error::Transient(msg) => Error::LowLevel(msg),
             repository::error::Error::NotFound => Error::NotFound(()),
             repository::error::Error::UnknownStateType(msg) => Error::ServerError(msg),
             repository::error::Error::UnknownConfidenceLevel(msg) => Error::ServerError(msg),
+            repository::error::Error::UnknownChangeType(msg) => Error::ServerError(msg),
         }
     }
 }
 
+impl From<settings::Error> for Error {
+    fn from(err: settings::Error) -> Self {
+        Error::Config(format!("{}", err))
+    }
+}
+
 impl From<rocket::Error> for Error {
     fn from(err: rocket::Error) -> Self {
         Error::Rocket(format!("{}", err))
     }
 }
 
-type SafeRepo = Mutex<Repository>;
+type SafeRepo = Pool;
+
+#[derive(StructOpt, Debug)]
+struct Opt {
+    /// The address to listen on, overriding server.address.
+    #[structopt(long)]
+    address: Option<String>,
+    /// The port to listen on, overriding server.port.
+    #[structopt(long)]
+    port: Option<u16>,
+    #[structopt(flatten)]
+    config_args: ConfigArgs,
+}
 
-pub struct CORS;
+/// Adds the CORS headers, allowing the configured origins.
+pub struct CORS {
+    origins: Vec<String>,
+}
+
+impl CORS {
+    /// The value of Access-Control-Allow-Origin for a request from `origin`,
+    /// if it is allowed.
+    fn allowed_origin<'a>(&'a self, origin: Option<&'a str>) -> Option<&'a str> {
+        if self.origins.iter().any(|o| o == "*") {
+            Some("*")
+        } else {
+            origin.filter(|origin| self.origins.iter().any(|o| o == origin))
+        }
+    }
+}
 
 #[rocket::async_trait]
 impl Fairing for CORS {
@@ -50,7 +89,12 @@ impl Fairing for CORS {
         let request_str = request.to_string();
         println!("Setting access control allow origin for {}", request_str);
 
-        response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+        if let Some(origin) = self.allowed_origin(request.headers().get_one("Origin")) {
+            response.set_header(Header::new("Access-Control-Allow-Origin", origin.to_owned()));
+            if origin != "*" {
+                response.set_header(Header::new("Vary", "Origin"));
+            }
+        }
         response.set_header(Header::new(
             "Access-Control-Allow-Methods",
             "POST, GET, PATCH, OPTIONS",
@@ -62,12 +106,31 @@ impl Fairing for CORS {
 
 #[rocket::main]
 async fn main() -> Result<(), crate::Error> {
-    Ok(rocket().await?.launch().await?)
+    let opt = Opt::from_args();
+    let mut settings = Settings::load(&opt.config_args)?;
+    if let Some(address) = opt.address {
+        settings.server.address = address;
+    }
+    if let Some(port) = opt.port {
+        settings.server.port = port;
+    }
+    if opt.config_args.print_config {
+        print!("{}", settings.to_redacted_toml()?);
+        return Ok(());
+    }
+
+    let pool = repository::open_pool(&settings.connection_string(), settings.database.pool_size).await?;
+    Ok(rocket(pool, &settings.server).launch().await?)
 }
 
-async fn rocket() -> Result<rocket::Rocket<Build>, crate::Error> {
-    let repo = Repository::open(None).await?;
-    let state = Mutex::new(repo);
+/// Builds the server around the given pool of repositories. Tests can pass a
+/// pool of one `InMemoryRepository` so that they do not need a database.
+/// The address and port override any given in Rocket.toml or ROCKET_ variables.
+fn rocket(pool: Pool, server: &ServerSettings) -> rocket::Rocket<Build> {
+    let state: SafeRepo = pool;
+    let figment = rocket::Config::figment()
+        .merge(("address", &server.address))
+        .merge(("port", server.port));
 
     let routes = routes![
         index,
@@ -76,16 +139,21 @@ async fn rocket() -> Result<rocket::Rocket<Build>, crate::Error> {
         update_image_source,
         get_states,
         get_counties,
+        get_county_by_fips,
         get_projects,
         get_manufacturers,
         get_models,
         get_turbines,
+        get_turbines_by_faa_asn,
+        get_turbine_history,
+        get_releases,
+        get_load_runs,
     ];
 
-    Ok(rocket::build()
-        .attach(CORS)
+    rocket::custom(figment)
+        .attach(CORS { origins: server.cors_origins.clone() })
         .mount("/", routes)
-        .manage(state))
+        .manage(state)
 }
 
 #[get("/")]
@@ -98,7 +166,7 @@ async fn index() -> &'static str {
 async fn get_image_sources(repo: &State<SafeRepo>) -> Result<Json<Vec<ImageSource>>, crate::Error> {
     let mut repo = repo.lock().await;
     let mut image_sources = repo.get_all_image_sources().await?;
-    image_sources.sort_by(|a, b| a.id.cmp(&b.id));
+    image_sources.sort_by_key(|s| s.id);
     let image_sources = image_sources.into_iter().map(|i| i.into()).collect();
     Ok(Json(image_sources))
 }
@@ -125,7 +193,7 @@ async fn update_image_source(
     match repo.update_image_source(id, &name).await? {
         0 => Err(Error::NotFound(())),
         1 => Ok(()),
-        n @ _ => Err(Error::ServerError(format!("Unexpected row count {}", n))),
+        n => Err(Error::ServerError(format!("Unexpected row count {}", n))),
     }
 }
 
@@ -144,11 +212,22 @@ async fn get_states(repo: &State<SafeRepo>) -> Result<Json<Vec<results::State>>,
 async fn get_counties(repo: &State<SafeRepo>) -> Result<Json<Vec<County>>, crate::Error> {
     let mut repo = repo.lock().await;
     let mut counties = repo.get_all_counties().await?;
-    counties.sort_by(|a, b| a.id.cmp(&b.id));
+    counties.sort_by_key(|c| c.id);
     let counties = counties.into_iter().map(|i| i.into()).collect();
     Ok(Json(counties))
 }
 
+/// curl -w "\n" -i -X GET http://localhost:8000/api/counties/19069
+#[get("/api/counties/<fips>")]
+async fn get_county_by_fips(
+    repo: &State<SafeRepo>,
+    fips: i32,
+) -> Result<Json<County>, crate::Error> {
+    let mut repo = repo.lock().await;
+    let county = repo.get_county_by_fips(fips).await?;
+    Ok(Json(county.into()))
+}
+
 /// curl -w "\n" -i -X GET http://localhost:8000/api/projects
 #[get("/api/projects")]
 async fn get_projects(repo: &State<SafeRepo>) -> Result<Json<Vec<Project>>, crate::Error> {
@@ -167,7 +246,21 @@ async fn get_manufacturers(
     let mut repo = repo.lock().await;
     let mut manufacturers = repo.get_all_manufacturers().await?;
     manufacturers.sort_by(|a, b| a.name.cmp(&b.name));
-    let manufacturers = manufacturers.into_iter().map(|i| i.into()).collect();
+    let mut aliases = repo.get_all_manufacturer_aliases().await?;
+    aliases.sort_by(|a, b| a.alias.cmp(&b.alias));
+
+    let manufacturers = manufacturers
+        .into_iter()
+        .map(|m| {
+            let mut manufacturer = Manufacturer::from(m);
+            manufacturer.aliases = aliases
+                .iter()
+                .filter(|a| a.manufacturer_id == manufacturer.id)
+                .map(|a| a.alias.clone())
+                .collect();
+            manufacturer
+        })
+        .collect();
     Ok(Json(manufacturers))
 }
 
@@ -177,16 +270,320 @@ async fn get_models(repo: &State<SafeRepo>) -> Result<Json<Vec<Model>>, crate::E
     let mut repo = repo.lock().await;
     let mut models = repo.get_all_models().await?;
     models.sort_by(|a, b| a.name.cmp(&b.name));
-    let models = models.into_iter().map(|i| i.into()).collect();
+    let mut aliases = repo.get_all_model_aliases().await?;
+    aliases.sort_by(|a, b| a.alias.cmp(&b.alias));
+
+    let models = models
+        .into_iter()
+        .map(|m| {
+            let mut model = Model::from(m);
+            model.aliases = aliases
+                .iter()
+                .filter(|a| {
+                    a.manufacturer_id == model.manufacturer_id && a.model_name == model.name
+                })
+                .map(|a| a.alias.clone())
+                .collect();
+            model
+        })
+        .collect();
     Ok(Json(models))
 }
 
 /// curl -w "\n" -i -X GET http://localhost:8000/api/turbines
-#[get("/api/turbines")]
-async fn get_turbines(repo: &State<SafeRepo>) -> Result<Json<Vec<Turbine>>, crate::Error> {
+/// curl -w "\n" -i -X GET http://localhost:8000/api/turbines?eia_id=56291
+#[get("/api/turbines?<eia_id>")]
+async fn get_turbines(
+    repo: &State<SafeRepo>,
+    eia_id: Option<i32>,
+) -> Result<Json<Vec<Turbine>>, crate::Error> {
+    let mut repo = repo.lock().await;
+    let mut turbines = match eia_id {
+        Some(eia_id) => repo.get_turbines_by_eia_id(eia_id).await?,
+        None => repo.get_all_turbines().await?,
+    };
+    turbines.sort_by_key(|t| t.id);
+    let turbines = turbines.into_iter().map(|i| i.into()).collect();
+    Ok(Json(turbines))
+}
+
+/// curl -w "\n" -i -X GET http://localhost:8000/api/turbines/by-faa/2013-WTE-2956-OE
+/// Ranked so that it does not collide with /api/turbines/<case_id>/history.
+#[get("/api/turbines/by-faa/<asn>", rank = 1)]
+async fn get_turbines_by_faa_asn(
+    repo: &State<SafeRepo>,
+    asn: &str,
+) -> Result<Json<Vec<Turbine>>, crate::Error> {
     let mut repo = repo.lock().await;
-    let mut turbines = repo.get_all_turbines().await?;
-    turbines.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut turbines = repo.get_turbines_by_faa_asn(asn).await?;
+    turbines.sort_by_key(|t| t.id);
     let turbines = turbines.into_iter().map(|i| i.into()).collect();
     Ok(Json(turbines))
 }
+
+/// The history of the turbine with the USWTDB case_id, which is kept once
+/// the turbine has been decommissioned.
+/// curl -w "\n" -i -X GET http://localhost:8000/api/turbines/3073403/history
+#[get("/api/turbines/<case_id>/history")]
+async fn get_turbine_history(
+    repo: &State<SafeRepo>,
+    case_id: i32,
+) -> Result<Json<Vec<TurbineChange>>, crate::Error> {
+    let mut repo = repo.lock().await;
+    let history = repo.get_turbine_history(case_id).await?;
+    let history = history.into_iter().map(|i| i.into()).collect();
+    Ok(Json(history))
+}
+
+/// curl -w "\n" -i -X GET http://localhost:8000/api/releases
+#[get("/api/releases")]
+async fn get_releases(repo: &State<SafeRepo>) -> Result<Json<Vec<Release>>, crate::Error> {
+    let mut repo = repo.lock().await;
+    let mut releases = repo.get_all_releases().await?;
+    releases.sort_by_key(|r| r.id);
+    let releases = releases.into_iter().map(|i| i.into()).collect();
+    Ok(Json(releases))
+}
+
+/// curl -w "\n" -i -X GET http://localhost:8000/api/loadruns
+#[get("/api/loadruns")]
+async fn get_load_runs(repo: &State<SafeRepo>) -> Result<Json<Vec<LoadRun>>, crate::Error> {
+    let mut repo = repo.lock().await;
+    let mut load_runs = repo.get_all_load_runs().await?;
+    load_runs.sort_by_key(|r| r.id);
+    let load_runs = load_runs.into_iter().map(|i| i.into()).collect();
+    Ok(Json(load_runs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use repository::models;
+    use repository::InMemoryRepository;
+    use rocket::http::{ContentType, Status};
+    use rocket::local::asynchronous::Client;
+
+    fn seeded() -> InMemoryRepository {
+        let turbine = models::Turbine {
+            id: 1,
+            case_id: 3_000_001,
+            county_id: 1,
+            project_id: 1,
+            model_id: 1,
+            image_source_id: 1,
+            retrofit: false,
+            retrofit_year: None,
+            attributes_confidence_level: models::ConfidenceLevel::High,
+            location_confidence_level: models::ConfidenceLevel::High,
+            image_date: None,
+            latitude: "43.5".parse().unwrap(),
+            longitude: "-95.7".parse().unwrap(),
+            faa_ors: None,
+            faa_asn: Some("2013-WTE-2956-OE".to_string()),
+            usgs_pr_id: None,
+            eia_id: Some(56291),
+            hub_height: None,
+            total_height_to_tip: None,
+            raw_manufacturer: Some("Vestas".to_string()),
+            raw_model: Some("V90".to_string()),
+        };
+
+        InMemoryRepository {
+            image_sources: vec![
+                models::ImageSource { id: 2, name: "NAIP".to_string() },
+                models::ImageSource { id: 1, name: "Digital Globe".to_string() },
+            ],
+            states: vec![models::State {
+                id: "IA".to_string(),
+                name: "Iowa".to_string(),
+                capital: Some("Des Moines".to_string()),
+                population: None,
+                area_square_km: None,
+                state_type: models::StateType::State,
+            }],
+            counties: vec![models::County { id: 1, state_id: "IA".to_string(), name: "Franklin County".to_string(), fips: Some(19069) }],
+            projects: vec![models::Project {
+                id: 1,
+                name: "Crystal Lake".to_string(),
+                num_turbines: Some(1),
+                capacity_mw: Some("1.5".parse().unwrap()),
+                year: Some(2008),
+                state_id: Some("IA".to_string()),
+            }],
+            manufacturers: vec![models::Manufacturer { id: 1, name: "Vestas".to_string() }],
+            manufacturer_aliases: vec![models::ManufacturerAlias { id: 1, manufacturer_id: 1, alias: "Vestas Wind Systems".to_string() }],
+            models: vec![models::Model {
+                id: 1,
+                manufacturer_id: 1,
+                name: "V90".to_string(),
+                capacity_kw: Some(1500),
+                hub_height: None,
+                rotor_diameter: None,
+                rotor_swept_area: None,
+                total_height_to_tip: None,
+            }],
+            model_aliases: vec![models::ModelAlias {
+                id: 1,
+                manufacturer_id: 1,
+                alias: "V-90".to_string(),
+                model_name: "V90".to_string(),
+            }],
+            turbines: vec![turbine],
+            releases: vec![models::Release {
+                id: 1,
+                version: "4.1".to_string(),
+                release_date: Some("2021-05-28".parse().unwrap()),
+                file_name: "uswtdb_v4_1_20210528.zip".to_string(),
+                checksum: "abc".to_string(),
+                turbine_count: 1,
+                new_turbines: 1,
+                changed_turbines: 0,
+                decommissioned_turbines: 0,
+                loaded_at: "2021-06-01T00:00:00".parse().unwrap(),
+            }],
+            load_runs: vec![models::LoadRun {
+                id: 1,
+                started_at: "2021-06-01T00:00:00".parse().unwrap(),
+                finished_at: "2021-06-01T00:01:00".parse().unwrap(),
+                succeeded: true,
+                file_name: Some("uswtdb_v4_1_20210528.zip".to_string()),
+                version: Some("4.1".to_string()),
+                checksum: Some("abc".to_string()),
+                rejected_rows: 0,
+                warnings: 0,
+                error_message: None,
+                report: "{}".to_string(),
+            }],
+            turbine_history: vec![models::TurbineChange {
+                id: 1,
+                release_id: 1,
+                case_id: 3_000_001,
+                change_type: models::ChangeType::New,
+                attribute: None,
+                old_value: None,
+                new_value: None,
+            }],
+        }
+    }
+
+    async fn client() -> Client {
+        let pool = Pool::new(vec![Box::new(seeded())]);
+        Client::tracked(rocket(pool, &ServerSettings::default())).await.unwrap()
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned + Send + 'static>(client: &Client, uri: &str) -> T {
+        let response = client.get(uri.to_string()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok, "GET {}", uri);
+        response.into_json().await.unwrap()
+    }
+
+    async fn status(client: &Client, uri: &str) -> Status {
+        client.get(uri.to_string()).dispatch().await.status()
+    }
+
+    #[rocket::async_test]
+    async fn lists_image_sources_by_id() {
+        let client = client().await;
+        let image_sources: Vec<ImageSource> = get_json(&client, "/api/imagesources").await;
+        assert_eq!(image_sources.iter().map(|i| i.id).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[rocket::async_test]
+    async fn gets_image_source() {
+        let client = client().await;
+        let image_source: ImageSource = get_json(&client, "/api/imagesources/2").await;
+        assert_eq!(image_source.name, "NAIP");
+        assert_eq!(status(&client, "/api/imagesources/9").await, Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn updates_image_source() {
+        let client = client().await;
+        let response = client.put("/api/imagesources/1").header(ContentType::JSON).body(r#""Maxar""#).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let image_source: ImageSource = get_json(&client, "/api/imagesources/1").await;
+        assert_eq!(image_source.name, "Maxar");
+
+        let response = client.put("/api/imagesources/9").header(ContentType::JSON).body(r#""Maxar""#).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn lists_states_counties_and_projects() {
+        let client = client().await;
+        let states: Vec<results::State> = get_json(&client, "/api/states").await;
+        assert_eq!(states[0].name, "Iowa");
+        let counties: Vec<County> = get_json(&client, "/api/counties").await;
+        assert_eq!(counties.len(), 1);
+        let projects: Vec<Project> = get_json(&client, "/api/projects").await;
+        assert_eq!(projects[0].name, "Crystal Lake");
+    }
+
+    #[rocket::async_test]
+    async fn gets_county_by_fips() {
+        let client = client().await;
+        let county: County = get_json(&client, "/api/counties/19069").await;
+        assert_eq!(county.name, "Franklin County");
+        assert_eq!(status(&client, "/api/counties/1").await, Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn lists_manufacturers_with_aliases_and_models() {
+        let client = client().await;
+        let manufacturers: Vec<Manufacturer> = get_json(&client, "/api/manufacturers").await;
+        assert_eq!(manufacturers[0].aliases, vec!["Vestas Wind Systems".to_string()]);
+        let models: Vec<Model> = get_json(&client, "/api/models").await;
+        assert_eq!(models[0].name, "V90");
+        assert_eq!(models[0].aliases, vec!["V-90".to_string()]);
+    }
+
+    #[rocket::async_test]
+    async fn lists_turbines() {
+        let client = client().await;
+        let turbines: Vec<Turbine> = get_json(&client, "/api/turbines").await;
+        assert_eq!(turbines.len(), 1);
+        let turbines: Vec<Turbine> = get_json(&client, "/api/turbines?eia_id=56291").await;
+        assert_eq!(turbines.len(), 1);
+        let turbines: Vec<Turbine> = get_json(&client, "/api/turbines?eia_id=1").await;
+        assert!(turbines.is_empty());
+        let turbines: Vec<Turbine> = get_json(&client, "/api/turbines/by-faa/2013-WTE-2956-OE").await;
+        assert_eq!(turbines.len(), 1);
+    }
+
+    #[rocket::async_test]
+    async fn gets_turbine_history_by_case_id() {
+        let client = client().await;
+        let history: Vec<TurbineChange> = get_json(&client, "/api/turbines/3000001/history").await;
+        assert_eq!(history.len(), 1);
+        assert_eq!(status(&client, "/api/turbines/1/history").await, Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn decommissioned_turbine_keeps_its_history() {
+        let mut repo = seeded();
+        let decommissioned = [(2, models::ChangeType::New), (3, models::ChangeType::Decommissioned)];
+        repo.turbine_history.extend(decommissioned.iter().map(|&(id, change_type)| models::TurbineChange {
+            id,
+            release_id: id - 1,
+            case_id: 3_000_002,
+            change_type,
+            attribute: None,
+            old_value: None,
+            new_value: None,
+        }));
+        let client = Client::tracked(rocket(Pool::new(vec![Box::new(repo)]), &ServerSettings::default())).await.unwrap();
+
+        let history: Vec<TurbineChange> = get_json(&client, "/api/turbines/3000002/history").await;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].change_type, ChangeType::Decommissioned);
+    }
+
+    #[rocket::async_test]
+    async fn lists_releases_and_load_runs() {
+        let client = client().await;
+        let releases: Vec<Release> = get_json(&client, "/api/releases").await;
+        assert_eq!(releases[0].version, "4.1");
+        let load_runs: Vec<LoadRun> = get_json(&client, "/api/loadruns").await;
+        assert_eq!(load_runs.len(), 1);
+    }
+}
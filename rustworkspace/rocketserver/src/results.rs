@@ -1,3 +1,4 @@
+use rocket::serde::json::Value;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
@@ -61,6 +62,7 @@ pub struct County {
     pub id: i32,
     pub state_id: String,
     pub name: String,
+    pub fips: Option<i32>,
 }
 
 impl From<repository::models::County> for County {
@@ -69,6 +71,7 @@ impl From<repository::models::County> for County {
             id: val.id,
             state_id: val.state_id,
             name: val.name,
+            fips: val.fips,
         }
     }
 }
@@ -94,13 +97,16 @@ impl From<repository::models::ConfidenceLevel> for ConfidenceLevel {
 pub struct Manufacturer {
     pub id: i32,
     pub name: String,
+    /// Other names for the manufacturer found in the USWTDB.
+    pub aliases: Vec<String>,
 }
 
 impl From<repository::models::Manufacturer> for Manufacturer {
     fn from(val: repository::models::Manufacturer) -> Self {
         Self {
             id: val.id,
-            name: val.name
+            name: val.name,
+            aliases: Vec::new(),
         }
     }
 }
@@ -111,6 +117,8 @@ pub struct Project {
     pub name: String,
     pub num_turbines: Option<i16>,
     pub capacity_mw: Option<Decimal>,
+    pub year: Option<i16>,
+    pub state_id: Option<String>,
 }
 
 impl From<repository::models::Project> for Project {
@@ -120,6 +128,8 @@ impl From<repository::models::Project> for Project {
             name: val.name,
             num_turbines: val.num_turbines,
             capacity_mw: val.capacity_mw,
+            year: val.year,
+            state_id: val.state_id,
         }
     }
 }
@@ -134,6 +144,8 @@ pub struct Model {
     pub rotor_diameter: Option<Decimal>,
     pub rotor_swept_area: Option<Decimal>,
     pub total_height_to_tip: Option<Decimal>,
+    /// Other names for the model found in the USWTDB.
+    pub aliases: Vec<String>,
 }
 
 impl From<repository::models::Model> for Model {
@@ -147,6 +159,7 @@ impl From<repository::models::Model> for Model {
             rotor_diameter: val.rotor_diameter,
             rotor_swept_area: val.rotor_swept_area,
             total_height_to_tip: val.total_height_to_tip,
+            aliases: Vec::new(),
         }
     }
 }
@@ -154,6 +167,7 @@ impl From<repository::models::Model> for Model {
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Turbine {
     pub id: i32,
+    pub case_id: i32,
     pub county_id: i32,
     pub project_id: i32,
     pub model_id: i32,
@@ -165,12 +179,21 @@ pub struct Turbine {
     pub image_date: Option<String>,
     pub latitude: Decimal,
     pub longitude: Decimal,
+    pub faa_ors: Option<String>,
+    pub faa_asn: Option<String>,
+    pub usgs_pr_id: Option<i32>,
+    pub eia_id: Option<i32>,
+    pub hub_height: Option<Decimal>,
+    pub total_height_to_tip: Option<Decimal>,
+    pub raw_manufacturer: Option<String>,
+    pub raw_model: Option<String>,
 }
 
 impl From<repository::models::Turbine> for Turbine {
     fn from(val: repository::models::Turbine) -> Self {
         Self {
             id: val.id,
+            case_id: val.case_id,
             county_id: val.county_id,
             project_id: val.project_id,
             model_id: val.model_id,
@@ -182,6 +205,124 @@ impl From<repository::models::Turbine> for Turbine {
             image_date: val.image_date.map(|d| d.format("%Y-%m-%d").to_string()),
             latitude: val.latitude,
             longitude: val.longitude,
+            faa_ors: val.faa_ors,
+            faa_asn: val.faa_asn,
+            usgs_pr_id: val.usgs_pr_id,
+            eia_id: val.eia_id,
+            hub_height: val.hub_height,
+            total_height_to_tip: val.total_height_to_tip,
+            raw_manufacturer: val.raw_manufacturer,
+            raw_model: val.raw_model,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Release {
+    pub id: i32,
+    pub version: String,
+    pub release_date: Option<String>,
+    pub file_name: String,
+    pub checksum: String,
+    pub turbine_count: i32,
+    pub new_turbines: i32,
+    pub changed_turbines: i32,
+    pub decommissioned_turbines: i32,
+    pub loaded_at: String,
+}
+
+impl From<repository::models::Release> for Release {
+    fn from(val: repository::models::Release) -> Self {
+        Self {
+            id: val.id,
+            version: val.version,
+            release_date: val.release_date.map(|d| d.format("%Y-%m-%d").to_string()),
+            file_name: val.file_name,
+            checksum: val.checksum,
+            turbine_count: val.turbine_count,
+            new_turbines: val.new_turbines,
+            changed_turbines: val.changed_turbines,
+            decommissioned_turbines: val.decommissioned_turbines,
+            loaded_at: val.loaded_at.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        }
+    }
+}
+
+/// A run of the dataloader's load command, with its report as JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoadRun {
+    pub id: i32,
+    pub started_at: String,
+    pub finished_at: String,
+    pub succeeded: bool,
+    pub file_name: Option<String>,
+    pub version: Option<String>,
+    pub checksum: Option<String>,
+    pub rejected_rows: i32,
+    pub warnings: i32,
+    pub error_message: Option<String>,
+    pub report: Value,
+}
+
+impl From<repository::models::LoadRun> for LoadRun {
+    fn from(val: repository::models::LoadRun) -> Self {
+        // A report that is not valid JSON is passed on as a string.
+        let report = rocket::serde::json::from_str(&val.report).unwrap_or(Value::String(val.report));
+
+        Self {
+            id: val.id,
+            started_at: val.started_at.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            finished_at: val.finished_at.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            succeeded: val.succeeded,
+            file_name: val.file_name,
+            version: val.version,
+            checksum: val.checksum,
+            rejected_rows: val.rejected_rows,
+            warnings: val.warnings,
+            error_message: val.error_message,
+            report,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChangeType {
+    New,
+    Changed,
+    Decommissioned,
+}
+
+impl From<repository::models::ChangeType> for ChangeType {
+    fn from(val: repository::models::ChangeType) -> Self {
+        match val {
+            repository::models::ChangeType::New => Self::New,
+            repository::models::ChangeType::Changed => Self::Changed,
+            repository::models::ChangeType::Decommissioned => Self::Decommissioned,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TurbineChange {
+    pub id: i32,
+    pub release_id: i32,
+    pub case_id: i32,
+    pub change_type: ChangeType,
+    pub attribute: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+impl From<repository::models::TurbineChange> for TurbineChange {
+    fn from(val: repository::models::TurbineChange) -> Self {
+        Self {
+            id: val.id,
+            release_id: val.release_id,
+            case_id: val.case_id,
+            change_type: val.change_type.into(),
+            attribute: val.attribute,
+            old_value: val.old_value,
+            new_value: val.new_value,
         }
     }
 }
@@ -0,0 +1,438 @@
+use connection_string::AdoNetString;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// The configuration file read when neither `--config` nor `USWPS_CONFIG`
+/// is given, if it exists in the working directory.
+pub const DEFAULT_CONFIG_FILE: &str = "uswindpowerstats.toml";
+
+/// What secrets are replaced with by `--print-config`.
+const REDACTED: &str = "********";
+
+#[derive(Debug)]
+pub enum Error {
+    File(PathBuf, String),
+    Invalid(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::File(path, msg) => write!(f, "{}: {}", path.display(), msg),
+            Error::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The settings shared by the dataloader and the REST API. Each one is taken
+/// from, in increasing order of precedence, its default, the TOML
+/// configuration file, an environment variable and a command line flag.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub database: DatabaseSettings,
+    pub load: LoadSettings,
+    pub server: ServerSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSettings {
+    /// `sqlite://path/to/file.db`, or an ADO-style MS SQL connection string.
+    pub url: String,
+    /// Added to an MS SQL connection string that has no password of its own.
+    /// It is a secret, so it is only read from `USWPS_DATABASE_PASSWORD` or
+    /// from `password_file`; one in the configuration file, such as the
+    /// redacted one `--print-config` writes, is ignored.
+    #[serde(deserialize_with = "ignore_password", skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_file: Option<PathBuf>,
+    /// The number of connections the REST API keeps open.
+    pub pool_size: usize,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        DatabaseSettings {
+            url: "server=tcp:localhost,1433;User Id=SA;Initial Catalog=UsWindPowerStats;TrustServerCertificate=true".to_owned(),
+            password: None,
+            password_file: None,
+            pool_size: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoadSettings {
+    /// The number of turbines written to the database at a time.
+    pub batch_size: usize,
+    /// The number of turbines the file reader may get ahead of the database.
+    pub channel_capacity: usize,
+    /// The load fails if more than this percentage of the rows in a file fail validation.
+    pub max_rejected_percent: f64,
+    /// Where to write the JSON report of each load.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report_file: Option<PathBuf>,
+    /// Whether to record each load in the LoadRun table.
+    pub record_runs: bool,
+    /// Whether to commit each batch with a checkpoint, so that a load that
+    /// stops part way can be resumed.
+    pub checkpoint: bool,
+    /// Whether to continue a checkpointed load from its last checkpoint. It
+    /// only applies to one run, so is only set by `--resume`.
+    #[serde(skip)]
+    pub resume: bool,
+    /// The number of times a load is retried after a transient error such as
+    /// a dropped connection.
+    pub retries: u32,
+    /// The wait before the first retry, doubled for each retry after it.
+    pub retry_delay_ms: u64,
+    /// The number of connections dimension rows are written on at once. Above
+    /// 1, each dimension's rows are committed as they are written rather than
+    /// with the rest of the load, so it needs `commit_dimensions_early`.
+    pub dimension_parallelism: usize,
+    /// Whether dimension rows may be committed before the rest of the load,
+    /// so that a failed load keeps them, as writing them on several
+    /// connections requires.
+    pub commit_dimensions_early: bool,
+}
+
+impl Default for LoadSettings {
+    fn default() -> Self {
+        LoadSettings {
+            batch_size: 2_000,
+            channel_capacity: 10_000,
+            max_rejected_percent: 1.0,
+            report_file: None,
+            record_runs: false,
+            checkpoint: false,
+            resume: false,
+            retries: 3,
+            retry_delay_ms: 1_000,
+            dimension_parallelism: 1,
+            commit_dimensions_early: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    pub address: String,
+    pub port: u16,
+    /// The origins browsers may call the API from; `*` allows any.
+    pub cors_origins: Vec<String>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            address: "127.0.0.1".to_owned(),
+            port: 8000,
+            cors_origins: vec!["*".to_owned()],
+        }
+    }
+}
+
+/// The command line flags common to both programs. Flatten this into the
+/// program's own options and pass it to `Settings::load`.
+#[derive(StructOpt, Debug, Default)]
+pub struct ConfigArgs {
+    /// The TOML configuration file [default: uswindpowerstats.toml, if it exists].
+    #[structopt(long, parse(from_os_str), env = "USWPS_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// The database to use, overriding database.url.
+    #[structopt(long)]
+    pub database_url: Option<String>,
+    /// The number of database connections, overriding database.pool_size.
+    #[structopt(long)]
+    pub pool_size: Option<usize>,
+    /// Print the settings in effect, with secrets redacted, and exit.
+    #[structopt(long)]
+    pub print_config: bool,
+}
+
+impl Settings {
+    /// Builds the settings from the defaults, the configuration file, the
+    /// environment and the command line, then reads the password file.
+    pub fn load(args: &ConfigArgs) -> Result<Self, Error> {
+        let mut settings = match &args.config {
+            Some(path) => Settings::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Settings::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Settings::default(),
+        };
+
+        settings.apply_env(|name| std::env::var(name).ok())?;
+
+        if let Some(url) = &args.database_url {
+            settings.database.url = url.clone();
+        }
+        if let Some(pool_size) = args.pool_size {
+            settings.database.pool_size = pool_size;
+        }
+
+        if settings.database.password.is_none() {
+            if let Some(path) = &settings.database.password_file {
+                let password = std::fs::read_to_string(path).map_err(|err| Error::File(path.clone(), err.to_string()))?;
+                settings.database.password = Some(password.trim_end().to_owned());
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Reads a configuration file. Settings it does not give keep their defaults.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).map_err(|err| Error::File(path.to_owned(), err.to_string()))?;
+        toml::from_str(&text).map_err(|err| Error::File(path.to_owned(), err.to_string()))
+    }
+
+    /// Overrides settings from the environment. `DATABASE_URL` takes
+    /// precedence over the older, MS SQL specific, `MSSQL_CONNECTION_STRING`.
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), Error> {
+        if let Some(url) = var("DATABASE_URL").or_else(|| var("MSSQL_CONNECTION_STRING")) {
+            self.database.url = url;
+        }
+        if let Some(password) = var("USWPS_DATABASE_PASSWORD") {
+            self.database.password = Some(password);
+        }
+        if let Some(path) = var("USWPS_DATABASE_PASSWORD_FILE") {
+            self.database.password_file = Some(path.into());
+        }
+        parse_env(&var, "USWPS_DATABASE_POOL_SIZE", &mut self.database.pool_size)?;
+        parse_env(&var, "USWPS_LOAD_BATCH_SIZE", &mut self.load.batch_size)?;
+        parse_env(&var, "USWPS_LOAD_CHANNEL_CAPACITY", &mut self.load.channel_capacity)?;
+        parse_env(&var, "USWPS_LOAD_MAX_REJECTED_PERCENT", &mut self.load.max_rejected_percent)?;
+        if let Some(path) = var("USWPS_LOAD_REPORT_FILE") {
+            self.load.report_file = Some(path.into());
+        }
+        parse_env(&var, "USWPS_LOAD_RECORD_RUNS", &mut self.load.record_runs)?;
+        parse_env(&var, "USWPS_LOAD_CHECKPOINT", &mut self.load.checkpoint)?;
+        parse_env(&var, "USWPS_LOAD_RETRIES", &mut self.load.retries)?;
+        parse_env(&var, "USWPS_LOAD_RETRY_DELAY_MS", &mut self.load.retry_delay_ms)?;
+        parse_env(&var, "USWPS_LOAD_DIMENSION_PARALLELISM", &mut self.load.dimension_parallelism)?;
+        parse_env(&var, "USWPS_LOAD_COMMIT_DIMENSIONS_EARLY", &mut self.load.commit_dimensions_early)?;
+        if let Some(address) = var("USWPS_SERVER_ADDRESS") {
+            self.server.address = address;
+        }
+        parse_env(&var, "USWPS_SERVER_PORT", &mut self.server.port)?;
+        if let Some(origins) = var("USWPS_SERVER_CORS_ORIGINS") {
+            self.server.cors_origins = origins.split(',').map(|o| o.trim().to_owned()).filter(|o| !o.is_empty()).collect();
+        }
+
+        Ok(())
+    }
+
+    /// The connection string to open the database with, including the
+    /// password if one was given separately.
+    pub fn connection_string(&self) -> String {
+        let url = &self.database.url;
+        match &self.database.password {
+            Some(password) if !url.starts_with("sqlite:") && !has_password(url) => {
+                let separator = if url.is_empty() || url.ends_with(';') { "" } else { ";" };
+                format!("{}{}Password={};", url, separator, escape_value(password))
+            }
+            _ => url.clone(),
+        }
+    }
+
+    /// The settings as TOML, with the password and any password in the
+    /// database URL replaced by asterisks.
+    pub fn to_redacted_toml(&self) -> Result<String, Error> {
+        let mut settings = self.clone();
+        settings.database.url = redact_url(&settings.database.url);
+        if settings.database.password.is_some() {
+            settings.database.password = Some(REDACTED.to_owned());
+        }
+
+        toml::to_string(&settings).map_err(|err| Error::Invalid(err.to_string()))
+    }
+}
+
+fn parse_env<T: FromStr>(var: impl Fn(&str) -> Option<String>, name: &str, value: &mut T) -> Result<(), Error> where T::Err: std::fmt::Display {
+    if let Some(text) = var(name) {
+        *value = text.trim().parse().map_err(|err| Error::Invalid(format!("{}={}: {}", name, text, err)))?;
+    }
+
+    Ok(())
+}
+
+fn ignore_password<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    IgnoredAny::deserialize(deserializer)?;
+    Ok(None)
+}
+
+/// Whether an ADO-style connection string has a password of its own. It is
+/// parsed the way tiberius's `Config::from_ado_string` parses it, so a `;` in
+/// a `{}` escaped value is not taken for the end of it.
+fn has_password(url: &str) -> bool {
+    match url.parse::<AdoNetString>() {
+        Ok(ado) => ado.contains_key("password") || ado.contains_key("pwd"),
+        Err(_) => false,
+    }
+}
+
+/// Escapes a connection string value that would otherwise end early or be
+/// trimmed, with `{}` unless it contains a `}` itself.
+fn escape_value(value: &str) -> String {
+    let plain = !value.contains(&[';', '=', '{', '}', '"', '\''][..]) && value.trim() == value;
+    if plain {
+        value.to_owned()
+    } else if !value.contains('}') {
+        format!("{{{}}}", value)
+    } else {
+        format!("\"{}\"", value)
+    }
+}
+
+/// Replaces any password in an ADO-style connection string with asterisks.
+/// The string is written back with its keys lower case and in order, and one
+/// that cannot be parsed is redacted completely, in case it has a password.
+fn redact_url(url: &str) -> String {
+    if url.starts_with("sqlite:") {
+        return url.to_owned();
+    }
+
+    match url.parse::<AdoNetString>() {
+        Ok(ado) if ado.contains_key("password") || ado.contains_key("pwd") => ado
+            .iter()
+            .map(|(key, value)| match key.as_str() {
+                "password" | "pwd" => (key, REDACTED.to_owned()),
+                _ => (key, escape_value(value)),
+            })
+            .collect::<BTreeMap<_, _>>()
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(";"),
+        Ok(_) => url.to_owned(),
+        Err(_) => REDACTED.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars = vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<HashMap<_, _>>();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn config_file(name: &str, text: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("uswps-settings-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn the_environment_overrides_the_file_and_the_file_the_defaults() {
+        let path = config_file("env", "[database]\nurl = \"sqlite://file.db\"\npool_size = 2\n\n[load]\nbatch_size = 10\n");
+        let mut settings = Settings::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        settings.apply_env(env(&[("USWPS_DATABASE_POOL_SIZE", "8"), ("USWPS_SERVER_CORS_ORIGINS", "http://a, ,http://b"),
+            ("USWPS_LOAD_COMMIT_DIMENSIONS_EARLY", "true")])).unwrap();
+
+        assert_eq!(settings.database.url, "sqlite://file.db");
+        assert_eq!(settings.database.pool_size, 8);
+        assert_eq!(settings.load.batch_size, 10);
+        assert_eq!(settings.load.channel_capacity, LoadSettings::default().channel_capacity);
+        assert_eq!(settings.server.cors_origins, vec!["http://a", "http://b"]);
+        assert!(settings.load.commit_dimensions_early);
+    }
+
+    #[test]
+    fn database_url_takes_precedence_over_the_mssql_connection_string() {
+        let mut settings = Settings::default();
+        settings.apply_env(env(&[("MSSQL_CONNECTION_STRING", "server=old"), ("DATABASE_URL", "sqlite://new.db")])).unwrap();
+        assert_eq!(settings.database.url, "sqlite://new.db");
+
+        let mut settings = Settings::default();
+        settings.apply_env(env(&[("MSSQL_CONNECTION_STRING", "server=old")])).unwrap();
+        assert_eq!(settings.database.url, "server=old");
+    }
+
+    #[test]
+    fn a_bad_environment_value_is_an_error() {
+        let err = Settings::default().apply_env(env(&[("USWPS_LOAD_BATCH_SIZE", "many")])).unwrap_err();
+        assert!(err.to_string().starts_with("USWPS_LOAD_BATCH_SIZE=many: "), "{}", err);
+    }
+
+    #[test]
+    fn the_command_line_overrides_the_file() {
+        let path = config_file("args", "[database]\nurl = \"sqlite://file.db\"\npool_size = 2\n");
+        let args = ConfigArgs { config: Some(path.clone()), database_url: Some("sqlite://args.db".to_owned()), pool_size: Some(6), print_config: false };
+        let settings = Settings::load(&args).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(settings.database.url, "sqlite://args.db");
+        assert_eq!(settings.database.pool_size, 6);
+    }
+
+    #[test]
+    fn an_unknown_setting_is_an_error() {
+        let path = config_file("unknown", "[load]\nbatch_sise = 10\n");
+        let err = Settings::from_file(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("batch_sise"), "{}", err);
+    }
+
+    #[test]
+    fn a_separate_password_is_added_to_a_connection_string_without_one() {
+        let mut settings = Settings::default();
+        settings.database.url = "server=tcp:localhost,1433;User Id=SA".to_owned();
+        settings.database.password = Some("p;w=d".to_owned());
+
+        let connection_string = settings.connection_string();
+        assert_eq!(connection_string, "server=tcp:localhost,1433;User Id=SA;Password={p;w=d};");
+        assert_eq!(connection_string.parse::<AdoNetString>().unwrap()["password"], "p;w=d");
+    }
+
+    #[test]
+    fn a_connection_string_keeps_its_own_escaped_password() {
+        let mut settings = Settings::default();
+        settings.database.url = "server=localhost;Password={a;b}".to_owned();
+        settings.database.password = Some("other".to_owned());
+        assert_eq!(settings.connection_string(), "server=localhost;Password={a;b}");
+
+        settings.database.url = "sqlite://file.db".to_owned();
+        assert_eq!(settings.connection_string(), "sqlite://file.db");
+    }
+
+    #[test]
+    fn redaction_hides_the_passwords() {
+        let mut settings = Settings::default();
+        settings.database.url = "Server=localhost;PWD={se;cret};User Id=SA".to_owned();
+        settings.database.password = Some("hunter2".to_owned());
+
+        let toml = settings.to_redacted_toml().unwrap();
+        assert!(!toml.contains("se;cret") && !toml.contains("cret") && !toml.contains("hunter2"), "{}", toml);
+        assert!(toml.contains("url = \"pwd=********;server=localhost;user id=SA\""), "{}", toml);
+        assert!(toml.contains("password = \"********\""), "{}", toml);
+
+        assert_eq!(redact_url("server=localhost;User Id=SA"), "server=localhost;User Id=SA");
+        assert_eq!(redact_url("server={unclosed"), REDACTED);
+    }
+
+    #[test]
+    fn printed_settings_read_back_without_the_password() {
+        let mut settings = Settings::default();
+        settings.database.password = Some("hunter2".to_owned());
+        settings.load.batch_size = 7;
+
+        let read: Settings = toml::from_str(&settings.to_redacted_toml().unwrap()).unwrap();
+        assert_eq!(read.database.password, None);
+        assert_eq!(read.database.url, settings.database.url);
+        assert_eq!(read.load.batch_size, 7);
+    }
+}